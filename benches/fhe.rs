@@ -0,0 +1,324 @@
+use std::cell::OnceCell;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tfhe::prelude::*;
+use tfhe::{set_server_key, ClientKey, FheUint16, FheUint32, FheUint64, FheUint8};
+
+use schnorr_fhe::backend::{FheUint, Tfhe};
+use schnorr_fhe::constants;
+use schnorr_fhe::cost::{self, OpTimings, PoseidonParams, PoseidonVariant};
+use schnorr_fhe::griffin::GriffinP8;
+use schnorr_fhe::hash::FheHash;
+use schnorr_fhe::lut;
+use schnorr_fhe::mimc::MimcP8;
+use schnorr_fhe::modint::{ModInt, Modulus};
+use schnorr_fhe::poseidon::Lane;
+use schnorr_fhe::poseidon_p8::{self, PoseidonP8, PoseidonP8Rf2Rp1};
+use schnorr_fhe::profile::BUILTIN_PROFILES;
+use schnorr_fhe::rescue::RescueP8;
+use schnorr_fhe::schnorr::{self, MessageVisibility, NonceMode};
+use schnorr_fhe::utils;
+
+/*
+ * Benchmarks of the FHE building blocks and of a full signature.
+ *
+ * Run with `cargo bench --bench fhe` (or e.g. `cargo bench --bench fhe -- ops/`
+ * for one group). Criterion writes machine-readable results to
+ * `target/criterion/<group>/<bench>/new/estimates.json`; to track regressions
+ * across tfhe versions, save a named baseline per version and compare:
+ *   cargo bench --bench fhe -- --save-baseline tfhe-0.6.1
+ *   cargo bench --bench fhe -- --baseline tfhe-0.6.1
+ *
+ * FHE ops take milliseconds to minutes, so every group uses the minimum of
+ * 10 samples. The `lut_vs_arith` group compares the WoPBS lookups of the
+ * 8-bit field with the same maps as FHE mults and `%`. The `estimate` group prints the running times that `cost.rs`
+ * predicts from measured per-op timings, e.g. to compare with the `hashes`
+ * and `sign` groups. The `profiles` group generates keys for every built-in
+ * `FheProfile`, so it runs last.
+ */
+
+const SAMPLE_SIZE: usize = 10;
+
+// add / mul / mod at one bit width, on fresh encryptions of `a` and `b`
+fn bench_ops<E, C>(c: &mut Criterion, client_key: &ClientKey, bits: u32, a: C, b: C)
+where
+    E: FheUint<C> + FheEncrypt<C, ClientKey>,
+    C: Copy,
+{
+    let a_enc = E::encrypt(a, client_key);
+    let b_enc = E::encrypt(b, client_key);
+    let mut group = c.benchmark_group(format!("ops/u{}", bits));
+    group.sample_size(SAMPLE_SIZE);
+
+    let input = || a_enc.clone();
+    group.bench_function("add", |bench| {
+        bench.iter_batched(input, |x| x + &b_enc, BatchSize::SmallInput)
+    });
+    group.bench_function("scalar_add", |bench| {
+        bench.iter_batched(input, |x| x + b, BatchSize::SmallInput)
+    });
+    group.bench_function("mul", |bench| {
+        bench.iter_batched(input, |x| x * &b_enc, BatchSize::SmallInput)
+    });
+    group.bench_function("scalar_mul", |bench| {
+        bench.iter_batched(input, |x| x * b, BatchSize::SmallInput)
+    });
+    group.bench_function("scalar_mod", |bench| {
+        bench.iter_batched(input, |x| x % b, BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn bench_casts(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("cast");
+    group.sample_size(SAMPLE_SIZE);
+
+    let x8 = FheUint8::encrypt(100u8, client_key);
+    let x16 = FheUint16::encrypt(100u16, client_key);
+    let x32 = FheUint32::encrypt(100u32, client_key);
+    let x64 = FheUint64::encrypt(100u64, client_key);
+    group.bench_function("u8_to_u16", |bench| {
+        bench.iter(|| -> FheUint16 { x8.clone().cast_into() })
+    });
+    group.bench_function("u16_to_u8", |bench| {
+        bench.iter(|| -> FheUint8 { x16.clone().cast_into() })
+    });
+    group.bench_function("u32_to_u64", |bench| {
+        bench.iter(|| -> FheUint64 { x32.clone().cast_into() })
+    });
+    group.bench_function("u64_to_u32", |bench| {
+        bench.iter(|| -> FheUint32 { x64.clone().cast_into() })
+    });
+    group.finish();
+}
+
+// The Poseidon S-box x^alpha (mod p) on the 32-bit field
+fn bench_modexp(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("modexp");
+    group.sample_size(SAMPLE_SIZE);
+
+    let p32 = Modulus::new(constants::POSEIDON_P_32 as u64).unwrap();
+    let x = ModInt::from_reduced(FheUint64::encrypt(123456789u64, client_key), p32);
+    group.bench_function("sbox_u64", |bench| {
+        bench.iter(|| utils::fhe_modexp_64(&x, constants::POSEIDON_ALPHA as u64))
+    });
+    group.finish();
+}
+
+// The S-box x^alpha (mod p) and the reduction x (mod p) on the 8-bit field, as
+// one WoPBS lookup (see `lut.rs`) and as FHE mults and `%`: with the 16-bit
+// container of the arithmetic Poseidon and with the 64-bit one
+fn bench_lut(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("lut_vs_arith");
+    group.sample_size(SAMPLE_SIZE);
+
+    let (p8, alpha) = (constants::POSEIDON_P_8, constants::POSEIDON_ALPHA);
+    let x8 = FheUint8::encrypt(123u8, client_key);
+    let x16 = ModInt::from_reduced(
+        FheUint16::encrypt(123u16, client_key),
+        Modulus::new(p8 as u16).unwrap(),
+    );
+    let x64 = ModInt::from_reduced(
+        FheUint64::encrypt(123u64, client_key),
+        Modulus::new(p8 as u64).unwrap(),
+    );
+    group.bench_function("sbox/lut_u8", |bench| {
+        bench.iter(|| lut::fhe_lut_modexp_8(&x8, alpha, p8))
+    });
+    group.bench_function("sbox/arith_u16", |bench| {
+        bench.iter(|| utils::fhe_modexp_16(&x16, alpha as u16))
+    });
+    group.bench_function("sbox/arith_u64", |bench| {
+        bench.iter(|| utils::fhe_modexp_64(&x64, alpha as u64))
+    });
+
+    let y8 = FheUint8::encrypt(250u8, client_key);
+    let y64 = FheUint64::encrypt(250u64, client_key);
+    group.bench_function("reduce/lut_u8", |bench| {
+        bench.iter(|| lut::fhe_lut_mod_8(&y8, p8))
+    });
+    group.bench_function("reduce/rem_u8", |bench| {
+        bench.iter_batched(|| y8.clone(), |y| y % p8, BatchSize::SmallInput)
+    });
+    group.bench_function("reduce/rem_u64", |bench| {
+        bench.iter_batched(|| y64.clone(), |y| y % p8 as u64, BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+// One full round and one MDS multiplication of the LUT Poseidon (8-bit field)
+fn bench_poseidon(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("poseidon_p8_lut");
+    group.sample_size(SAMPLE_SIZE);
+
+    let inputs: Vec<FheUint8> = [1u8, 0, 2, 8]
+        .iter()
+        .map(|x| FheUint8::encrypt(*x, client_key))
+        .collect();
+    let rc = [11u16, 22, 33, 44];
+    group.bench_function("full_round", |bench| {
+        bench.iter_batched(
+            || inputs.iter().cloned().map(Lane::Enc).collect(),
+            |state| poseidon_p8::poseidon_p8_lut_round::<Tfhe>(state, &rc, true),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("mds", |bench| {
+        bench.iter_batched(
+            || inputs.clone(),
+            |x| {
+                lut::fhe_lut_apply_matrix_8::<Tfhe, { constants::POSEIDON_T }>(
+                    x,
+                    &poseidon_p8::T4_P8_MDS_MATRIX,
+                    constants::POSEIDON_P_8,
+                )
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+// One hash of 4 encrypted inputs (8-bit field) with each `FheHash`
+fn bench_hash<H: FheHash<Enc = FheUint8>>(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("hashes");
+    group.sample_size(SAMPLE_SIZE);
+
+    let inputs: Vec<FheUint8> = [1u8, 0, 2, 8]
+        .iter()
+        .map(|x| FheUint8::encrypt(*x, client_key))
+        .collect();
+    group.bench_function(H::NAME, |bench| bench.iter(|| H::hash_enc(&inputs)));
+    group.finish();
+}
+
+// The predicted Poseidon and signing times; the benches time the op counting
+// itself, and each prints its prediction once. The per-op timings are only
+// measured if one of these benches runs
+fn bench_estimate(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("estimate");
+    group.sample_size(SAMPLE_SIZE);
+
+    let timings = OnceCell::new();
+    let report = |what: String, counts: &cost::OpCounts| {
+        let timings = timings.get_or_init(|| OpTimings::measure(client_key));
+        let estimate = timings.estimate(counts).unwrap();
+        println!(
+            "{}: {} FHE ops, estimated {:.2?}",
+            what,
+            counts.total(),
+            estimate
+        );
+    };
+    for variant in [
+        PoseidonVariant::P8Lut,
+        PoseidonVariant::P8,
+        PoseidonVariant::P32,
+    ] {
+        for params in [
+            PoseidonParams::rf2_rp1(variant),
+            PoseidonParams::full(variant),
+        ] {
+            let id = format!(
+                "poseidon/{:?}/{}_{}",
+                variant, params.r_full, params.r_partial
+            );
+            let mut reported = false;
+            group.bench_function(id, |bench| {
+                if !reported {
+                    report(
+                        format!("{:?}", params),
+                        &cost::count_poseidon(&params, 4).unwrap(),
+                    );
+                    reported = true;
+                }
+                bench.iter(|| cost::count_poseidon(&params, 4))
+            });
+        }
+    }
+    for params in [
+        PoseidonParams::rf2_rp1(PoseidonVariant::P8Lut),
+        PoseidonParams::full(PoseidonVariant::P8Lut),
+    ] {
+        for msg_len in [None, Some(32)] {
+            let msg = msg_len.map_or("public".to_string(), |n| format!("enc{}", n));
+            let id = format!("sign/{}_{}/{}", params.r_full, params.r_partial, msg);
+            let mut reported = false;
+            group.bench_function(id, |bench| {
+                if !reported {
+                    let what =
+                        format!("sign {:?} (encrypted message bytes: {:?})", params, msg_len);
+                    report(what, &cost::count_sign(&params, msg_len).unwrap());
+                    reported = true;
+                }
+                bench.iter(|| cost::count_sign(&params, msg_len))
+            });
+        }
+    }
+    group.finish();
+}
+
+// A full signature with the reduced-round hash
+fn bench_sign(c: &mut Criterion, client_key: &ClientKey) {
+    let mut group = c.benchmark_group("sign");
+    group.sample_size(SAMPLE_SIZE);
+
+    let (x_sch, _y_sch) = schnorr::keygen().unwrap();
+    let x_sch_enc: FheUint8 = x_sch.encrypt(client_key);
+    let msg: &[u8] = b"benchmark message";
+    group.bench_function("public_msg_rf2_rp1", |bench| {
+        bench.iter(|| {
+            let msg = MessageVisibility::Public(msg);
+            schnorr::sign_enc::<PoseidonP8Rf2Rp1>(&x_sch_enc, msg, NonceMode::Deterministic)
+        })
+    });
+    group.finish();
+}
+
+// The signature of `bench_sign` under every built-in tfhe parameter profile
+fn bench_profiles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("profiles");
+    group.sample_size(SAMPLE_SIZE);
+
+    let (x_sch, _y_sch) = schnorr::keygen().unwrap();
+    let msg: &[u8] = b"benchmark message";
+    for profile in BUILTIN_PROFILES {
+        let (client_key, server_key, _public_key) = utils::init_keys_with(&profile);
+        set_server_key(server_key);
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
+        group.bench_function(
+            format!("sign_public_msg_rf2_rp1/{}", profile.name()),
+            |bench| {
+                bench.iter(|| {
+                    let msg = MessageVisibility::Public(msg);
+                    schnorr::sign_enc::<PoseidonP8Rf2Rp1>(&x_sch_enc, msg, NonceMode::Deterministic)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_all(c: &mut Criterion) {
+    let (client_key, server_key, _public_key) = utils::init_keys();
+    set_server_key(server_key);
+
+    bench_ops::<FheUint8, u8>(c, &client_key, 8, 100, 7);
+    bench_ops::<FheUint16, u16>(c, &client_key, 16, 1344, 7);
+    bench_ops::<FheUint32, u32>(c, &client_key, 32, 1344, 7);
+    bench_ops::<FheUint64, u64>(c, &client_key, 64, 1344, 7);
+    bench_casts(c, &client_key);
+    bench_modexp(c, &client_key);
+    bench_lut(c, &client_key);
+    bench_poseidon(c, &client_key);
+    bench_hash::<PoseidonP8>(c, &client_key);
+    bench_hash::<RescueP8>(c, &client_key);
+    bench_hash::<MimcP8>(c, &client_key);
+    bench_hash::<GriffinP8>(c, &client_key);
+    bench_sign(c, &client_key);
+    bench_estimate(c, &client_key);
+    bench_profiles(c);
+}
+
+criterion_group!(benches, bench_all);
+criterion_main!(benches);
@@ -0,0 +1,170 @@
+"""
+Parameter generation and reference implementations for the alternative
+arithmetization-friendly hashes in `src/{rescue,mimc,griffin}.rs`.
+
+Constants are derived deterministically from SHAKE256 over a domain string, so
+re-running this script reproduces the hardcoded Rust constants exactly. The
+reference hashes print the test vectors used by the Rust unit tests.
+
+NOTE: round numbers are toy values for the 8-bit dev field (p = 199); they do
+not carry any security claim.
+
+Usage (standard library only):
+    python scripts/arith_hash_gen.py --p 199 --t 4
+"""
+import argparse
+import hashlib
+import logging
+
+logger = logging.getLogger(__name__)
+
+
+ALPHA = 5
+RESCUE_ROUNDS = 4
+MIMC_ROUNDS = 8
+GRIFFIN_ROUNDS = 8
+
+
+def field_elements(domain: str, p: int, count: int):
+    # Rejection-sample `count` uniform field elements from SHAKE256(domain)
+    n_bytes = (p.bit_length() + 7) // 8
+    mask = (1 << p.bit_length()) - 1
+    out = []
+    stream = hashlib.shake_256(domain.encode()).digest(64 * count * n_bytes)
+    i = 0
+    while len(out) < count:
+        if i + n_bytes > len(stream):
+            stream = hashlib.shake_256(domain.encode()).digest(2 * len(stream))
+        x = int.from_bytes(stream[i:i + n_bytes], "little") & mask
+        i += n_bytes
+        if x < p:
+            out.append(x)
+    return out
+
+
+def is_square(x, p):
+    return x == 0 or pow(x, (p - 1) // 2, p) == 1
+
+
+def cauchy_mds(p, t):
+    # M[i][j] = 1 / (x_i + y_j) with x_i = i, y_j = t + j (all distinct, nonzero)
+    return [[pow(i + t + j, -1, p) for j in range(t)] for i in range(t)]
+
+
+def matmul(m, v, p):
+    return [sum(m[i][j] * v[j] for j in range(len(v))) % p for i in range(len(m))]
+
+
+############################### Rescue-Prime ###############################
+
+def rescue_params(p, t):
+    alpha_inv = pow(ALPHA, -1, p - 1)
+    mds = cauchy_mds(p, t)
+    rc = field_elements(f"Rescue-Prime(p={p},t={t},alpha={ALPHA})", p, 2 * t * RESCUE_ROUNDS)
+    return alpha_inv, mds, rc
+
+
+def rescue_clear(inputs, p, t):
+    alpha_inv, mds, rc = rescue_params(p, t)
+    state = [x % p for x in inputs]
+    for r in range(RESCUE_ROUNDS):
+        state = [pow(x, ALPHA, p) for x in state]
+        state = matmul(mds, state, p)
+        state = [(x + c) % p for x, c in zip(state, rc[2 * r * t:(2 * r + 1) * t])]
+        state = [pow(x, alpha_inv, p) for x in state]
+        state = matmul(mds, state, p)
+        state = [(x + c) % p for x, c in zip(state, rc[(2 * r + 1) * t:(2 * r + 2) * t])]
+    return state[1]
+
+
+#################################### MiMC ##################################
+
+def mimc_params(p):
+    # c_0 = 0 by convention
+    return [0] + field_elements(f"MiMC(p={p},alpha={ALPHA})", p, MIMC_ROUNDS - 1)
+
+
+def mimc_encrypt(x, k, p):
+    for c in mimc_params(p):
+        x = pow((x + k + c) % p, ALPHA, p)
+    return (x + k) % p
+
+
+def mimc_clear(inputs, p):
+    # Miyaguchi-Preneel: h_{i+1} = E_{h_i}(m_i) + h_i + m_i
+    h = 0
+    for m in inputs:
+        m = m % p
+        h = (mimc_encrypt(m, h, p) + h + m) % p
+    return h
+
+
+################################## Griffin #################################
+
+GRIFFIN_MATRIX_T4 = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]]
+
+
+def griffin_params(p, t):
+    assert t == 4, "only t = 4 is supported"
+    alpha_inv = pow(ALPHA, -1, p - 1)
+    # alpha, beta such that alpha^2 - 4 beta is a non-square and beta != 0
+    candidates = field_elements(f"Griffin-ab(p={p},t={t})", p, 256)
+    alpha, beta = None, None
+    for a, b in zip(candidates[0::2], candidates[1::2]):
+        if b != 0 and not is_square((a * a - 4 * b) % p, p):
+            alpha, beta = a, b
+            break
+    assert alpha is not None
+    rc = field_elements(f"Griffin(p={p},t={t},d={ALPHA})", p, t * (GRIFFIN_ROUNDS - 1))
+    return alpha_inv, alpha, beta, rc
+
+
+def griffin_clear(inputs, p, t):
+    alpha_inv, alpha, beta, rc = griffin_params(p, t)
+    m = [[x % p for x in row] for row in GRIFFIN_MATRIX_T4]
+    state = matmul(m, [x % p for x in inputs], p)
+    for r in range(GRIFFIN_ROUNDS):
+        x0, x1, x2, x3 = state
+        y0 = pow(x0, alpha_inv, p)
+        y1 = pow(x1, ALPHA, p)
+        l2 = (y0 + y1) % p
+        y2 = x2 * (l2 * l2 + alpha * l2 + beta) % p
+        l3 = (2 * y0 + y1 + x2) % p
+        y3 = x3 * (l3 * l3 + 2 * alpha * l3 + 4 * beta) % p
+        state = matmul(m, [y0, y1, y2, y3], p)
+        if r < GRIFFIN_ROUNDS - 1:
+            state = [(x + c) % p for x, c in zip(state, rc[r * t:(r + 1) * t])]
+    return state[1]
+
+
+def chunks(xs, n=16):
+    return [", ".join(map(str, xs[i:i + n])) for i in range(0, len(xs), n)]
+
+
+def main(p: int = 199, t: int = 4):
+    alpha_inv, mds, rescue_rc = rescue_params(p, t)
+    logger.info(f"Rescue-Prime: alpha_inv = {alpha_inv}, rounds = {RESCUE_ROUNDS}")
+    print(f"MDS = {mds}")
+    print("RC =", *chunks(rescue_rc), sep="\n    ")
+
+    mimc_rc = mimc_params(p)
+    logger.info(f"MiMC: rounds = {MIMC_ROUNDS}")
+    print("RC =", *chunks(mimc_rc), sep="\n    ")
+
+    alpha_inv, alpha, beta, griffin_rc = griffin_params(p, t)
+    logger.info(f"Griffin: alpha_inv = {alpha_inv}, alpha = {alpha}, beta = {beta}, rounds = {GRIFFIN_ROUNDS}")
+    print("RC =", *chunks(griffin_rc), sep="\n    ")
+
+    logger.info("Test vectors")
+    for inputs in ([1, 0, 2, 8], [0xAA, 5, 0, 0]):
+        print(f"{inputs}: rescue = {rescue_clear(inputs, p, t)}, "
+              f"mimc = {mimc_clear(inputs, p)}, griffin = {griffin_clear(inputs, p, t)}")
+
+
+if __name__ == "__main__":
+    logging.basicConfig(level=logging.INFO, format="%(levelname)s | %(message)s")
+    parser = argparse.ArgumentParser(description=__doc__.strip().splitlines()[0])
+    parser.add_argument("--p", type=int, default=199, help="field prime")
+    parser.add_argument("--t", type=int, default=4, help="state width")
+    args = parser.parse_args()
+    main(args.p, args.t)
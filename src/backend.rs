@@ -0,0 +1,127 @@
+use std::ops::{Add, BitAnd, Mul, Rem, Shr, ShrAssign, Sub};
+
+use tfhe::prelude::*;
+use tfhe::{ClientKey, FheBool, FheUint16, FheUint32, FheUint64, FheUint8};
+
+/*
+ * Integer backends for the FHE circuits.
+ *
+ * `FheUint<C>` is the part of the operator surface of tfhe's `FheUint*` (with
+ * clear scalars of type `C`) that the circuits use, so the same circuit code
+ * can run on real ciphertexts or on stand-ins: the op-counting backend of
+ * `cost.rs` or the plaintext simulation of `sim.rs`. A `Backend` picks one
+ * such type per bit width, with the casts between the widths the circuits use
+ * (8 <-> 16 bits for the 8-bit dev field, 32 <-> 64 bits for the 32-bit field).
+ *
+ * The generic circuits take the backend as a type parameter, e.g.
+ * `poseidon_p8::poseidon_p8_lut_mixed_impl::<Tfhe>`; the concrete FHE entry
+ * points (`poseidon_p8_lut`, `schnorr::sign_enc`, ...) use `Tfhe`.
+ *
+ * NOTE: the binary ops take the left operand by value; use `x.clone() * &y`
+ * where tfhe code would write `&x * &y`.
+ */
+
+pub trait FheUint<C>:
+    Clone
+    + Send
+    + Sync
+    + FheTrivialEncrypt<C>
+    + Add<C, Output = Self>
+    + for<'a> Add<&'a Self, Output = Self>
+    + Sub<C, Output = Self>
+    + for<'a> Sub<&'a Self, Output = Self>
+    + Mul<C, Output = Self>
+    + for<'a> Mul<&'a Self, Output = Self>
+    + Rem<C, Output = Self>
+    + BitAnd<C, Output = Self>
+    + Shr<C, Output = Self>
+    + ShrAssign<C>
+{
+    // Encrypted boolean, e.g. the result of `eq`
+    type Bool: IfThenElse<Self> + Send + Sync;
+
+    fn eq(&self, other: C) -> Self::Bool;
+
+    // Univariate lookup table x -> func(x), i.e. one programmable bootstrapping
+    fn map<F: Fn(u64) -> u64>(&self, func: F) -> Self;
+
+    // Clear value, if known with `client_key` (for the debug oracle, see
+    // `oracle.rs`)
+    fn reveal(&self, client_key: Option<&ClientKey>) -> Option<C>;
+}
+
+pub trait Backend {
+    type U8: FheUint<u8> + CastInto<Self::U16>;
+    type U16: FheUint<u16> + CastInto<Self::U8>;
+    type U32: FheUint<u32> + CastInto<Self::U64>;
+    type U64: FheUint<u64> + CastInto<Self::U32>;
+}
+
+// Real ciphertexts; needs the server key on the calling thread
+pub struct Tfhe;
+
+impl Backend for Tfhe {
+    type U8 = FheUint8;
+    type U16 = FheUint16;
+    type U32 = FheUint32;
+    type U64 = FheUint64;
+}
+
+macro_rules! impl_fhe_uint {
+    ($fhe:ty, $clear:ty) => {
+        impl FheUint<$clear> for $fhe {
+            type Bool = FheBool;
+
+            fn eq(&self, other: $clear) -> FheBool {
+                FheEq::eq(self, other)
+            }
+
+            fn map<F: Fn(u64) -> u64>(&self, func: F) -> Self {
+                FheBootstrap::map(self, func)
+            }
+
+            fn reveal(&self, client_key: Option<&ClientKey>) -> Option<$clear> {
+                client_key.map(|key| self.decrypt(key))
+            }
+        }
+    };
+}
+
+impl_fhe_uint!(FheUint8, u8);
+impl_fhe_uint!(FheUint16, u16);
+impl_fhe_uint!(FheUint32, u32);
+impl_fhe_uint!(FheUint64, u64);
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use tfhe::set_server_key;
+
+    // Generic code on the `Tfhe` backend must behave like the tfhe operators
+    fn mod_square_plus_one<B: Backend>(x: &B::U8, modval: u8) -> B::U16 {
+        let x: B::U16 = x.clone().cast_into();
+        let is_zero = x.eq(0);
+        let y = (x.clone() * &x) % (modval as u16) + 1;
+        is_zero.if_then_else(&x, &y)
+    }
+
+    #[test]
+    fn test_tfhe_backend() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        for (x, expected) in [(0u8, 0u16), (12u8, 145u16), (198u8, 2u16)] {
+            let x_enc = FheUint8::encrypt(x, &client_key);
+            let y: u16 = mod_square_plus_one::<Tfhe>(&x_enc, 199).decrypt(&client_key);
+            assert_eq!(y, expected);
+        }
+        let x_enc = FheUint8::encrypt(5u8, &client_key);
+        let y: u8 = FheUint::map(&x_enc, |v| v * 3).decrypt(&client_key);
+        assert_eq!(y, 15);
+    }
+}
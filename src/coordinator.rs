@@ -0,0 +1,258 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use tfhe::prelude::*;
+use tfhe::{set_server_key, FheUint16, FheUint8, ServerKey};
+
+use crate::backend::Tfhe;
+use crate::error::Result;
+use crate::hash::FheHash;
+use crate::hd::{self, ChildKey};
+use crate::parallel::FhePool;
+use crate::rotation::RotationKey;
+use crate::schnorr::{self, EncryptedSignature, MessageVisibility, NonceMode};
+use crate::utils;
+
+/*
+ * The coordinator (server) side of Schnorr-FHE: holds the signer's encrypted
+ * key FHE(x_sch) and signs any number of messages with it.
+ *
+ * The key-dependent preprocessing (casting FHE(x_sch) up to FheUint16 for the
+ * (mod q) arithmetic) is done once, when the coordinator is built, and reused
+ * for every message.
+ * All signing runs on an `FhePool` (see `parallel.rs`): the messages of a batch
+ * are signed in parallel, as are the lanes and MDS rows within each hash, and
+ * the pool size bounds the CPU use of the coordinator. Several coordinators
+ * (e.g. one per signer) can share a pool with `with_pool`.
+ *
+ * A batch may run for days with the full-round hashes, so progress is
+ * reported after every message (see `BatchProgress`), and a message that
+ * cannot be signed (e.g. a malformed encrypted message) fails on its own: the
+ * batch returns one `Result` per message.
+ *
+ * Each request may hedge its nonce with fresh randomness FHE(a) from the
+ * signer (see `SignRequest` and `schnorr::NonceMode`).
+ *
+ * When the signer rotates its FHE keys, `rotate` switches FHE(x_sch) to the
+ * new key without decrypting it (see `rotation.rs`).
+ *
+ * `child` derives the coordinator of an HD child key from FHE(x_sch), so new
+ * accounts need no new upload from the signer (see `hd.rs`).
+ */
+
+pub struct Coordinator<H> {
+    x_sch_enc: FheUint8,
+    x_sch_enc_up: FheUint16,
+    pool: Arc<FhePool>,
+    _hash: PhantomData<fn() -> H>,
+}
+
+// A message to sign with the nonce mode of its signature; a bare message is
+// signed with a deterministic nonce
+#[derive(Clone, Copy)]
+pub struct SignRequest<'a> {
+    pub msg: MessageVisibility<'a>,
+    pub nonce_mode: NonceMode<'a>,
+}
+
+impl<'a> From<MessageVisibility<'a>> for SignRequest<'a> {
+    fn from(msg: MessageVisibility<'a>) -> Self {
+        SignRequest {
+            msg,
+            nonce_mode: NonceMode::Deterministic,
+        }
+    }
+}
+
+// Progress of a batch, reported after each signed message
+#[derive(Clone, Copy, Debug)]
+pub struct BatchProgress {
+    // Index of the message just signed
+    pub index: usize,
+    // Number of messages signed so far, out of `total`
+    pub done: usize,
+    pub total: usize,
+    // Time since the start of the batch
+    pub elapsed: Duration,
+}
+
+impl BatchProgress {
+    // Naive estimate of the remaining time, assuming equal-cost messages
+    pub fn eta(&self) -> Duration {
+        self.elapsed
+            .mul_f64((self.total - self.done) as f64 / self.done as f64)
+    }
+}
+
+impl<H: FheHash<Enc = FheUint8, Clear = u8>> Coordinator<H> {
+    // Sign on a pool of `n_threads` threads (e.g. the available parallelism);
+    // also installs `server_key` on the calling thread
+    pub fn new(server_key: ServerKey, x_sch_enc: FheUint8, n_threads: usize) -> Result<Self> {
+        set_server_key(server_key.clone());
+        let pool = Arc::new(FhePool::new(n_threads, &server_key)?);
+        Ok(Coordinator::with_pool(pool, x_sch_enc))
+    }
+
+    // Sign on a shared pool, e.g. to bound the total CPU use of several jobs;
+    // the pool must have the server key of FHE(x_sch)
+    pub fn with_pool(pool: Arc<FhePool>, x_sch_enc: FheUint8) -> Self {
+        let x_sch_enc_up: FheUint16 = pool.install(|| x_sch_enc.clone().cast_into());
+        Coordinator {
+            x_sch_enc,
+            x_sch_enc_up,
+            pool,
+            _hash: PhantomData,
+        }
+    }
+
+    // Switch FHE(x_sch) to the signer's new FHE key and sign with the new
+    // `server_key` from then on; also installs it on the calling thread. The
+    // coordinator gets its own pool of the same size, since a shared pool
+    // still has the old server key
+    pub fn rotate(self, rotation_key: &RotationKey, server_key: ServerKey) -> Result<Self> {
+        let x_sch_enc = rotation_key.switch(&self.x_sch_enc);
+        Coordinator::new(server_key, x_sch_enc, self.pool.n_threads())
+    }
+
+    // The coordinator of the HD child key `child` of this one, signing with
+    // FHE(x_sch + tweak) (mod q) on the same server key and pool
+    pub fn child(&self, child: &ChildKey) -> Result<Self> {
+        let (x_sch_enc, x_sch_enc_up) = self
+            .pool
+            .install(|| hd::child_secret_enc::<Tfhe>(&self.x_sch_enc_up, child.tweak))?;
+        Ok(Coordinator {
+            x_sch_enc,
+            x_sch_enc_up,
+            pool: self.pool.clone(),
+            _hash: PhantomData,
+        })
+    }
+
+    // Sign one message
+    pub fn sign(
+        &self,
+        msg: MessageVisibility,
+        nonce_mode: NonceMode,
+    ) -> Result<EncryptedSignature> {
+        self.pool
+            .install(|| self.sign_on_pool(SignRequest { msg, nonce_mode }))
+    }
+
+    fn sign_on_pool(&self, req: SignRequest) -> Result<EncryptedSignature> {
+        let (x_sch_enc, x_sch_enc_up) = (&self.x_sch_enc, &self.x_sch_enc_up);
+        schnorr::sign_enc_with_key_up::<H>(x_sch_enc, x_sch_enc_up, req.msg, req.nonce_mode)
+    }
+
+    // Sign a batch of messages, logging progress after each one
+    pub fn sign_batch(&self, reqs: &[SignRequest]) -> Vec<Result<EncryptedSignature>> {
+        self.sign_batch_with_progress(reqs, |progress| {
+            utils::log(&format!(
+                "Signed message {} ({}/{}) in {:.2?}, ETA {:.2?}",
+                progress.index,
+                progress.done,
+                progress.total,
+                progress.elapsed,
+                progress.eta()
+            ));
+        })
+    }
+
+    // Sign a batch of messages, calling `on_progress` (from the pool threads)
+    // after each one; signatures are in the order of `reqs`
+    pub fn sign_batch_with_progress<F>(
+        &self,
+        reqs: &[SignRequest],
+        on_progress: F,
+    ) -> Vec<Result<EncryptedSignature>>
+    where
+        F: Fn(&BatchProgress) + Sync + Send,
+    {
+        let total = reqs.len();
+        let start = Instant::now();
+        let done = AtomicUsize::new(0);
+
+        self.pool.install(|| {
+            reqs.par_iter()
+                .enumerate()
+                .map(|(index, req)| {
+                    let sig = self.sign_on_pool(*req);
+                    on_progress(&BatchProgress {
+                        index,
+                        done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                        total,
+                        elapsed: start.elapsed(),
+                    });
+                    sig
+                })
+                .collect()
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+
+    #[test]
+    fn test_sign_batch() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
+        let pool = Arc::new(FhePool::new(2, &server_key).unwrap());
+        let coordinator = Coordinator::<PoseidonP8Rf2Rp1>::with_pool(pool, x_sch_enc);
+
+        let clear_msgs: Vec<&[u8]> = vec![b"first", b"second", b"", b"fourth message"];
+        let msg_enc = message::encrypt_message(clear_msgs[1], &client_key).unwrap();
+        // The last message has a hedged nonce
+        let aux = 42u8;
+        let aux_enc = FheUint8::encrypt(aux, &client_key);
+        let msgs: Vec<SignRequest> = vec![
+            MessageVisibility::Public(clear_msgs[0]).into(),
+            MessageVisibility::Encrypted(&msg_enc).into(),
+            MessageVisibility::Public(clear_msgs[2]).into(),
+            SignRequest {
+                msg: MessageVisibility::Public(clear_msgs[3]),
+                nonce_mode: NonceMode::Hedged(&aux_enc),
+            },
+        ];
+
+        let n_reported = AtomicUsize::new(0);
+        let sigs = coordinator.sign_batch_with_progress(&msgs, |progress| {
+            assert_eq!(progress.total, msgs.len());
+            assert!(progress.done >= 1 && progress.done <= progress.total);
+            n_reported.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(n_reported.into_inner(), msgs.len());
+
+        // In order, and equal to the clear signatures
+        assert_eq!(sigs.len(), msgs.len());
+        let auxs = [0, 0, 0, aux];
+        for ((sig_enc, msg), aux) in sigs.iter().zip(clear_msgs.iter()).zip(auxs) {
+            let sig = sig_enc.as_ref().unwrap().decrypt(&client_key);
+            let sig_clear =
+                schnorr::sign_clear_hedged::<PoseidonP8Rf2Rp1>(&x_sch, msg, aux).unwrap();
+            assert_eq!(sig, sig_clear);
+            assert_eq!(
+                schnorr::verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig),
+                Ok(())
+            );
+        }
+
+        // A malformed message fails alone
+        let msgs: [SignRequest; 2] = [
+            MessageVisibility::Encrypted(&msg_enc[..2]).into(),
+            MessageVisibility::Public(clear_msgs[0]).into(),
+        ];
+        let sigs = coordinator.sign_batch(&msgs);
+        assert!(sigs[0].is_err() && sigs[1].is_ok());
+    }
+}
@@ -0,0 +1,609 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Add, BitAnd, Mul, Rem, Shr, ShrAssign, Sub};
+use std::time::{Duration, Instant};
+
+use tfhe::prelude::*;
+use tfhe::{ClientKey, FheUint16, FheUint32, FheUint64, FheUint8};
+
+use crate::backend::{Backend, FheUint};
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{self, MixedInput};
+use crate::message;
+use crate::parallel;
+use crate::poseidon::{self, Lane};
+use crate::poseidon_p8;
+use crate::schnorr;
+
+/*
+ * Operation-count cost model of the FHE circuits.
+ *
+ * `Counting` is a backend (see `backend.rs`) whose integers hold no value and
+ * record every FHE op they perform, by kind and bit width. Since the circuits
+ * never branch on encrypted values, running a generic circuit on `Counting`
+ * (`count_ops`) gives its exact op counts in milliseconds, without keys or
+ * encryption. Combined with per-op timings (`OpTimings`, e.g. measured by
+ * `OpTimings::measure` in the `estimate` benches of `benches/fhe.rs`), this
+ * predicts the running time of a Poseidon hash (`count_poseidon`) or of a
+ * signature (`count_sign`) for any parameter set before running it.
+ *
+ * NOTE: the estimate is the sequential time; ops that run in parallel on an
+ * `FhePool` (see `parallel.rs`) are counted one after another; `count_ops`
+ * runs the circuit on the calling thread, since the counts are per thread.
+ * Trivial encryptions and clones are free and not counted.
+ */
+
+// An FHE op; `Scalar*` ops have a clear right operand
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    Add,
+    ScalarAdd,
+    Sub,
+    ScalarSub,
+    Mul,
+    ScalarMul,
+    ScalarRem,
+    ScalarBitAnd,
+    ScalarShr,
+    ScalarEq,
+    IfThenElse,
+    // Univariate lookup table (`map`)
+    Lut,
+    // Cast from the given bit width
+    Cast(u32),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Add => write!(f, "add"),
+            Op::ScalarAdd => write!(f, "scalar add"),
+            Op::Sub => write!(f, "sub"),
+            Op::ScalarSub => write!(f, "scalar sub"),
+            Op::Mul => write!(f, "mul"),
+            Op::ScalarMul => write!(f, "scalar mul"),
+            Op::ScalarRem => write!(f, "scalar rem"),
+            Op::ScalarBitAnd => write!(f, "scalar and"),
+            Op::ScalarShr => write!(f, "scalar shr"),
+            Op::ScalarEq => write!(f, "scalar eq"),
+            Op::IfThenElse => write!(f, "if_then_else"),
+            Op::Lut => write!(f, "lut"),
+            Op::Cast(from) => write!(f, "cast from {}-bit", from),
+        }
+    }
+}
+
+// Number of FHE ops by (bit width, op)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    counts: BTreeMap<(u32, Op), u64>,
+}
+
+impl OpCounts {
+    pub fn get(&self, op: Op, bits: u32) -> u64 {
+        self.counts.get(&(bits, op)).copied().unwrap_or(0)
+    }
+
+    // Total number of ops, all kinds and widths
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    // (op, bit width, count), by width
+    pub fn iter(&self) -> impl Iterator<Item = (Op, u32, u64)> + '_ {
+        self.counts.iter().map(|((bits, op), n)| (*op, *bits, *n))
+    }
+
+    pub fn add(&mut self, op: Op, bits: u32, n: u64) {
+        *self.counts.entry((bits, op)).or_insert(0) += n;
+    }
+
+    pub fn merge(&mut self, other: &OpCounts) {
+        for (op, bits, n) in other.iter() {
+            self.add(op, bits, n);
+        }
+    }
+}
+
+impl fmt::Display for OpCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (op, bits, n) in self.iter() {
+            writeln!(f, "{:>3}-bit {:<18} {:>8}", bits, op.to_string(), n)?;
+        }
+        write!(f, "total {:>27}", self.total())
+    }
+}
+
+thread_local! {
+    // Ops recorded by the `Counting` backend on this thread
+    static COUNTS: RefCell<OpCounts> = RefCell::new(OpCounts::default());
+}
+
+fn record(op: Op, bits: u32) {
+    COUNTS.with(|c| c.borrow_mut().add(op, bits, 1));
+}
+
+// Run `f` and count the ops of the `Counting` backend within it; the ops of a
+// nested `count_ops` (e.g. a dry run for tracing, see `trace.rs`) are not
+// counted by the enclosing one. `f` runs sequentially on this thread (see
+// `parallel::sequential`), since the counts are per thread
+pub fn count_ops<R>(f: impl FnOnce() -> R) -> (R, OpCounts) {
+    let outer = COUNTS.with(|c| c.take());
+    let result = parallel::sequential(f);
+    let counts = COUNTS.with(|c| c.replace(outer));
+    (result, counts)
+}
+
+// The clear/encrypted pattern of `state` as `Counting` lanes, to dry-run a
+// Poseidon step on it
+pub fn counting_lanes<E, C: Default>(state: &[Lane<E>]) -> Vec<Lane<C>> {
+    state
+        .iter()
+        .map(|lane| match lane {
+            Lane::Clear(x) => Lane::Clear(*x),
+            Lane::Enc(_) => Lane::Enc(C::default()),
+        })
+        .collect()
+}
+
+////////////////////////////// Counting backend //////////////////////////////
+
+// Encrypted boolean of the `Counting` backend
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingBool;
+
+macro_rules! counting_uint {
+    ($name:ident, $clear:ty, $bits:expr) => {
+        // Op-counting stand-in for the FheUint of the same width
+        #[derive(Clone, Copy, Debug, Default)]
+        pub struct $name;
+
+        impl FheTrivialEncrypt<$clear> for $name {
+            fn encrypt_trivial(_value: $clear) -> Self {
+                $name
+            }
+        }
+
+        counting_uint!(@binary $name, $clear, $bits, Add, add, Add, ScalarAdd);
+        counting_uint!(@binary $name, $clear, $bits, Sub, sub, Sub, ScalarSub);
+        counting_uint!(@binary $name, $clear, $bits, Mul, mul, Mul, ScalarMul);
+        counting_uint!(@scalar $name, $clear, $bits, Rem, rem, ScalarRem);
+        counting_uint!(@scalar $name, $clear, $bits, BitAnd, bitand, ScalarBitAnd);
+        counting_uint!(@scalar $name, $clear, $bits, Shr, shr, ScalarShr);
+
+        impl ShrAssign<$clear> for $name {
+            fn shr_assign(&mut self, _rhs: $clear) {
+                record(Op::ScalarShr, $bits);
+            }
+        }
+
+        impl IfThenElse<$name> for CountingBool {
+            fn if_then_else(&self, _ct_then: &$name, _ct_else: &$name) -> $name {
+                record(Op::IfThenElse, $bits);
+                $name
+            }
+        }
+
+        impl FheUint<$clear> for $name {
+            type Bool = CountingBool;
+
+            fn eq(&self, _other: $clear) -> CountingBool {
+                record(Op::ScalarEq, $bits);
+                CountingBool
+            }
+
+            fn map<F: Fn(u64) -> u64>(&self, _func: F) -> Self {
+                record(Op::Lut, $bits);
+                $name
+            }
+
+            fn reveal(&self, _client_key: Option<&ClientKey>) -> Option<$clear> {
+                None
+            }
+        }
+    };
+    (@binary $name:ident, $clear:ty, $bits:expr, $tr:ident, $f:ident, $op:ident, $scalar_op:ident) => {
+        impl<'a> $tr<&'a $name> for $name {
+            type Output = $name;
+            fn $f(self, _rhs: &$name) -> $name {
+                record(Op::$op, $bits);
+                $name
+            }
+        }
+        counting_uint!(@scalar $name, $clear, $bits, $tr, $f, $scalar_op);
+    };
+    (@scalar $name:ident, $clear:ty, $bits:expr, $tr:ident, $f:ident, $op:ident) => {
+        impl $tr<$clear> for $name {
+            type Output = $name;
+            fn $f(self, _rhs: $clear) -> $name {
+                record(Op::$op, $bits);
+                $name
+            }
+        }
+    };
+}
+
+counting_uint!(CountingUint8, u8, 8);
+counting_uint!(CountingUint16, u16, 16);
+counting_uint!(CountingUint32, u32, 32);
+counting_uint!(CountingUint64, u64, 64);
+
+macro_rules! counting_cast {
+    ($from:ident, $from_bits:expr, $to:ident, $to_bits:expr) => {
+        impl CastFrom<$from> for $to {
+            fn cast_from(_value: $from) -> $to {
+                record(Op::Cast($from_bits), $to_bits);
+                $to
+            }
+        }
+    };
+}
+
+counting_cast!(CountingUint8, 8, CountingUint16, 16);
+counting_cast!(CountingUint16, 16, CountingUint8, 8);
+counting_cast!(CountingUint32, 32, CountingUint64, 64);
+counting_cast!(CountingUint64, 64, CountingUint32, 32);
+
+// Backend that counts ops instead of computing them
+pub struct Counting;
+
+impl Backend for Counting {
+    type U8 = CountingUint8;
+    type U16 = CountingUint16;
+    type U32 = CountingUint32;
+    type U64 = CountingUint64;
+}
+
+/////////////////////////// Circuits to estimate ///////////////////////////
+
+// A Poseidon implementation of this crate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoseidonVariant {
+    // 8-bit field with lookup tables (`poseidon_p8_lut`, the `PoseidonP8` hashes)
+    P8Lut,
+    // 8-bit field with FHE arithmetic (`poseidon_p8`)
+    P8,
+    // 32-bit field with FHE arithmetic (`poseidon_p32`)
+    P32,
+}
+
+// NOTE: round constants only exist for the full rounds and for rf2_rp1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoseidonParams {
+    pub variant: PoseidonVariant,
+    pub r_full: usize,
+    pub r_partial: usize,
+}
+
+impl PoseidonParams {
+    pub fn full(variant: PoseidonVariant) -> Self {
+        PoseidonParams {
+            variant,
+            r_full: constants::POSEIDON_R_FULL,
+            r_partial: constants::POSEIDON_R_PARTIAL,
+        }
+    }
+
+    // r_full = 2, r_partial = 1 (for fast testing)
+    pub fn rf2_rp1(variant: PoseidonVariant) -> Self {
+        PoseidonParams {
+            variant,
+            r_full: 2,
+            r_partial: 1,
+        }
+    }
+}
+
+// The first `n_enc` inputs encrypted, the others clear (e.g. zero padding)
+fn mixed_inputs<C: Default, E: Default>(n_enc: usize) -> [MixedInput<C, E>; constants::POSEIDON_T] {
+    std::array::from_fn(|i| match i < n_enc {
+        true => MixedInput::Enc(E::default()),
+        false => MixedInput::Clear(C::default()),
+    })
+}
+
+// Hash of the 8-bit Poseidon variants on the `Counting` backend
+fn hash_p8(
+    params: &PoseidonParams,
+    inputs: &[MixedInput<u8, CountingUint8>],
+) -> Result<CountingUint8> {
+    let inputs = hash::pad_mixed(inputs)?;
+    let (r_full, r_partial) = (params.r_full, params.r_partial);
+    match params.variant {
+        PoseidonVariant::P8Lut => {
+            poseidon_p8::poseidon_p8_lut_mixed_impl::<Counting>(inputs, r_full, r_partial)
+        }
+        PoseidonVariant::P8 => {
+            poseidon_p8::poseidon_p8_arith_mixed_impl::<Counting>(inputs, r_full, r_partial)
+        }
+        PoseidonVariant::P32 => Err(SchnorrFheError::InvalidParams(format!(
+            "not an 8-bit Poseidon: {:?}",
+            params.variant
+        ))),
+    }
+}
+
+// Op counts of one Poseidon hash with `n_enc` encrypted inputs, the other
+// inputs being clear (see `FheHash::hash_mixed`)
+pub fn count_poseidon(params: &PoseidonParams, n_enc: usize) -> Result<OpCounts> {
+    if n_enc > constants::POSEIDON_T {
+        return Err(SchnorrFheError::HashParamMismatch(format!(
+            "too many inputs: {}",
+            n_enc
+        )));
+    }
+    let (r_full, r_partial) = (params.r_full, params.r_partial);
+    let (result, counts) = count_ops(|| match params.variant {
+        PoseidonVariant::P8Lut | PoseidonVariant::P8 => {
+            hash_p8(params, &mixed_inputs(n_enc)).map(|_| ())
+        }
+        PoseidonVariant::P32 => {
+            let inputs = mixed_inputs(n_enc);
+            poseidon::poseidon_p32_mixed_impl::<Counting>(inputs, r_full, r_partial).map(|_| ())
+        }
+    });
+    result.map(|_| counts)
+}
+
+// Op counts of a signature (`schnorr::sign_enc_with_key_up`, as done by the
+// coordinator) with an 8-bit Poseidon hash and a deterministic nonce, for a
+// public message or for an encrypted message of `encrypted_msg_len` bytes
+pub fn count_sign(params: &PoseidonParams, encrypted_msg_len: Option<usize>) -> Result<OpCounts> {
+    let hash = |inputs: &[MixedInput<u8, CountingUint8>]| hash_p8(params, inputs);
+    let (result, counts) = count_ops(|| {
+        let d = match encrypted_msg_len {
+            None => MixedInput::Clear(0u8),
+            Some(len) => {
+                let n_elems = message::encode_message(&vec![0u8; len])?.len();
+                let elems = vec![CountingUint8; n_elems];
+                let arity = constants::POSEIDON_T;
+                MixedInput::Enc(message::hash_message_enc::<Counting>(&elems, arity, &hash)?)
+            }
+        };
+        let nonce_mode = schnorr::NonceMode::Deterministic;
+        schnorr::sign_circuit::<Counting>(&CountingUint8, &CountingUint16, d, nonce_mode, hash)
+    });
+    result.map(|_| counts)
+}
+
+////////////////////////////// Op timings //////////////////////////////
+
+// Time of one FHE op by (bit width, op)
+#[derive(Clone, Debug, Default)]
+pub struct OpTimings {
+    timings: BTreeMap<(u32, Op), Duration>,
+}
+
+impl OpTimings {
+    pub fn set(&mut self, op: Op, bits: u32, time: Duration) {
+        self.timings.insert((bits, op), time);
+    }
+
+    pub fn get(&self, op: Op, bits: u32) -> Option<Duration> {
+        self.timings.get(&(bits, op)).copied()
+    }
+
+    // Predicted sequential time of the ops in `counts`; every op needs a timing
+    pub fn estimate(&self, counts: &OpCounts) -> Result<Duration> {
+        counts
+            .iter()
+            .map(|(op, bits, n)| {
+                let time = self.get(op, bits).ok_or_else(|| {
+                    SchnorrFheError::InvalidParams(format!("no timing for {}-bit {}", bits, op))
+                })?;
+                Ok(time.mul_f64(n as f64))
+            })
+            .sum()
+    }
+
+    // Time every op once per bit width on real ciphertexts (needs the server key)
+    pub fn measure(client_key: &ClientKey) -> Self {
+        let mut timings = OpTimings::default();
+        measure_width::<FheUint8, u8>(&mut timings, client_key, 8, true);
+        measure_width::<FheUint16, u16>(&mut timings, client_key, 16, true);
+        // NOTE: the 32-bit field does not use lookup tables (see `lut.rs`)
+        measure_width::<FheUint32, u32>(&mut timings, client_key, 32, false);
+        measure_width::<FheUint64, u64>(&mut timings, client_key, 64, false);
+
+        let x = FheUint8::encrypt(100u8, client_key);
+        let x: FheUint16 = timed(&mut timings, Op::Cast(8), 16, || x.cast_into());
+        timed(&mut timings, Op::Cast(16), 8, || -> FheUint8 {
+            x.cast_into()
+        });
+        let x = FheUint32::encrypt(100u32, client_key);
+        let x: FheUint64 = timed(&mut timings, Op::Cast(32), 64, || x.cast_into());
+        timed(&mut timings, Op::Cast(64), 32, || -> FheUint32 {
+            x.cast_into()
+        });
+        timings
+    }
+}
+
+// Run `f` and record its time as the timing of `op`
+fn timed<R>(timings: &mut OpTimings, op: Op, bits: u32, f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    timings.set(op, bits, start.elapsed());
+    result
+}
+
+fn measure_width<E, C>(timings: &mut OpTimings, client_key: &ClientKey, bits: u32, lut: bool)
+where
+    E: FheUint<C> + FheEncrypt<C, ClientKey>,
+    C: Copy + From<u8>,
+{
+    let (clear_a, clear_c) = (C::from(100u8), C::from(7u8));
+    let a = E::encrypt(clear_a, client_key);
+    let b = E::encrypt(clear_c, client_key);
+
+    timed(timings, Op::Add, bits, || a.clone() + &b);
+    timed(timings, Op::ScalarAdd, bits, || a.clone() + clear_c);
+    timed(timings, Op::Sub, bits, || a.clone() - &b);
+    timed(timings, Op::ScalarSub, bits, || a.clone() - clear_c);
+    timed(timings, Op::Mul, bits, || a.clone() * &b);
+    timed(timings, Op::ScalarMul, bits, || a.clone() * clear_c);
+    timed(timings, Op::ScalarRem, bits, || a.clone() % clear_c);
+    timed(timings, Op::ScalarBitAnd, bits, || a.clone() & clear_c);
+    timed(timings, Op::ScalarShr, bits, || a.clone() >> C::from(1u8));
+    let cond = timed(timings, Op::ScalarEq, bits, || a.eq(clear_a));
+    timed(timings, Op::IfThenElse, bits, || cond.if_then_else(&a, &b));
+    if lut {
+        timed(timings, Op::Lut, bits, || a.map(|v| v % 7));
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modint::{ClearUint, ModInt, Modulus};
+    use crate::utils;
+
+    // Op-counting value (mod p)
+    fn counting_modint<E: FheUint<C>, C: ClearUint>(x: E, p: C) -> ModInt<E, C> {
+        ModInt::from_reduced(x, Modulus::new(p).unwrap())
+    }
+
+    #[test]
+    fn test_count_modexp() {
+        // exp - 1 = 0b100: two squarings, then one multiply and one squaring
+        let (_, counts) =
+            count_ops(|| utils::fhe_modexp_64(&counting_modint(CountingUint64, 199), 5));
+        assert_eq!(counts.get(Op::Mul, 64), 4);
+        assert_eq!(counts.get(Op::ScalarRem, 64), 4);
+        assert_eq!(counts.total(), 8);
+
+        let base = counting_modint(CountingUint16, 199);
+        let (_, counts) = count_ops(|| utils::fhe2_modexp_16(&base, &CountingUint16));
+        assert_eq!(counts.get(Op::ScalarSub, 16), 0);
+        for op in [
+            Op::ScalarBitAnd,
+            Op::ScalarEq,
+            Op::IfThenElse,
+            Op::ScalarShr,
+        ] {
+            assert_eq!(counts.get(op, 16), 16, "{}", op);
+        }
+        assert_eq!(counts.get(Op::Mul, 16), 32);
+    }
+
+    // The ladder takes no clear exponent and never branches on the encrypted
+    // one: every bit costs the same ops, so these counts hold for every exponent
+    #[test]
+    fn test_count_modexp_ladder() {
+        let base = counting_modint(CountingUint16, 199);
+        let (_, counts) = count_ops(|| utils::fhe2_modexp_16_ladder(&base, &CountingUint16));
+        let base = counting_modint(CountingUint64, 199);
+        let (_, counts_64) = count_ops(|| utils::fhe2_modexp_64_ladder(&base, &CountingUint64));
+        for (counts, bits) in [(counts, 16), (counts_64, 64)] {
+            let n = bits as u64;
+            for op in [Op::ScalarShr, Op::ScalarBitAnd, Op::ScalarEq] {
+                assert_eq!(counts.get(op, bits), n, "{}", op);
+            }
+            assert_eq!(counts.get(Op::IfThenElse, bits), 3 * n);
+            assert_eq!(counts.get(Op::Mul, bits), 2 * n);
+            assert_eq!(counts.get(Op::ScalarRem, bits), 2 * n);
+            assert_eq!(counts.total(), 10 * n);
+        }
+    }
+
+    #[test]
+    fn test_count_ops_nested() {
+        let (_, outer) = count_ops(|| {
+            let _ = CountingUint8 + 1u8;
+            let (_, inner) = count_ops(|| CountingUint8 * &CountingUint8);
+            assert_eq!(inner.total(), 1);
+        });
+        assert_eq!(outer.get(Op::ScalarAdd, 8), 1);
+        assert_eq!(outer.get(Op::Mul, 8), 0);
+        // Ops outside of `count_ops` are not counted
+        let _ = CountingUint8 + 1u8;
+        let (_, counts) = count_ops(|| ());
+        assert_eq!(counts.total(), 0);
+    }
+
+    #[test]
+    fn test_count_poseidon() {
+        // LUT variant: 4 input reductions, then per round 4 S-box lookups and
+        // an MDS of 4 rows x (4 lookups + 4 casts up + 3 adds + 1 `%` + 1 cast down)
+        let params = PoseidonParams::rf2_rp1(PoseidonVariant::P8Lut);
+        let counts = count_poseidon(&params, 4).unwrap();
+        assert_eq!(counts.get(Op::Lut, 8), 4 + 3 * (4 + 16));
+        assert_eq!(counts.get(Op::Lut, 16), 0);
+        assert_eq!(counts.get(Op::ScalarRem, 16), 3 * 4);
+        assert_eq!(counts.get(Op::Add, 16), 3 * 12);
+        assert_eq!(counts.get(Op::Cast(8), 16), 3 * 16);
+        assert_eq!(counts.get(Op::Cast(16), 8), 3 * 4);
+        // Clear inputs skip FHE work in the first round
+        assert!(count_poseidon(&params, 2).unwrap().total() < counts.total());
+        assert_eq!(count_poseidon(&params, 0).unwrap().total(), 0);
+        assert!(count_poseidon(&params, 5).is_err());
+        // No round constants for these rounds
+        let params = PoseidonParams {
+            r_full: 4,
+            ..params
+        };
+        assert!(matches!(
+            count_poseidon(&params, 4),
+            Err(SchnorrFheError::HashParamMismatch(_))
+        ));
+
+        // Full-round P32: 8 * 4 + 56 S-boxes of 4 mults, and 16 MDS entries per round
+        let counts = count_poseidon(&PoseidonParams::full(PoseidonVariant::P32), 4).unwrap();
+        assert_eq!(counts.get(Op::Mul, 64), 4 * (8 * 4 + 56));
+        assert_eq!(counts.get(Op::ScalarMul, 64), 16 * 64);
+        assert_eq!(counts.get(Op::ScalarRem, 32), 4);
+    }
+
+    #[test]
+    fn test_count_sign() {
+        let params = PoseidonParams::rf2_rp1(PoseidonVariant::P8Lut);
+        let public = count_sign(&params, None).unwrap();
+        // Three hashes (two for the nonce) with one encrypted input each,
+        // plus the (mod q) tail
+        let hash = count_poseidon(&params, 1).unwrap();
+        assert_eq!(public.get(Op::Lut, 8), 3 * hash.get(Op::Lut, 8) + 1);
+        assert_eq!(public.get(Op::Mul, 16), 1);
+        assert_eq!(public.get(Op::Sub, 16), 1);
+
+        // Encrypted messages are hashed under FHE first: 11 bytes encode to
+        // 18 elements, i.e. 6 hashes
+        let encrypted = count_sign(&params, Some(11)).unwrap();
+        let hash_all = count_poseidon(&params, constants::POSEIDON_T).unwrap();
+        let hash_two = count_poseidon(&params, 2).unwrap();
+        let rems = |counts: &OpCounts| counts.get(Op::ScalarRem, 16);
+        assert_eq!(
+            rems(&encrypted) - rems(&public),
+            6 * rems(&hash_all) + 3 * rems(&hash_two) - 3 * rems(&hash)
+        );
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut timings = OpTimings::default();
+        timings.set(Op::Mul, 64, Duration::from_millis(100));
+        timings.set(Op::ScalarRem, 64, Duration::from_millis(30));
+        let (_, counts) =
+            count_ops(|| utils::fhe_modexp_64(&counting_modint(CountingUint64, 199), 5));
+        assert_eq!(
+            timings.estimate(&counts),
+            Ok(Duration::from_millis(4 * 130))
+        );
+    }
+
+    #[test]
+    fn test_estimate_missing_timing() {
+        let (_, counts) =
+            count_ops(|| utils::fhe_modexp_64(&counting_modint(CountingUint64, 199), 5));
+        let error = OpTimings::default().estimate(&counts).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid parameters: no timing for 64-bit mul"
+        );
+    }
+}
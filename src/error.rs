@@ -0,0 +1,47 @@
+use std::fmt;
+
+/*
+ * Errors of the public signing, hashing and verification APIs.
+ *
+ * Bad input (parameters, ciphertexts, signatures) is reported as a
+ * `SchnorrFheError` instead of a panic, so a long-running coordinator service
+ * can reject a request and carry on. Panics are left for broken internal
+ * invariants only.
+ */
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchnorrFheError {
+    // Parameters the circuits do not support, e.g. an odd number of full rounds
+    InvalidParams(String),
+    // A value does not fit the bit width it is stored in
+    WidthOverflow { what: &'static str, bits: u32 },
+    // Ciphertexts that cannot be a valid input, e.g. a truncated encrypted message
+    MalformedCiphertext(String),
+    // Hash inputs or rounds that the hash has no parameters for
+    HashParamMismatch(String),
+    // The signature (or proof) does not verify
+    VerificationFailed,
+    // A stored key that cannot be sealed or opened, e.g. with a wrong passphrase
+    KeyStorage(String),
+}
+
+pub type Result<T> = std::result::Result<T, SchnorrFheError>;
+
+impl fmt::Display for SchnorrFheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchnorrFheError::InvalidParams(msg) => write!(f, "invalid parameters: {}", msg),
+            SchnorrFheError::WidthOverflow { what, bits } => {
+                write!(f, "{} does not fit in {} bits", what, bits)
+            }
+            SchnorrFheError::MalformedCiphertext(msg) => write!(f, "malformed ciphertext: {}", msg),
+            SchnorrFheError::HashParamMismatch(msg) => {
+                write!(f, "hash parameter mismatch: {}", msg)
+            }
+            SchnorrFheError::VerificationFailed => write!(f, "verification failed"),
+            SchnorrFheError::KeyStorage(msg) => write!(f, "key storage: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SchnorrFheError {}
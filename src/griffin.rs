@@ -0,0 +1,193 @@
+use tfhe::prelude::*;
+use tfhe::{FheUint16, FheUint8};
+
+use crate::backend::Tfhe;
+use crate::constants;
+use crate::error::Result;
+use crate::hash::{self, ClearHash, FheHash};
+use crate::lut;
+use crate::utils;
+
+/*
+ * Griffin over the 8-bit dev field (p = `POSEIDON_P_8`, t = 4, d = alpha = 5).
+ *
+ * Each round applies the non-linear layer
+ *   y0 = x0^(1/d), y1 = x1^d,
+ *   y2 = x2 * (L2^2 + a2 * L2 + b2), L2 = y0 + y1,
+ *   y3 = x3 * (L3^2 + a3 * L3 + b3), L3 = 2 * y0 + y1 + x2,
+ * with (a_i, b_i) = ((i - 1) * a, (i - 1)^2 * b) and a^2 - 4b a non-square,
+ * followed by the linear layer M and round constants (none in the last round).
+ * M is also applied once before the first round.
+ *
+ * Only two elements per round go through a power map, so Griffin needs few
+ * lookups per round; the quadratic factors are one `%` and one lookup each on
+ * L_i, then one FHE mult in FheUint16 ((p - 1)^2 < 2^16) and one `%`.
+ *
+ * Same convention as `poseidon_p8`: the inputs are the initial state and the
+ * output is state element 1 after the permutation.
+ * Constants and test vectors: `scripts/arith_hash_gen.py`.
+ */
+
+const T: usize = constants::POSEIDON_T;
+
+// x * y (mod p) for x, y already (mod p)
+fn fhe_modmul_8(x: &FheUint8, y: &FheUint8, prime: u8) -> FheUint8 {
+    let prod: FheUint16 = FheUint16::cast_from(x.clone()) * &FheUint16::cast_from(y.clone());
+    (prod % prime as u16).cast_into()
+}
+
+// x^2 + a * x + b (mod p) for x < 2^16
+fn fhe_quadratic_8(x: &FheUint16, a: u8, b: u8, prime: u8) -> FheUint8 {
+    let x: FheUint8 = (x % prime as u16).cast_into();
+    lut::fhe_lut_quadratic_8(&x, a, b, prime)
+}
+
+pub fn griffin_p8(inputs: [&FheUint8; T]) -> FheUint8 {
+    let p8 = constants::POSEIDON_P_8;
+    let alpha = constants::POSEIDON_ALPHA;
+    let state: Vec<FheUint8> = inputs.iter().map(|x| lut::fhe_lut_mod_8(*x, p8)).collect();
+    let mut state = lut::fhe_lut_apply_matrix_8::<Tfhe, T>(state, &GRIFFIN_MATRIX, p8);
+    // Constants still to be added to each element, fused into the next lookup
+    let mut pending: [u8; T] = [0; T];
+
+    for r in 0..GRIFFIN_ROUNDS {
+        // 1. Non-linear layer
+        let x2 = lut::fhe_lut_add_modexp_8(&state[2], pending[2], 1, p8);
+        let x3 = lut::fhe_lut_add_modexp_8(&state[3], pending[3], 1, p8);
+        let y0 = lut::fhe_lut_add_modexp_8(&state[0], pending[0], GRIFFIN_ALPHA_INV, p8);
+        let y1 = lut::fhe_lut_add_modexp_8(&state[1], pending[1], alpha, p8);
+        let y0_up: FheUint16 = y0.clone().cast_into();
+        let y1_up: FheUint16 = y1.clone().cast_into();
+        let x2_up: FheUint16 = x2.clone().cast_into();
+
+        let l2: FheUint16 = &y0_up + &y1_up;
+        let g2 = fhe_quadratic_8(&l2, GRIFFIN_A, GRIFFIN_B, p8);
+        let y2 = fhe_modmul_8(&x2, &g2, p8);
+
+        let l3: FheUint16 = &y0_up * 2u16 + &y1_up + &x2_up;
+        let (a3, b3) = griffin_coeffs(3);
+        let g3 = fhe_quadratic_8(&l3, a3 as u8, b3 as u8, p8);
+        let y3 = fhe_modmul_8(&x3, &g3, p8);
+
+        // 2. Linear layer
+        state = lut::fhe_lut_apply_matrix_8::<Tfhe, T>(vec![y0, y1, y2, y3], &GRIFFIN_MATRIX, p8);
+
+        // 3. Add round constants (deferred; none in the last round)
+        if r < GRIFFIN_ROUNDS - 1 {
+            for j in 0..T {
+                pending[j] = GRIFFIN_RC[r * T + j] as u8;
+            }
+        }
+    }
+
+    state[1].clone()
+}
+
+// (a_i, b_i) = ((i - 1) * a, (i - 1)^2 * b) (mod p)
+fn griffin_coeffs(i: u16) -> (u16, u16) {
+    let p = constants::POSEIDON_P_8 as u16;
+    (((i - 1) * GRIFFIN_A as u16) % p, ((i - 1) * (i - 1) * GRIFFIN_B as u16) % p)
+}
+
+// Same as `griffin_p8` but in the clear
+pub fn griffin_p8_clear(inputs: [u8; T]) -> u8 {
+    let p = constants::POSEIDON_P_8 as u64;
+    let apply_matrix = |v: &[u64]| -> Vec<u64> {
+        (0..T)
+            .map(|i| (0..T).map(|j| GRIFFIN_MATRIX[i][j] as u64 * v[j]).sum::<u64>() % p)
+            .collect()
+    };
+    let quadratic = |l: u64, (a, b): (u16, u16)| (l * l + a as u64 * l + b as u64) % p;
+
+    let inputs: Vec<u64> = inputs.iter().map(|x| *x as u64 % p).collect();
+    let mut state = apply_matrix(&inputs);
+    for r in 0..GRIFFIN_ROUNDS {
+        let y0 = utils::clear_modexp_64(state[0], GRIFFIN_ALPHA_INV as u64, p);
+        let y1 = utils::clear_modexp_64(state[1], constants::POSEIDON_ALPHA as u64, p);
+        let l2 = (y0 + y1) % p;
+        let y2 = state[2] * quadratic(l2, griffin_coeffs(2)) % p;
+        let l3 = (2 * y0 + y1 + state[2]) % p;
+        let y3 = state[3] * quadratic(l3, griffin_coeffs(3)) % p;
+        state = apply_matrix(&[y0, y1, y2, y3]);
+        if r < GRIFFIN_ROUNDS - 1 {
+            for j in 0..T {
+                state[j] = (state[j] + GRIFFIN_RC[r * T + j] as u64) % p;
+            }
+        }
+    }
+    state[1] as u8
+}
+
+pub struct GriffinP8;
+
+impl ClearHash for GriffinP8 {
+    type Clear = u8;
+    const NAME: &'static str = "griffin_p8";
+    const ARITY: usize = T;
+
+    fn hash_clear(inputs: &[u8]) -> Result<u8> {
+        Ok(griffin_p8_clear(hash::pad_clear(inputs)?))
+    }
+}
+
+impl FheHash for GriffinP8 {
+    type Enc = FheUint8;
+
+    fn hash_enc(inputs: &[FheUint8]) -> Result<FheUint8> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        Ok(griffin_p8(std::array::from_fn(|i| &padded[i])))
+    }
+}
+
+////////////////////////////// Griffin constants ///////////////////////////////
+
+// NOTE: toy round number for the 8-bit dev field; no security claim
+pub const GRIFFIN_ROUNDS: usize = 8;
+
+// 1/d (mod p - 1)
+pub const GRIFFIN_ALPHA_INV: u8 = 119;
+
+// (a, b) with a^2 - 4b a non-square (mod p)
+const GRIFFIN_A: u8 = 37;
+const GRIFFIN_B: u8 = 92;
+
+// Linear layer for t = 4 from the Griffin paper
+const GRIFFIN_MATRIX: [[u16; T]; T] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+
+// t round constants for each round but the last; all values are mod POSEIDON_P_8
+const GRIFFIN_RC: [u16; T * (GRIFFIN_ROUNDS - 1)] = [
+    27, 112, 180, 166, 158, 175, 49, 139, 49, 180, 94, 177, 101, 66, 190, 88, 147, 31, 87, 8, 140,
+    15, 171, 116, 75, 158, 63, 154,
+];
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::set_server_key;
+
+    // Expected outputs from `python scripts/arith_hash_gen.py --p 199 --t 4`
+    #[test]
+    fn test_griffin_p8_clear() {
+        assert_eq!(griffin_p8_clear([1, 0, 2, 8]), 71);
+        assert_eq!(griffin_p8_clear([0xAA, 5, 0, 0]), 113);
+    }
+
+    #[test]
+    fn test_griffin_p8() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let inputs: [&FheUint8; T] = [
+            &FheUint8::encrypt(1u8, &client_key),
+            &FheUint8::encrypt(0u8, &client_key),
+            &FheUint8::encrypt(2u8, &client_key),
+            &FheUint8::encrypt(8u8, &client_key),
+        ];
+        let output: u8 = griffin_p8(inputs).decrypt(&client_key);
+        assert_eq!(output, 71);
+    }
+}
@@ -0,0 +1,177 @@
+use tfhe::prelude::*;
+use tfhe::ClientKey;
+
+use crate::error::{Result, SchnorrFheError};
+
+/*
+ * Common interface for the arithmetization-friendly hashes in this crate
+ * (Poseidon, Rescue-Prime, MiMC, Griffin) so the Schnorr signer and verifier
+ * can be generic over the hash function and hashes can be benchmarked
+ * against each other.
+ *
+ * - `ClearHash`: the hash in the clear, used by the verifier
+ * - `FheHash`: the same hash under FHE, used by the coordinator to sign
+ *
+ * Each hash takes up to `ARITY` inputs (missing inputs are zero, as in
+ * `H(m, x_sch) = H(m, x_sch, 0, 0)`) and returns a single field element.
+ *
+ * Inputs can also mix clear and encrypted values (`MixedInput`), e.g. a
+ * public message with an encrypted key; implementors may override
+ * `hash_mixed` to skip FHE operations on the clear inputs.
+ *
+ * More than `ARITY` inputs is a `SchnorrFheError::HashParamMismatch`.
+ *
+ * Consistency guarantee: an `FheHash` implementor must agree with its
+ * `ClearHash` implementation, i.e. for any clear inputs `xs`,
+ *   decrypt(hash_enc(encrypt(xs))) == hash_clear(xs).
+ * Signatures produced with `hash_enc` only verify with `hash_clear` if this
+ * holds; use `check_hash_consistency` to test an implementor.
+ */
+
+pub trait ClearHash {
+    // Clear field element type, e.g. u8 for the 8-bit dev field
+    type Clear: Copy + Default;
+
+    // Short name for logs and benchmarks
+    const NAME: &'static str;
+
+    // Number of hash inputs (i.e. the state width)
+    const ARITY: usize;
+
+    fn hash_clear(inputs: &[Self::Clear]) -> Result<Self::Clear>;
+}
+
+// A hash input that is either public (clear) or encrypted
+#[derive(Clone)]
+pub enum MixedInput<C, E> {
+    Clear(C),
+    Enc(E),
+}
+
+pub trait FheHash: ClearHash {
+    // FHE field element type, e.g. FheUint8 for the 8-bit dev field
+    type Enc: Clone + FheTrivialEncrypt<Self::Clear>;
+
+    fn hash_enc(inputs: &[Self::Enc]) -> Result<Self::Enc>;
+
+    // Default: trivially encrypt the clear inputs, i.e. no savings
+    fn hash_mixed(inputs: &[MixedInput<Self::Clear, Self::Enc>]) -> Result<Self::Enc> {
+        let inputs: Vec<Self::Enc> = inputs
+            .iter()
+            .map(|x| match x {
+                MixedInput::Clear(x) => Self::Enc::encrypt_trivial(*x),
+                MixedInput::Enc(x) => x.clone(),
+            })
+            .collect();
+        Self::hash_enc(&inputs)
+    }
+}
+
+// Zero-pad the inputs of a clear hash to exactly `N` elements
+pub fn pad_clear<T: Copy + Default, const N: usize>(inputs: &[T]) -> Result<[T; N]> {
+    check_n_inputs(inputs.len(), N)?;
+    let mut padded = [T::default(); N];
+    padded[..inputs.len()].copy_from_slice(inputs);
+    Ok(padded)
+}
+
+// Zero-pad the inputs of an FHE hash to exactly `H::ARITY` elements; the
+// padding is a trivial (noiseless, public) encryption of zero so no key is needed
+pub fn pad_enc<H: FheHash>(inputs: &[H::Enc]) -> Result<Vec<H::Enc>> {
+    check_n_inputs(inputs.len(), H::ARITY)?;
+    let mut padded: Vec<H::Enc> = inputs.to_vec();
+    padded.resize(H::ARITY, H::Enc::encrypt_trivial(H::Clear::default()));
+    Ok(padded)
+}
+
+// Zero-pad mixed inputs to exactly `N` elements; the padding is a clear zero
+pub fn pad_mixed<C: Copy + Default, E: Clone, const N: usize>(
+    inputs: &[MixedInput<C, E>],
+) -> Result<[MixedInput<C, E>; N]> {
+    check_n_inputs(inputs.len(), N)?;
+    Ok(std::array::from_fn(|i| {
+        inputs
+            .get(i)
+            .cloned()
+            .unwrap_or(MixedInput::Clear(C::default()))
+    }))
+}
+
+fn check_n_inputs(n_inputs: usize, arity: usize) -> Result<()> {
+    match n_inputs <= arity {
+        true => Ok(()),
+        false => Err(SchnorrFheError::HashParamMismatch(format!(
+            "too many hash inputs: {} > {}",
+            n_inputs, arity
+        ))),
+    }
+}
+
+// Test harness: check the consistency guarantee of `H` on the given inputs
+pub fn check_hash_consistency<H>(client_key: &ClientKey, inputs: &[H::Clear]) -> Result<bool>
+where
+    H: FheHash,
+    H::Clear: PartialEq,
+    H::Enc: FheEncrypt<H::Clear, ClientKey> + FheDecrypt<H::Clear>,
+{
+    let inputs_enc: Vec<H::Enc> = inputs
+        .iter()
+        .map(|x| H::Enc::encrypt(*x, client_key))
+        .collect();
+    let hash_enc: H::Clear = H::hash_enc(&inputs_enc)?.decrypt(client_key);
+
+    // Also with every other input left in the clear
+    let inputs_mixed: Vec<MixedInput<H::Clear, H::Enc>> = inputs_enc
+        .into_iter()
+        .zip(inputs.iter())
+        .enumerate()
+        .map(|(i, (x_enc, x))| match i % 2 {
+            0 => MixedInput::Enc(x_enc),
+            _ => MixedInput::Clear(*x),
+        })
+        .collect();
+    let hash_mixed: H::Clear = H::hash_mixed(&inputs_mixed)?.decrypt(client_key);
+
+    let hash_clear = H::hash_clear(inputs)?;
+    Ok(hash_enc == hash_clear && hash_mixed == hash_clear)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::griffin::GriffinP8;
+    use crate::mimc::MimcP8;
+    use crate::poseidon::PoseidonP32Rf2Rp1;
+    use crate::poseidon_p8::{PoseidonP8, PoseidonP8Rf2Rp1};
+    use crate::rescue::RescueP8;
+    use crate::utils;
+    use tfhe::set_server_key;
+
+    #[test]
+    fn test_pad_clear() {
+        let padded: [u8; 4] = pad_clear(&[3u8, 7u8]).unwrap();
+        assert_eq!(padded, [3, 7, 0, 0]);
+        let too_many: Result<[u8; 2]> = pad_clear(&[3u8, 7u8, 1u8]);
+        assert!(matches!(too_many, Err(SchnorrFheError::HashParamMismatch(_))));
+    }
+
+    // Check FHE/clear agreement of every hash, including with padded inputs
+    #[test]
+    fn test_hash_consistency() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        for inputs in [&[1u8, 0u8, 2u8, 8u8][..], &[0xAAu8, 5u8][..]] {
+            assert!(check_hash_consistency::<PoseidonP8>(&client_key, inputs).unwrap());
+            assert!(check_hash_consistency::<PoseidonP8Rf2Rp1>(&client_key, inputs).unwrap());
+            assert!(check_hash_consistency::<RescueP8>(&client_key, inputs).unwrap());
+            assert!(check_hash_consistency::<MimcP8>(&client_key, inputs).unwrap());
+            assert!(check_hash_consistency::<GriffinP8>(&client_key, inputs).unwrap());
+        }
+        assert!(check_hash_consistency::<PoseidonP32Rf2Rp1>(&client_key, &[1u32, 0u32, 2u32, 8u32]).unwrap());
+    }
+}
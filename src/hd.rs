@@ -0,0 +1,190 @@
+use rand::rngs::OsRng;
+use rand::Rng;
+use tfhe::prelude::*;
+
+use crate::backend::Backend;
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::ClearHash;
+use crate::modint::{ModInt, Modulus};
+use crate::signing_key::SigningKey;
+use crate::utils;
+
+/*
+ * Hierarchical deterministic (HD) child keys, derived from one encrypted
+ * master secret.
+ *
+ * An `ExtendedPublicKey` is a public key y with a public chain code c. The
+ * child at `index` has the tweak and the chain code
+ *   t = H(c, y, index, 0) (mod q),  c_child = H(c, y, index, 1)
+ * and the keys
+ *   x_child = x + t (mod q),  y_child = y * g^t (mod p)
+ * so y_child is computed in the clear from public data (`child`), while the
+ * coordinator computes FHE(x_child) from its FHE(x) with one addition
+ * (`Coordinator::child`): the signer uploads FHE(x) once, and any number of
+ * accounts are derived from it. Children derive grandchildren the same way.
+ *
+ * As in BIP-32's non-hardened derivation, anyone with the extended public
+ * key can link the children to the parent, and a child secret with the
+ * parent's chain code reveals the parent secret; the child secrets stay
+ * encrypted here, so only the signer could do so.
+ *
+ * With probability 1/q a child has x_child = 0 (y_child = 1), which is not a
+ * valid key; `child` rejects it, and the caller moves on to the next index.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    pub y_sch: u8,
+    pub chaincode: u8,
+}
+
+// A derived child: its public key and the tweak of its secret
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChildKey {
+    pub index: u8,
+    pub tweak: u8,
+    pub public: ExtendedPublicKey,
+}
+
+// Random chain code of a master key
+pub fn chaincode_rand() -> u8 {
+    OsRng.gen_range(0..constants::POSEIDON_P_8)
+}
+
+impl ExtendedPublicKey {
+    pub fn new(y_sch: u8, chaincode: u8) -> Result<Self> {
+        check_field(chaincode, "chain code")?;
+        Ok(ExtendedPublicKey { y_sch, chaincode })
+    }
+
+    // The child at `index`, or `InvalidParams` if its key is not valid
+    pub fn child<H: ClearHash<Clear = u8>>(&self, index: u8) -> Result<ChildKey> {
+        check_field(index, "child index")?;
+        let inputs = |domain| [self.chaincode, self.y_sch, index, domain];
+        let tweak = H::hash_clear(&inputs(0))? % constants::SCH_Q;
+        let chaincode = H::hash_clear(&inputs(1))?;
+        let p_sch = constants::SCH_P as u64;
+        let g_t = utils::clear_modexp_64(constants::SCH_G as u64, tweak as u64, p_sch);
+        let y_child = (self.y_sch as u64 * g_t % p_sch) as u8;
+        if y_child == 1 {
+            return Err(SchnorrFheError::InvalidParams(format!(
+                "child {} has no valid key, use the next index",
+                index
+            )));
+        }
+        Ok(ChildKey {
+            index,
+            tweak,
+            public: ExtendedPublicKey {
+                y_sch: y_child,
+                chaincode,
+            },
+        })
+    }
+}
+
+// FHE(x + t) (mod q) from FHE(x) in the 16-bit container (e.g. the
+// coordinator's FHE(x_sch) cast up), in both containers
+pub fn child_secret_enc<B: Backend>(x_enc_up: &B::U16, tweak: u8) -> Result<(B::U8, B::U16)> {
+    let q_sch = Modulus::new(constants::SCH_Q as u16)?;
+    let x_child_up = ModInt::from_reduced(x_enc_up.clone(), q_sch)
+        .add_clear(tweak as u16)
+        .into_inner();
+    Ok((x_child_up.clone().cast_into(), x_child_up))
+}
+
+// x + t (mod q), the clear reference
+pub fn child_secret_clear(x_sch: &SigningKey, child: &ChildKey) -> Result<SigningKey> {
+    let q = constants::SCH_Q as u16;
+    SigningKey::new(((x_sch.expose_secret() as u16 + child.tweak as u16) % q) as u8)
+}
+
+// Hash inputs are elements of the 8-bit field
+fn check_field(x: u8, what: &str) -> Result<()> {
+    if x >= constants::POSEIDON_P_8 {
+        return Err(SchnorrFheError::InvalidParams(format!(
+            "{} {} is not in the field",
+            what, x
+        )));
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::Coordinator;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+    use crate::schnorr::{self, MessageVisibility, NonceMode};
+    use crate::sim::{Sim, SimUint16};
+    use tfhe::FheUint8;
+
+    #[test]
+    fn test_child_keys() {
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let master = ExtendedPublicKey::new(y_sch, 42).unwrap();
+        let mut n_valid = 0;
+        for index in 0..30 {
+            let child = match master.child::<PoseidonP8Rf2Rp1>(index) {
+                Ok(child) => child,
+                // Only if x + t = 0 (mod q)
+                Err(_) => continue,
+            };
+            n_valid += 1;
+            // y_child = g^x_child, also on `Sim`
+            let x_child = child_secret_clear(&x_sch, &child).unwrap();
+            assert_eq!(schnorr::public_key(&x_child).unwrap(), child.public.y_sch);
+            let x_up = SimUint16(x_sch.expose_secret() as u16);
+            let (x_child_sim, _) = child_secret_enc::<Sim>(&x_up, child.tweak).unwrap();
+            assert_eq!(x_child_sim.0, x_child.expose_secret());
+
+            // Grandchildren, from the child's extended public key
+            if let Ok(grandchild) = child.public.child::<PoseidonP8Rf2Rp1>(7) {
+                let x_grandchild = child_secret_clear(&x_child, &grandchild).unwrap();
+                let y_grandchild = schnorr::public_key(&x_grandchild).unwrap();
+                assert_eq!(y_grandchild, grandchild.public.y_sch);
+            }
+        }
+        assert!(n_valid > 0);
+        assert!(ExtendedPublicKey::new(y_sch, constants::POSEIDON_P_8).is_err());
+        assert!(master
+            .child::<PoseidonP8Rf2Rp1>(constants::POSEIDON_P_8)
+            .is_err());
+    }
+
+    #[test]
+    fn test_coordinator_child() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
+        let coordinator = Coordinator::<PoseidonP8Rf2Rp1>::new(server_key, x_sch_enc, 2).unwrap();
+        let master = ExtendedPublicKey::new(y_sch, chaincode_rand()).unwrap();
+        let child = (0..)
+            .find_map(|index| master.child::<PoseidonP8Rf2Rp1>(index).ok())
+            .unwrap();
+
+        // Signed by the child coordinator, verified with the child public key
+        let msg: &[u8] = b"account";
+        let sig = coordinator
+            .child(&child)
+            .unwrap()
+            .sign(MessageVisibility::Public(msg), NonceMode::Deterministic)
+            .unwrap()
+            .decrypt(&client_key);
+        let x_child = child_secret_clear(&x_sch, &child).unwrap();
+        assert_eq!(
+            sig,
+            schnorr::sign_clear::<PoseidonP8Rf2Rp1>(&x_child, msg).unwrap()
+        );
+        let y_child = child.public.y_sch;
+        assert_eq!(
+            schnorr::verify::<PoseidonP8Rf2Rp1>(y_child, msg, &sig),
+            Ok(())
+        );
+    }
+}
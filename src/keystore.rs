@@ -0,0 +1,449 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use argon2::Argon2;
+use blake2::{Blake2s256, Digest};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tfhe::{ClientKey, CompactPublicKey, ServerKey};
+use zeroize::Zeroizing;
+
+use crate::error::{Result, SchnorrFheError};
+use crate::profile::FheProfile;
+use crate::signing_key::{KeyScalar, SigningKey};
+
+/*
+ * Key files and the keystore directory, so that keys outlive a run instead of
+ * being regenerated by `utils::init_keys` (minutes with real parameters).
+ *
+ * Every key file is
+ *   magic "SFHE" || version (u16) || kind (u8) || parameter set (u8)
+ *   || payload length (u64) || payload || BLAKE2s-256 of all of the above
+ * with little-endian integers. Loading checks the whole header against what
+ * the caller expects, so a server key is never read as a public key, nor a
+ * 32-bit Schnorr key as an 8-bit one, nor FHE keys of another `FheProfile`.
+ * The checksum catches a truncated or corrupted file before bincode sees it.
+ * Files of another version are rejected.
+ *
+ * Signer-private payloads (the `ClientKey` and x_sch) are sealed with a
+ * passphrase:
+ *   salt (16 bytes) || nonce (12 bytes) || ChaCha20-Poly1305(key)
+ * The encryption key is derived from the passphrase by Argon2id with a fresh
+ * salt, and the kind and the parameter set are the associated data. The other
+ * payloads are bincode (FHE keys) or a u64 (y_sch).
+ *
+ * A `Keystore` directory keeps the two sides apart:
+ *   <dir>/signer/       client_key, signing_key: stay with the signer
+ *   <dir>/coordinator/  server_key, public_key, verifying_key: shipped to
+ *                       the coordinator
+ * so the coordinator directory can be copied as is and holds nothing that
+ * decrypts.
+ */
+
+const MAGIC: [u8; 4] = *b"SFHE";
+// 2: FHE keys with function evaluation, and fixed parameter set ids; the keys
+// of version 1 files have to be regenerated
+pub const VERSION: u16 = 2;
+const HEADER_LEN: usize = 16;
+const CHECKSUM_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// What a key file holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyKind {
+    Client = 1,
+    Server = 2,
+    Public = 3,
+    // x_sch
+    Signing = 4,
+    // y_sch
+    Verifying = 5,
+}
+
+// The parameters a key was generated for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamSet {
+    // FHE keys of a built-in `FheProfile` by its fixed id (0 is the default),
+    // or of a custom one (`TFHE_CUSTOM`)
+    Tfhe(u8),
+    // Schnorr keys of the 8-bit dev set (SCH_P_8, SCH_G_8, SCH_Q_4)
+    Schnorr8,
+    // Schnorr keys of the 32-bit set (SCH_P_32, SCH_G_32, SCH_Q_16)
+    Schnorr32,
+}
+
+impl ParamSet {
+    pub const TFHE_CUSTOM: u8 = 0x7f;
+
+    // The parameter set byte of the header
+    pub fn id(self) -> u8 {
+        match self {
+            ParamSet::Tfhe(i) => 0x80 | i,
+            ParamSet::Schnorr8 => 1,
+            ParamSet::Schnorr32 => 2,
+        }
+    }
+}
+
+// Wrap a payload into a key file (see the format above)
+pub fn encode(kind: KeyKind, params: ParamSet, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + CHECKSUM_LEN);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(kind as u8);
+    bytes.push(params.id());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    let checksum = Blake2s256::digest(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bytes
+}
+
+// The payload of a key file, if it is a `kind` key for `params`
+pub fn decode(bytes: &[u8], kind: KeyKind, params: ParamSet) -> Result<&[u8]> {
+    if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+        return Err(storage_err("truncated key file".to_string()));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if body[..4] != MAGIC {
+        return Err(storage_err("not a key file".to_string()));
+    }
+    let version = u16::from_le_bytes([body[4], body[5]]);
+    if version != VERSION {
+        return Err(storage_err(format!(
+            "unsupported key file version {} (expected {})",
+            version, VERSION
+        )));
+    }
+    let len = u64::from_le_bytes(body[8..HEADER_LEN].try_into().unwrap());
+    if len != (body.len() - HEADER_LEN) as u64 || Blake2s256::digest(body)[..] != *checksum {
+        return Err(storage_err("corrupted key file".to_string()));
+    }
+    if body[6] != kind as u8 {
+        return Err(storage_err(format!(
+            "expected a {:?} key file, found kind {}",
+            kind, body[6]
+        )));
+    }
+    if body[7] != params.id() {
+        return Err(storage_err(format!(
+            "expected a {:?} key, found parameter set {}",
+            params, body[7]
+        )));
+    }
+    Ok(&body[HEADER_LEN..])
+}
+
+// Seal a secret payload with `passphrase` (see the format above)
+pub fn seal(passphrase: &[u8], kind: KeyKind, params: ParamSet, secret: &[u8]) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let payload = Payload {
+        msg: secret,
+        aad: &[kind as u8, params.id()],
+    };
+    let sealed = cipher(passphrase, &salt)?
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .map_err(|_| storage_err("cannot seal the key".to_string()))?;
+    Ok([&salt[..], &nonce[..], &sealed[..]].concat())
+}
+
+// Open a payload sealed by `seal`
+pub fn open(
+    passphrase: &[u8],
+    kind: KeyKind,
+    params: ParamSet,
+    sealed: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    if sealed.len() < SALT_LEN + NONCE_LEN {
+        return Err(storage_err("truncated key".to_string()));
+    }
+    let (salt, rest) = sealed.split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let payload = Payload {
+        msg: sealed,
+        aad: &[kind as u8, params.id()],
+    };
+    let secret = cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| storage_err("wrong passphrase or corrupted key".to_string()))?;
+    Ok(Zeroizing::new(secret))
+}
+
+// An FHE key that may be shipped to the coordinator, stored in the clear
+pub trait CoordinatorKey: Serialize + DeserializeOwned {
+    const KIND: KeyKind;
+}
+
+impl CoordinatorKey for ServerKey {
+    const KIND: KeyKind = KeyKind::Server;
+}
+
+impl CoordinatorKey for CompactPublicKey {
+    const KIND: KeyKind = KeyKind::Public;
+}
+
+// An FHE key generated with `profile`
+pub fn fhe_key_to_bytes<K: CoordinatorKey>(key: &K, profile: &FheProfile) -> Result<Vec<u8>> {
+    let payload = bincode::serialize(key).map_err(|e| storage_err(e.to_string()))?;
+    Ok(encode(K::KIND, profile.param_set(), &payload))
+}
+
+pub fn fhe_key_from_bytes<K: CoordinatorKey>(bytes: &[u8], profile: &FheProfile) -> Result<K> {
+    let payload = decode(bytes, K::KIND, profile.param_set())?;
+    bincode::deserialize(payload).map_err(|e| storage_err(e.to_string()))
+}
+
+// The client key of `profile`, sealed with `passphrase`
+pub fn client_key_to_bytes(
+    client_key: &ClientKey,
+    profile: &FheProfile,
+    passphrase: &[u8],
+) -> Result<Vec<u8>> {
+    let (kind, params) = (KeyKind::Client, profile.param_set());
+    let secret =
+        Zeroizing::new(bincode::serialize(client_key).map_err(|e| storage_err(e.to_string()))?);
+    Ok(encode(
+        kind,
+        params,
+        &seal(passphrase, kind, params, &secret)?,
+    ))
+}
+
+pub fn client_key_from_bytes(
+    bytes: &[u8],
+    profile: &FheProfile,
+    passphrase: &[u8],
+) -> Result<ClientKey> {
+    let (kind, params) = (KeyKind::Client, profile.param_set());
+    let secret = open(passphrase, kind, params, decode(bytes, kind, params)?)?;
+    bincode::deserialize(&secret).map_err(|e| storage_err(e.to_string()))
+}
+
+pub fn verifying_key_to_bytes<T: KeyScalar>(y_sch: T) -> Vec<u8> {
+    encode(KeyKind::Verifying, T::PARAMS, &y_sch.into().to_le_bytes())
+}
+
+pub fn verifying_key_from_bytes<T: KeyScalar>(bytes: &[u8]) -> Result<T> {
+    let payload = decode(bytes, KeyKind::Verifying, T::PARAMS)?;
+    let y_sch: [u8; 8] = payload
+        .try_into()
+        .map_err(|_| storage_err("bad verifying key length".to_string()))?;
+    T::try_from(u64::from_le_bytes(y_sch))
+        .map_err(|_| storage_err("verifying key too wide".to_string()))
+}
+
+pub fn write(path: impl AsRef<Path>, bytes: &[u8]) -> Result<()> {
+    fs::write(path, bytes).map_err(|e| storage_err(e.to_string()))
+}
+
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| storage_err(e.to_string()))
+}
+
+// The key files of one signer (see the layout above), with FHE keys of
+// `profile`
+pub struct Keystore {
+    dir: PathBuf,
+    profile: FheProfile,
+}
+
+const CLIENT_KEY: &str = "client_key.key";
+const SIGNING_KEY: &str = "signing_key.key";
+const SERVER_KEY: &str = "server_key.key";
+const PUBLIC_KEY: &str = "public_key.key";
+const VERIFYING_KEY: &str = "verifying_key.key";
+
+impl Keystore {
+    pub fn new(dir: impl Into<PathBuf>, profile: FheProfile) -> Self {
+        Keystore {
+            dir: dir.into(),
+            profile,
+        }
+    }
+
+    // Signer-private material
+    pub fn signer_dir(&self) -> PathBuf {
+        self.dir.join("signer")
+    }
+
+    // Material shipped to the coordinator
+    pub fn coordinator_dir(&self) -> PathBuf {
+        self.dir.join("coordinator")
+    }
+
+    // Whether all the key files are there
+    pub fn exists(&self) -> bool {
+        let (signer, coordinator) = (self.signer_dir(), self.coordinator_dir());
+        [CLIENT_KEY, SIGNING_KEY]
+            .iter()
+            .all(|f| signer.join(f).is_file())
+            && [SERVER_KEY, PUBLIC_KEY, VERIFYING_KEY]
+                .iter()
+                .all(|f| coordinator.join(f).is_file())
+    }
+
+    pub fn save_signer<T: KeyScalar>(
+        &self,
+        client_key: &ClientKey,
+        x_sch: &SigningKey<T>,
+        passphrase: &[u8],
+    ) -> Result<()> {
+        let dir = self.signer_dir();
+        create_private_dir(&dir)?;
+        write(
+            dir.join(CLIENT_KEY),
+            &client_key_to_bytes(client_key, &self.profile, passphrase)?,
+        )?;
+        x_sch.save(dir.join(SIGNING_KEY), passphrase)
+    }
+
+    pub fn load_signer<T: KeyScalar>(
+        &self,
+        passphrase: &[u8],
+    ) -> Result<(ClientKey, SigningKey<T>)> {
+        let dir = self.signer_dir();
+        let bytes = read(dir.join(CLIENT_KEY))?;
+        let client_key = client_key_from_bytes(&bytes, &self.profile, passphrase)?;
+        let x_sch = SigningKey::load(dir.join(SIGNING_KEY), passphrase)?;
+        Ok((client_key, x_sch))
+    }
+
+    pub fn save_coordinator<T: KeyScalar>(
+        &self,
+        server_key: &ServerKey,
+        public_key: &CompactPublicKey,
+        y_sch: T,
+    ) -> Result<()> {
+        let dir = self.coordinator_dir();
+        fs::create_dir_all(&dir).map_err(|e| storage_err(e.to_string()))?;
+        write(
+            dir.join(SERVER_KEY),
+            &fhe_key_to_bytes(server_key, &self.profile)?,
+        )?;
+        write(
+            dir.join(PUBLIC_KEY),
+            &fhe_key_to_bytes(public_key, &self.profile)?,
+        )?;
+        write(dir.join(VERIFYING_KEY), &verifying_key_to_bytes(y_sch))
+    }
+
+    pub fn load_coordinator<T: KeyScalar>(&self) -> Result<(ServerKey, CompactPublicKey, T)> {
+        let dir = self.coordinator_dir();
+        Ok((
+            fhe_key_from_bytes(&read(dir.join(SERVER_KEY))?, &self.profile)?,
+            fhe_key_from_bytes(&read(dir.join(PUBLIC_KEY))?, &self.profile)?,
+            verifying_key_from_bytes(&read(dir.join(VERIFYING_KEY))?)?,
+        ))
+    }
+}
+
+// Only the owner may list the signer directory
+fn create_private_dir(dir: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir).map_err(|e| storage_err(e.to_string()))
+}
+
+// ChaCha20-Poly1305 keyed by Argon2id(passphrase, salt)
+fn cipher(passphrase: &[u8], salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key[..])
+        .map_err(|e| storage_err(e.to_string()))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key[..])))
+}
+
+fn storage_err(msg: String) -> SchnorrFheError {
+    SchnorrFheError::KeyStorage(msg)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schnorr;
+    use crate::utils;
+    use tfhe::prelude::*;
+    use tfhe::{set_server_key, FheUint8};
+
+    #[test]
+    fn test_key_file_header() {
+        let (kind, params) = (KeyKind::Verifying, ParamSet::Schnorr8);
+        let bytes = encode(kind, params, b"payload");
+        assert_eq!(bytes.len(), HEADER_LEN + 7 + CHECKSUM_LEN);
+        assert_eq!(&bytes[..4], b"SFHE");
+        assert_eq!(decode(&bytes, kind, params).unwrap(), b"payload");
+
+        // Wrong kind or parameter set
+        assert!(decode(&bytes, KeyKind::Signing, params).is_err());
+        assert!(decode(&bytes, kind, ParamSet::Schnorr32).is_err());
+        // Any flipped byte, in the header, the payload or the checksum
+        for i in 0..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 1;
+            let err = decode(&corrupted, kind, params);
+            assert!(
+                matches!(err, Err(SchnorrFheError::KeyStorage(_))),
+                "byte {}",
+                i
+            );
+        }
+        // Truncated, or another version
+        assert!(decode(&bytes[..bytes.len() - 1], kind, params).is_err());
+        let mut v1 = bytes[..HEADER_LEN + 7].to_vec();
+        v1[4] = 1;
+        let checksum = Blake2s256::digest(&v1);
+        v1.extend_from_slice(&checksum);
+        let err = decode(&v1, kind, params).unwrap_err();
+        assert!(err.to_string().contains("version 1"));
+    }
+
+    #[test]
+    fn test_keystore() {
+        let (client_key, server_key, public_key) = utils::init_keys();
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let dir = std::env::temp_dir().join("schnorr_fhe_test_keystore");
+        let keystore = Keystore::new(&dir, FheProfile::default());
+        assert!(!keystore.exists());
+        keystore.save_signer(&client_key, &x_sch, b"pass").unwrap();
+        keystore
+            .save_coordinator(&server_key, &public_key, y_sch)
+            .unwrap();
+        assert!(keystore.exists());
+
+        let loaded_signer = keystore.load_signer::<u8>(b"pass");
+        let loaded_coordinator = keystore.load_coordinator::<u8>();
+        let wrong = keystore.load_signer::<u8>(b"wrong");
+        let wrong_width = keystore.load_coordinator::<u32>();
+        let other_profile = FheProfile::from_name("m1c1").unwrap();
+        let wrong_profile = Keystore::new(&dir, other_profile).load_coordinator::<u8>();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(wrong, Err(SchnorrFheError::KeyStorage(_))));
+        assert!(wrong_width.is_err());
+        assert!(wrong_profile.is_err());
+
+        // The loaded keys work together
+        let (client_key, x_sch_loaded) = loaded_signer.unwrap();
+        let (server_key, public_key, y_sch_loaded) = loaded_coordinator.unwrap();
+        assert_eq!(x_sch_loaded.expose_secret(), x_sch.expose_secret());
+        assert_eq!(y_sch_loaded, y_sch);
+        set_server_key(server_key);
+        let a = FheUint8::encrypt(3u8, &public_key);
+        let b = FheUint8::encrypt(4u8, &client_key);
+        let c: u8 = (a + b).decrypt(&client_key);
+        assert_eq!(c, 7);
+    }
+}
@@ -1,6 +1,30 @@
 pub mod utils;
+pub mod backend;
 pub mod constants;
+pub mod coordinator;
+pub mod error;
+pub mod cost;
+pub mod griffin;
+pub mod hash;
+pub mod hd;
+pub mod keystore;
+pub mod lut;
+pub mod merkle;
+pub mod message;
+pub mod modint;
+pub mod mimc;
+pub mod nonce;
+pub mod oracle;
+pub mod parallel;
 pub mod poseidon;
 pub mod poseidon_p8;
+pub mod profile;
+pub mod rescue;
+pub mod rotation;
+pub mod schnorr;
+pub mod signing_key;
+pub mod sim;
+pub mod threshold;
+pub mod trace;
 pub mod main_p8;
 pub mod main_p32;
\ No newline at end of file
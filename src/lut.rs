@@ -0,0 +1,164 @@
+use tfhe::prelude::*;
+
+use crate::backend::{Backend, FheUint};
+use crate::parallel;
+use crate::utils::clear_modexp_64 as clear_modexp;
+
+/*
+ * Programmable bootstrapping (PBS) lookup tables for small primes.
+ *
+ * For the 8-bit dev field (`POSEIDON_P_8 = 199`), any univariate map over the
+ * 8-bit container fits in a lookup table that TFHE evaluates during
+ * bootstrapping. This replaces chains of FHE mults and `%` (each of which
+ * costs many PBS on radix blocks) with a single table lookup:
+ * - S-box: x -> x^alpha (mod p), optionally fused with the round-constant add
+ * - modular reduction: x -> x (mod p)
+ * - scalar modular mult: x -> c * x (mod p), e.g. for MDS matrix entries
+ * - fixed-base modexp: k -> g^k (mod p), e.g. for the Schnorr commitment
+ *
+ * tfhe evaluates the tables with WoPBS (`FheBootstrap::map`), so the keys
+ * need function evaluation enabled (see `FheProfile::config`). A table has one
+ * entry per input value, so only the 8-bit containers use them: sums that
+ * outgrow 8 bits (e.g. an MDS row) are added in FheUint16 and reduced with
+ * `%`, not with a 2^16-entry table. The 32-bit parameters use no tables.
+ *
+ * NOTE: the tables are built in the clear (the functions are public) and
+ * only the input is encrypted.
+ *
+ * The lookups are generic over the backend (see `backend.rs`), e.g. with
+ * E = FheUint8 for the `_8` variants.
+ */
+
+// Precompute the table for x -> x^exp (mod modval) over all 8-bit inputs
+pub fn modexp_table_8(exp: u8, modval: u8) -> [u8; 256] {
+    let mut table = [0u8; 256];
+    for (x, entry) in table.iter_mut().enumerate() {
+        *entry = clear_modexp(x as u64, exp as u64, modval as u64) as u8;
+    }
+    table
+}
+
+// LUT modular reduction: x -> x (mod modval)
+pub fn fhe_lut_mod_8<E: FheUint<u8>>(x: &E, modval: u8) -> E {
+    x.map(|v| v % modval as u64)
+}
+
+// LUT S-box: x -> x^exp (mod modval); inputs are expected to be already (mod modval)
+pub fn fhe_lut_modexp_8<E: FheUint<u8>>(x: &E, exp: u8, modval: u8) -> E {
+    let table = modexp_table_8(exp, modval);
+    x.map(|v| table[v as usize] as u64)
+}
+
+// LUT round-constant add fused with the S-box: x -> (x + c)^exp (mod modval).
+// With exp = 1 this is a plain modular add of a constant (partial-round lanes).
+pub fn fhe_lut_add_modexp_8<E: FheUint<u8>>(x: &E, c: u8, exp: u8, modval: u8) -> E {
+    x.map(|v| clear_modexp(v + c as u64, exp as u64, modval as u64))
+}
+
+// LUT quadratic: x -> x^2 + a * x + b (mod modval), with x reduced (mod modval) first
+pub fn fhe_lut_quadratic_8<E: FheUint<u8>>(x: &E, a: u8, b: u8, modval: u8) -> E {
+    let (a, b, modval) = (a as u64, b as u64, modval as u64);
+    x.map(|v| {
+        let v = v % modval;
+        (v * v + a * v + b) % modval
+    })
+}
+
+// LUT scalar modular mult: x -> c * x (mod modval)
+pub fn fhe_lut_scalar_modmul_8<E: FheUint<u8>>(x: &E, c: u8, modval: u8) -> E {
+    x.map(|v| (v * c as u64) % modval as u64)
+}
+
+// LUT matrix-vector product (mod prime) for a t x t matrix of clear entries.
+// Each entry is one lookup x -> m_ij * x (mod p); the t terms are summed in
+// FheUint16 (t * (p - 1) < 2^16) and reduced with `%`.
+pub fn fhe_lut_apply_matrix_8<B: Backend, const T: usize>(
+    inputs: Vec<B::U8>,
+    matrix: &[[u16; T]; T],
+    prime: u8,
+) -> Vec<B::U8> {
+    parallel::map((0..inputs.len()).collect(), |i| {
+        let mut output: B::U16 =
+            fhe_lut_scalar_modmul_8(&inputs[0], matrix[i][0] as u8, prime).cast_into();
+        for j in 1..inputs.len() {
+            let mult: B::U16 =
+                fhe_lut_scalar_modmul_8(&inputs[j], matrix[i][j] as u8, prime).cast_into();
+            output = output + &mult;
+        }
+        (output % prime as u16).cast_into()
+    })
+}
+
+// LUT fixed-base modexp: k -> base^k (mod modval), with an FHE exponent and a
+// clear base. Computes FHE(g)^FHE(k) in one PBS instead of one
+// square-and-multiply step per exponent bit (see `utils::fhe2_modexp_16`).
+pub fn fhe_lut_fixed_base_modexp_8<E: FheUint<u8>>(exp_enc: &E, base: u8, modval: u8) -> E {
+    exp_enc.map(|k| clear_modexp(base as u64, k, modval as u64))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+    use crate::utils;
+    use tfhe::{set_server_key, FheUint8};
+
+    #[test]
+    fn test_modexp_table_8() {
+        let table = modexp_table_8(constants::POSEIDON_ALPHA, constants::POSEIDON_P_8);
+        for x in 0..constants::POSEIDON_P_8 {
+            let expected = (x as u64).pow(5) % constants::POSEIDON_P_8 as u64;
+            assert_eq!(table[x as usize] as u64, expected);
+        }
+    }
+
+    #[test]
+    fn test_fhe_lut_sbox_and_mod() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+        let p = constants::POSEIDON_P_8;
+
+        // S-box
+        let x = 123u8;
+        let x_enc = FheUint8::encrypt(x, &client_key);
+        let sbox_enc = fhe_lut_modexp_8(&x_enc, constants::POSEIDON_ALPHA, p);
+        let sbox: u8 = sbox_enc.decrypt(&client_key);
+        assert_eq!(sbox as u64, (x as u64).pow(5) % p as u64);
+
+        // Reduction of a value that is not below p
+        let y = 230u8;
+        let y_enc = FheUint8::encrypt(y, &client_key);
+        let reduced: u8 = fhe_lut_mod_8(&y_enc, p).decrypt(&client_key);
+        assert_eq!(reduced, y % p);
+
+        // Quadratic, e.g. Griffin's x^2 + a * x + b
+        let quad: u8 = fhe_lut_quadratic_8(&y_enc, 3, 7, p).decrypt(&client_key);
+        let y = (y % p) as u64;
+        assert_eq!(quad as u64, (y * y + 3 * y + 7) % p as u64);
+
+        // Scalar modular mult
+        let prod: u8 = fhe_lut_scalar_modmul_8(&x_enc, 166, p).decrypt(&client_key);
+        assert_eq!(prod as u64, (x as u64 * 166) % p as u64);
+    }
+
+    #[test]
+    fn test_fhe_lut_fixed_base_modexp_8() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let k = 7u8;
+        let k_enc = FheUint8::encrypt(k, &client_key);
+        let r_enc = fhe_lut_fixed_base_modexp_8(&k_enc, constants::SCH_G_8, constants::SCH_P_8);
+        let r: u8 = r_enc.decrypt(&client_key);
+        let expected = clear_modexp(
+            constants::SCH_G_8 as u64,
+            k as u64,
+            constants::SCH_P_8 as u64,
+        );
+        assert_eq!(r as u64, expected);
+    }
+}
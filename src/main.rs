@@ -1,22 +1,45 @@
 extern crate chrono;
-use chrono::Local;
-use num_bigint::BigUint;
-use num_traits::ToPrimitive;
-use rand::rngs::OsRng;
-use rand::Rng;
+
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use tfhe::prelude::*;
 use tfhe::set_server_key;
-use tfhe::{FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
+use tfhe::{ClientKey, CompactPublicKey, FheUint8, ServerKey};
 
+mod backend;
 mod constants;
+mod coordinator;
+mod error;
+mod cost;
+mod griffin;
+mod hash;
+mod hd;
+mod keystore;
+mod lut;
+mod message;
+mod mimc;
+mod modint;
+mod nonce;
+mod oracle;
+mod parallel;
 mod poseidon;
 mod poseidon_p8;
+mod profile;
+mod rescue;
+mod rotation;
+mod schnorr;
+mod signing_key;
+mod sim;
+mod threshold;
+mod trace;
 mod utils;
 // use crate::constants;
 // use crate::poseidon_p8;
 // use crate::utils;
 
+use hash::FheHash;
+
 
 /*
  * Implementation choices:
@@ -27,116 +50,217 @@ mod utils;
  *   - We will need to cast between FheUint types
  *   - we will have << 8-bit values in the FheUint16 container
  * - Genreally, for a given bit-width n, we will need 2n-bit FheUint containers
+ *
+ * The hash function is a type parameter of the signer (see `hash.rs`); pick it
+ * with the first command line argument, e.g. `cargo run --release -- rescue`.
+ * Add `--public-msg` to sign a public message with only the key encrypted.
+ * Add `--trace=<file>` to write a Chrome trace JSON timeline of the signing
+ * phases (see `trace.rs`).
+ * Add `--debug-oracle` to first sign one message with every intermediate
+ * checked against the clear reference (see `oracle.rs`), and to log the clear
+ * signature next to each decrypted one.
+ * Add `--hedged` to hedge each nonce with fresh encrypted randomness (see
+ * `nonce.rs`).
+ * Add `--keystore=<dir>` to load the FHE and Schnorr keys from a keystore
+ * directory, generating and saving them on the first run (see `keystore.rs`);
+ * the signer keys are sealed with the passphrase in SCHNORR_FHE_PASSPHRASE.
+ * Add `--profile=<name>` to generate the FHE keys with another tfhe parameter
+ * set than the default m2c2, e.g. `--profile=m1c1` (see `profile.rs`).
  */
 
+// Command line options of `run`
+struct Opts {
+    public_msg: bool,
+    debug_oracle: bool,
+    hedged: bool,
+    keystore: Option<PathBuf>,
+    profile: profile::FheProfile,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let (client_key, server_keys, public_key) = utils::init_keys();
-    set_server_key(server_keys);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = Opts {
+        public_msg: args.iter().any(|a| a == "--public-msg"),
+        debug_oracle: args.iter().any(|a| a == "--debug-oracle"),
+        hedged: args.iter().any(|a| a == "--hedged"),
+        keystore: args
+            .iter()
+            .find_map(|a| a.strip_prefix("--keystore="))
+            .map(PathBuf::from),
+        profile: match args.iter().find_map(|a| a.strip_prefix("--profile=")) {
+            Some(name) => profile::FheProfile::from_name(name)?,
+            None => profile::FheProfile::default(),
+        },
+    };
+    let _trace_guard = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--trace="))
+        .map(trace::chrome_trace);
+    let hash_name = args
+        .iter()
+        .find(|a| !a.starts_with("--"))
+        .cloned()
+        .unwrap_or("poseidon".to_string());
+    match hash_name.as_str() {
+        "poseidon" => run::<poseidon_p8::PoseidonP8>(&opts),
+        "poseidon_rf2_rp1" => run::<poseidon_p8::PoseidonP8Rf2Rp1>(&opts),
+        "rescue" => run::<rescue::RescueP8>(&opts),
+        "mimc" => run::<mimc::MimcP8>(&opts),
+        "griffin" => run::<griffin::GriffinP8>(&opts),
+        _ => Err(format!("unknown hash: {hash_name}").into()),
+    }
+}
+
+// FHE keys, then Schnorr keys x_sch and y_sch
+type Keys = (ClientKey, ServerKey, CompactPublicKey, signing_key::SigningKey, u8);
+
+// The keys, from the keystore if there is one
+fn init_keys(
+    keystore: Option<&keystore::Keystore>,
+    profile: &profile::FheProfile,
+) -> Result<Keys, Box<dyn std::error::Error>> {
+    let passphrase = || {
+        std::env::var("SCHNORR_FHE_PASSPHRASE")
+            .map_err(|_| "--keystore needs the passphrase in SCHNORR_FHE_PASSPHRASE")
+    };
+    if let Some(keystore) = keystore.filter(|keystore| keystore.exists()) {
+        utils::log("Loading keys from the keystore...");
+        let (client_key, x_sch) = keystore.load_signer(passphrase()?.as_bytes())?;
+        let (server_key, public_key, y_sch) = keystore.load_coordinator()?;
+        return Ok((client_key, server_key, public_key, x_sch, y_sch));
+    }
+    let (client_key, server_key, public_key) = utils::init_keys_with(profile);
+    utils::log("Generating plaintext Schnorr keys...");
+    let (x_sch, y_sch) = schnorr::keygen()?;
+    if let Some(keystore) = keystore {
+        utils::log("Saving keys to the keystore...");
+        keystore.save_signer(&client_key, &x_sch, passphrase()?.as_bytes())?;
+        keystore.save_coordinator(&server_key, &public_key, y_sch)?;
+    }
+    Ok((client_key, server_key, public_key, x_sch, y_sch))
+}
+
+fn run<H: FheHash<Enc = FheUint8, Clear = u8>>(
+    opts: &Opts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Opts {
+        public_msg,
+        debug_oracle,
+        hedged,
+        ..
+    } = *opts;
 
     ////////////////////////////////////////////////////////////////////////////
     /////////// Initialization: Schnorr params, keys, and encryption ///////////
     // TODO: for now, we focus on 8-bit p, keys, and message, and 16-bit q
     ////////////////////////////////////////////////////////////////////////////
-    utils::log("Generating plaintext Schnorr keys...");
-    let mut rng = OsRng;
-    let x_sch = rng.gen_range(1..constants::SCH_Q_4);
-    let g_sch = BigUint::from(constants::SCH_G_8);
-    let p_sch = BigUint::from(constants::SCH_P_8);
-    let q_sch = constants::SCH_Q_4;
-    let y_sch = g_sch.modpow(&BigUint::from(x_sch), &p_sch);
-    // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
+    let keystore = opts
+        .keystore
+        .as_ref()
+        .map(|dir| keystore::Keystore::new(dir, opts.profile));
+    let (client_key, server_keys, public_key, x_sch, y_sch) =
+        init_keys(keystore.as_ref(), &opts.profile)?;
+    // Shared, not copied, with the debug oracle
+    let client_key = Arc::new(client_key);
+    set_server_key(server_keys.clone());
+    // The secret x_sch is never logged (see `signing_key.rs`)
     utils::log(&format!(
-        "x_sch: {}, g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
-        x_sch, g_sch, p_sch, q_sch, y_sch
+        "g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
+        constants::SCH_G,
+        constants::SCH_P,
+        constants::SCH_Q,
+        y_sch
     ));
 
     // Encrypt the signing key
     utils::log("Encrypting signing Schnorr key...");
-    let x_sch_enc = FheUint8::encrypt(x_sch, &client_key);
+    let x_sch_enc: FheUint8 = x_sch.encrypt(&*client_key);
 
     ////////////////////////////////////////////////////////////////////////////
     ///////////////////////////////// Signing //////////////////////////////////
     ////////////////////////////////////////////////////////////////////////////
-    utils::log("FHE signing @ server ...");
-    // let msg: u8 = 0xAAAAAAAA; // EXP: this is some random 8-bit message to sign; swappable
-    let msg: u8 = 0xAA; // EXP: this is some random 8-bit message to sign; swappable
-    let msg_enc: FheUint8 = FheUint8::encrypt(msg, &public_key);
-    let zero_enc: FheUint8 = FheUint8::encrypt(0u8, &public_key);
-    let zero_enc2: FheUint8 = FheUint8::encrypt(0u8, &public_key);
-
-    // Generate pseudo-random nonce, as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q)
-    utils::log("\t Pseudorandomness as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q) ...");
-    let k_enc: FheUint8 =
-        poseidon_p8::poseidon_p8([&msg_enc, &x_sch_enc, &zero_enc, &zero_enc2]);
-    let k_enc: FheUint8 = &k_enc % ((q_sch - 1) as u8) + 1; // ensures k in [1, q-1]
-    let k_enc_up: FheUint16 = (&k_enc).clone().cast_into();
-
-    // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
-    let k_enc_dec: u8 = k_enc.decrypt(&client_key);
-    utils::log(&format!(
-        "DEBUG k_clear from direct hash: {}",
-        poseidon_p8::poseidon_p8_clear([msg, x_sch, 0u8, 0u8])
-    ));
-    utils::log(&format!("DEBUG k_enc_dec: {}", k_enc_dec));
-
-    // Compute exponentiation: FHE(r) = FHE(g)^FHE(k) (mod p).
-    // NOTE: need to move up to 16-bit FheUint to handle this operation
-    utils::log("\t Exponentiating: FHE(k) = FHE(r) = FHE(g)^FHE(k) (mod p) ...");
-    let g_enc_up: FheUint16 = FheUint8::encrypt(constants::SCH_G, &public_key).cast_into();
-    let p_sch_up: u16 = p_sch.to_u16().expect("p_sch is too large");
-    let r_enc_up: FheUint16 = utils::fhe2_modexp_16(&g_enc_up, &k_enc_up, p_sch_up);
-    let r_enc: FheUint8 = r_enc_up.cast_into(); // already (mod p)
-
-    // Compute hash of the message: FHE(h) = H(FHE(m), FHE(r)) (mod q)
-    utils::log("\t Hashing: FHE(h) = H(FHE(m), FHE(r)) (mod q) ...");
-    let h_enc: FheUint8 =
-        poseidon_p8::poseidon_p8([&msg_enc, &r_enc, &zero_enc, &zero_enc2]);
-    let h_enc: FheUint8 = &h_enc % (q_sch as u8); // NOTE: ensures h in [0, q-1]
-
-    // Compute signature: FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q)
-    utils::log("\t Signing: FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q) ...");
-    let x_sch_enc_up: FheUint16 = x_sch_enc.clone().cast_into();
-    let h_enc_up: FheUint16 = (&h_enc).clone().cast_into();
-    let hx_enc_up: FheUint16 = (&x_sch_enc_up * &h_enc_up) % (q_sch as u16);
-    // Extra add q_sch to handle case if hx_enc_up > k_enc_up;
-    // since we use 2x bit width there is no overflow.
-    let s_enc_up: FheUint16 = (&k_enc_up + (q_sch as u16) - &hx_enc_up) % (q_sch as u16);
-    let s_enc: FheUint8 = s_enc_up.clone().cast_into();
-
-    utils::log("FHE decryption of FHE(s), FHE(h) @ client ...");
-    let s_dec: u8 = (&s_enc).clone().decrypt(&client_key);
-    let h_dec: u8 = (&h_enc).clone().decrypt(&client_key);
-
-    //  DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
-    let hx_dec: u16 = (x_sch as u16 * h_dec as u16) % (q_sch as u16);
-    let hx_enc_dec: u16 = hx_enc_up.clone().decrypt(&client_key);  // hx_enc_up not used anymore
-    let r_enc_dec: u8 = (&r_enc).clone().decrypt(&client_key);
-    let k_enc_dec: u16 = (&k_enc_up).clone().decrypt(&client_key);
-    utils::log(&format!(
-        "k_enc_dec: {}, r_enc_dec: {}, h_dec: {}, hx_dec: {}, hx_enc_dec: {}, s_dec: {}",
-        k_enc_dec, r_enc_dec, h_dec, hx_dec, hx_enc_dec, s_dec,
-    ));
+    utils::log(&format!("FHE signing @ server with {} ...", H::NAME));
+    // EXP: these are some byte-string messages to sign as a batch; swappable
+    let msgs: Vec<&[u8]> = vec![b"Schnorr-FHE", b"Schnorr-FHE, batched"];
+    let msgs_enc: Vec<Vec<FheUint8>> = match public_msg {
+        true => Vec::new(),
+        false => msgs
+            .iter()
+            .map(|msg| message::encrypt_message(msg, &public_key))
+            .collect::<Result<_, _>>()?,
+    };
+    let visibilities: Vec<schnorr::MessageVisibility> = match public_msg {
+        true => msgs.iter().map(|msg| schnorr::MessageVisibility::Public(msg)).collect(),
+        false => msgs_enc
+            .iter()
+            .map(|msg_enc| schnorr::MessageVisibility::Encrypted(msg_enc))
+            .collect(),
+    };
+    // Fresh randomness a per message for hedged nonces, a = 0 otherwise
+    let auxs: Vec<u8> = msgs
+        .iter()
+        .map(|_| if hedged { nonce::aux_rand() } else { 0 })
+        .collect();
+    let auxs_enc: Vec<FheUint8> = auxs
+        .iter()
+        .map(|aux| FheUint8::encrypt(*aux, &public_key))
+        .collect();
+    let nonce_modes: Vec<schnorr::NonceMode> = match hedged {
+        true => auxs_enc.iter().map(schnorr::NonceMode::Hedged).collect(),
+        false => vec![schnorr::NonceMode::Deterministic; msgs.len()],
+    };
+    if debug_oracle {
+        utils::log("FHE signing with the debug oracle ...");
+        let oracle = oracle::DebugOracle::new(client_key.clone());
+        let (sig_enc, report) =
+            oracle.run(|| schnorr::sign_enc::<H>(&x_sch_enc, visibilities[0], nonce_modes[0]));
+        sig_enc?;
+        utils::log(&format!(
+            "DebugOracle: {} values checked, {} divergences",
+            report.n_checks,
+            report.divergences.len()
+        ));
+    }
+    let n_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let coordinator = coordinator::Coordinator::<H>::new(server_keys, x_sch_enc, n_threads)?;
+    let reqs: Vec<coordinator::SignRequest> = visibilities
+        .iter()
+        .zip(nonce_modes.iter())
+        .map(|(msg, nonce_mode)| coordinator::SignRequest {
+            msg: *msg,
+            nonce_mode: *nonce_mode,
+        })
+        .collect();
+    let sigs_enc = coordinator.sign_batch(&reqs);
 
-    ////////////////////////////////////////////////////////////////////////////
-    ///////////////////////////////// Verification /////////////////////////////
-    ////////////////////////////////////////////////////////////////////////////
-    utils::log("FHE signature verification @ server ...");
-    // Compute r_v = g^s * y^h (mod p); here, g and y are public and s and h are decrypted
-    let r_v_left = g_sch.modpow(&BigUint::from(s_dec), &p_sch);
-    let r_v_right = y_sch.modpow(&BigUint::from(h_dec), &p_sch);
-    let r_v = (&r_v_left * &r_v_right) % &p_sch;
-    let r_v = r_v.to_u8().expect("r_v is too large");
-    // Compute clear hash of the message: h_v = H(m, r_v) (mod q)
-    let h_v = poseidon_p8::poseidon_p8_clear([msg, r_v, 0u8, 0u8]);
-    let h_v = h_v % (q_sch as u8);
-    // Verify if h_v == h_dec
-    // DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
-    utils::log(&format!(
-        "h_v: {}, h_dec: {}, r_v: {}, r_v_left: {}, r_v_right: {}",
-        h_v, h_dec, r_v, &r_v_left, &r_v_right
-    ));
-    let is_verified = h_v == h_dec;
-    utils::log(&format!("Signature verification: {}", is_verified));
+    for ((msg, sig_enc), aux) in msgs.iter().zip(sigs_enc.iter()).zip(auxs) {
+        // A message that failed to sign does not stop the batch
+        let sig_enc = match sig_enc {
+            Ok(sig_enc) => sig_enc,
+            Err(e) => {
+                utils::log(&format!("FHE signing failed: {}", e));
+                continue;
+            }
+        };
+        utils::log("FHE decryption of FHE(s), FHE(h) @ client ...");
+        let sig = sig_enc.decrypt(&client_key);
+
+        // The clear signature needs the secret key; debugging only
+        if debug_oracle {
+            let sig_clear = schnorr::sign_clear_hedged::<H>(&x_sch, msg, aux)?;
+            utils::log(&format!(
+                "s_dec: {}, h_dec: {}, s_clear: {}, h_clear: {}",
+                sig.s, sig.h, sig_clear.s, sig_clear.h
+            ));
+        }
+
+        ////////////////////////////////////////////////////////////////////////
+        /////////////////////////////// Verification ///////////////////////////
+        ////////////////////////////////////////////////////////////////////////
+        utils::log("FHE signature verification @ server ...");
+        let is_verified = schnorr::verify::<H>(y_sch, msg, &sig).is_ok();
+        utils::log(&format!("Signature verification: {}", is_verified));
+    }
 
     Ok(())
 }
@@ -2,8 +2,6 @@ extern crate chrono;
 use chrono::Local;
 use num_bigint::BigUint;
 use num_traits::ToPrimitive;
-use rand::rngs::OsRng;
-use rand::Rng;
 
 use tfhe::prelude::*;
 use tfhe::set_server_key;
@@ -13,8 +11,13 @@ use tfhe::{FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
 // mod poseidon;
 // mod poseidon_p8;
 // mod utils;
+use crate::backend::Tfhe;
 use crate::constants;
-use crate::poseidon;
+use crate::hash::{ClearHash, FheHash, MixedInput};
+use crate::modint::{ModInt, Modulus};
+use crate::nonce;
+use crate::poseidon::{self, PoseidonP32};
+use crate::signing_key::SigningKey;
 use crate::utils;
 
 /*
@@ -37,21 +40,20 @@ fn main_p32() -> Result<(), Box<dyn std::error::Error>> {
     // TODO: for now, we focus on 32-bit p, keys, and message, and 16-bit q
     ////////////////////////////////////////////////////////////////////////////
     utils::log("Generating plaintext Schnorr keys...");
-    let mut rng = OsRng;
-    let x_sch = rng.gen_range(1..constants::SCH_Q_16);
+    let x_sch = SigningKey::<u32>::generate();
     let g_sch = BigUint::from(constants::SCH_G_32);
     let p_sch = BigUint::from(constants::SCH_P_32);
     let q_sch = constants::SCH_Q_16;
-    let y_sch = g_sch.modpow(&BigUint::from(x_sch), &p_sch);
+    let y_sch = g_sch.modpow(&BigUint::from(x_sch.expose_secret()), &p_sch);
     // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
     utils::log(&format!(
-        "x_sch: {}, g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
-        x_sch, g_sch, p_sch, q_sch, y_sch
+        "g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
+        g_sch, p_sch, q_sch, y_sch
     ));
 
     // Encrypt the signing key
     utils::log("Encrypting signing Schnorr key...");
-    let x_sch_enc = FheUint32::encrypt(x_sch, &client_key);
+    let x_sch_enc: FheUint32 = x_sch.encrypt(&client_key);
 
     ////////////////////////////////////////////////////////////////////////////
     ///////////////////////////////// Signing //////////////////////////////////
@@ -59,21 +61,28 @@ fn main_p32() -> Result<(), Box<dyn std::error::Error>> {
     utils::log("FHE signing @ server ...");
     let msg: u32 = 0xAAAAAAAA; // EXP: this is some random 32-bit message to sign; swappable
     let msg_enc: FheUint32 = FheUint32::encrypt(msg, &public_key);
-    let zero_enc: FheUint32 = FheUint32::encrypt(0u32, &public_key);
-    let zero_enc2: FheUint32 = FheUint32::encrypt(0u32, &public_key);
-
-    // Generate pseudo-random nonce, as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q)
-    utils::log("\t Pseudorandomness as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q) ...");
-    let k_enc: FheUint32 =
-        poseidon::poseidon_p32([&msg_enc, &x_sch_enc, &zero_enc, &zero_enc2]);
-    let k_enc: FheUint32 = &k_enc % ((q_sch - 1) as u32) + 1; // ensures k in [1, q-1]
-    let k_enc_up: FheUint64 = (&k_enc).clone().cast_into();
+    // The zero-padding inputs of the hash are public, so they stay in the clear
+
+    // Generate pseudo-random nonce, as FHE(k) = (FHE(h_1) * p + FHE(h_2)) (mod q - 1) + 1
+    // with FHE(h_i) = H(FHE(m), FHE(x_sch), i), without bias (see `nonce.rs`)
+    utils::log("\t Pseudorandomness as FHE(k) from H(FHE(m), FHE(x_sch), i) ...");
+    let p_hash = constants::POSEIDON_P_32 as u64;
+    let h_enc: Vec<FheUint32> = nonce::hash_nonce(
+        &MixedInput::Enc(msg_enc.clone()),
+        &x_sch_enc,
+        &MixedInput::Clear(0u32), // deterministic nonce
+        PoseidonP32::hash_mixed,
+    )?;
+    let (k_enc, k_enc_up): (FheUint32, FheUint64) =
+        nonce::reduce_wide_64::<Tfhe>(&h_enc, p_hash, q_sch as u64)?;
 
     // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
     let k_enc_dec: u64 = k_enc.decrypt(&client_key);
+    let h_clear = nonce::hash_nonce_clear(msg, x_sch.expose_secret(), 0, PoseidonP32::hash_clear)?;
+    let h_clear: Vec<u64> = h_clear.into_iter().map(u64::from).collect();
     utils::log(&format!(
         "DEBUG k_clear from direct hash: {}",
-        poseidon::poseidon_p32_clear([msg, x_sch, 0u32, 0u32])
+        nonce::reduce_wide_clear(&h_clear, p_hash, q_sch as u64)?
     ));
     utils::log(&format!("DEBUG k_enc_dec: {}", k_enc_dec));
 
@@ -82,13 +91,18 @@ fn main_p32() -> Result<(), Box<dyn std::error::Error>> {
     utils::log("\t Exponentiating: FHE(k) = FHE(r) = FHE(g)^FHE(k) (mod p) ...");
     let g_enc_up: FheUint64 = FheUint32::encrypt(constants::SCH_G, &public_key).cast_into();
     let p_sch_up: u64 = p_sch.to_u64().expect("p_sch is too large");
-    let r_enc_up: FheUint64 = utils::fhe2_modexp_64(&g_enc_up, &k_enc_up, p_sch_up);
+    let g_enc_up = ModInt::new(g_enc_up, Modulus::new(p_sch_up)?);
+    let r_enc_up: FheUint64 = utils::fhe2_modexp_64(&g_enc_up, &k_enc_up).into_inner();
     let r_enc: FheUint32 = r_enc_up.cast_into(); // already (mod p)
 
     // Compute hash of the message: FHE(h) = H(FHE(m), FHE(r)) (mod q)
     utils::log("\t Hashing: FHE(h) = H(FHE(m), FHE(r)) (mod q) ...");
-    let h_enc: FheUint32 =
-        poseidon::poseidon_p32([&msg_enc, &r_enc, &zero_enc, &zero_enc2]);
+    let h_enc: FheUint32 = poseidon::poseidon_p32_mixed([
+        MixedInput::Enc(msg_enc.clone()),
+        MixedInput::Enc(r_enc.clone()),
+        MixedInput::Clear(0u32),
+        MixedInput::Clear(0u32),
+    ])?;
     let h_enc: FheUint32 = &h_enc % (q_sch as u32); // NOTE: ensures h in [0, q-1]
 
     // Compute signature: FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q)
@@ -104,7 +118,7 @@ fn main_p32() -> Result<(), Box<dyn std::error::Error>> {
     let h_dec: u32 = h_enc.decrypt(&client_key);
 
     //  DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
-    let hx_dec: u64 = (x_sch as u64 * h_dec as u64) % (q_sch as u64);
+    let hx_dec: u64 = (x_sch.expose_secret() as u64 * h_dec as u64) % (q_sch as u64);
     let hx_enc_dec: u64 = (&hx_enc_up).clone().decrypt(&client_key);  // hx_enc_up not used anymore
     let r_enc_dec: u32 = (&r_enc).clone().decrypt(&client_key);
     let k_enc_dec: u64 = (&k_enc_up).clone().decrypt(&client_key);
@@ -123,7 +137,7 @@ fn main_p32() -> Result<(), Box<dyn std::error::Error>> {
     let r_v = (&r_v_left * &r_v_right) % &p_sch;
     let r_v = r_v.to_u32().expect("r_v is too large");
     // Compute clear hash of the message: h_v = H(m, r_v) (mod q)
-    let h_v = poseidon::poseidon_p32_clear([msg, r_v, 0u32, 0u32]);
+    let h_v = poseidon::poseidon_p32_clear([msg, r_v, 0u32, 0u32])?;
     let h_v = h_v % (q_sch as u32);
     // Verify if h_v == h_dec
     // DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
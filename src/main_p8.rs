@@ -2,8 +2,6 @@ extern crate chrono;
 use chrono::Local;
 use num_bigint::BigUint;
 use num_traits::ToPrimitive;
-use rand::rngs::OsRng;
-use rand::Rng;
 
 use tfhe::prelude::*;
 use tfhe::set_server_key;
@@ -13,8 +11,13 @@ use tfhe::{FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
 // mod poseidon;
 // mod poseidon_p8;
 // mod utils;
+use crate::backend::Tfhe;
 use crate::constants;
-use crate::poseidon_p8;
+use crate::hash::{FheHash, MixedInput};
+use crate::modint::{ModInt, Modulus};
+use crate::nonce;
+use crate::poseidon_p8::{self, PoseidonP8Rf2Rp1};
+use crate::signing_key::SigningKey;
 use crate::utils;
 
 
@@ -38,22 +41,21 @@ fn main_p8() -> Result<(), Box<dyn std::error::Error>> {
     // TODO: for now, we focus on 8-bit p, keys, and message, and 16-bit q
     ////////////////////////////////////////////////////////////////////////////
     utils::log("Generating plaintext Schnorr keys...");
-    let mut rng = OsRng;
-    let x_sch = rng.gen_range(1..constants::SCH_Q);
-    // let x_sch = 1u8;  // EXP: fix a secret key
+    let x_sch = SigningKey::<u8>::generate();
+    // let x_sch = SigningKey::new(1u8)?;  // EXP: fix a secret key
     let g_sch = BigUint::from(constants::SCH_G_8);
     let p_sch = BigUint::from(constants::SCH_P_8);
     let q_sch = constants::SCH_Q_4;
-    let y_sch = g_sch.modpow(&BigUint::from(x_sch), &p_sch);
+    let y_sch = g_sch.modpow(&BigUint::from(x_sch.expose_secret()), &p_sch);
     // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
     utils::log(&format!(
-        "x_sch: {}, g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
-        x_sch, g_sch, p_sch, q_sch, y_sch
+        "g_sch: {}, p_sch: {}, q_sch: {}, y_sch: {}",
+        g_sch, p_sch, q_sch, y_sch
     ));
 
     // Encrypt the signing key
     utils::log("Encrypting signing Schnorr key...");
-    let x_sch_enc = FheUint8::encrypt(x_sch, &client_key);
+    let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
 
     ////////////////////////////////////////////////////////////////////////////
     ///////////////////////////////// Signing //////////////////////////////////
@@ -62,21 +64,23 @@ fn main_p8() -> Result<(), Box<dyn std::error::Error>> {
     // let msg: u8 = 0xAAAAAAAA; // EXP: this is some random 8-bit message to sign; swappable
     let msg: u8 = 0xAA; // EXP: this is some random 8-bit message to sign; swappable
     let msg_enc: FheUint8 = FheUint8::encrypt(msg, &public_key);
-    let zero_enc: FheUint8 = FheUint8::encrypt(0u8, &public_key);
-    let zero_enc2: FheUint8 = FheUint8::encrypt(0u8, &public_key);
-
-    // Generate pseudo-random nonce, as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q)
-    utils::log("\t Pseudorandomness as FHE(k) = H(FHE(m), FHE(x_sch)) (mod q) ...");
-    let k_enc: FheUint8 =
-        poseidon_p8::poseidon_p8_rf2_rp1([&msg_enc, &x_sch_enc, &zero_enc, &zero_enc2]);
-    let k_enc: FheUint8 = &k_enc % ((q_sch - 1) as u8) + 1; // ensures k in [1, q-1]
-    let k_enc_up: FheUint16 = (&k_enc).clone().cast_into();
+    // The zero-padding inputs of the hash are public, so they stay in the clear
+
+    // Generate pseudo-random nonce, as FHE(k) = (FHE(h_1) * p + FHE(h_2)) (mod q - 1) + 1
+    // with FHE(h_i) = H(FHE(m), FHE(x_sch), i), without bias (see `nonce.rs`)
+    utils::log("\t Pseudorandomness as FHE(k) from H(FHE(m), FHE(x_sch), i) ...");
+    let (k_enc, k_enc_up): (FheUint8, FheUint16) = nonce::derive_nonce::<Tfhe>(
+        &MixedInput::Enc(msg_enc.clone()),
+        &x_sch_enc,
+        &MixedInput::Clear(0u8), // deterministic nonce
+        PoseidonP8Rf2Rp1::hash_mixed,
+    )?;
 
     // #DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
     let k_enc_dec: u8 = k_enc.decrypt(&client_key);
     utils::log(&format!(
         "DEBUG k_clear from direct hash: {}",
-        poseidon_p8::poseidon_p8_clear_rf2_rp1([msg, x_sch, 0u8, 0u8])
+        nonce::derive_nonce_clear::<PoseidonP8Rf2Rp1>(msg, x_sch.expose_secret(), 0)?
     ));
     utils::log(&format!("DEBUG k_enc_dec: {}", k_enc_dec));
 
@@ -85,13 +89,18 @@ fn main_p8() -> Result<(), Box<dyn std::error::Error>> {
     utils::log("\t Exponentiating: FHE(k) = FHE(r) = FHE(g)^FHE(k) (mod p) ...");
     let g_enc_up: FheUint16 = FheUint8::encrypt(constants::SCH_G, &public_key).cast_into();
     let p_sch_up: u16 = p_sch.to_u16().expect("p_sch is too large");
-    let r_enc_up: FheUint16 = utils::fhe2_modexp_16(&g_enc_up, &k_enc_up, p_sch_up);
+    let g_enc_up = ModInt::new(g_enc_up, Modulus::new(p_sch_up)?);
+    let r_enc_up: FheUint16 = utils::fhe2_modexp_16(&g_enc_up, &k_enc_up).into_inner();
     let r_enc: FheUint8 = r_enc_up.cast_into(); // already (mod p)
 
     // Compute hash of the message: FHE(h) = H(FHE(m), FHE(r)) (mod q)
     utils::log("\t Hashing: FHE(h) = H(FHE(m), FHE(r)) (mod q) ...");
-    let h_enc: FheUint8 =
-        poseidon_p8::poseidon_p8_rf2_rp1([&msg_enc, &r_enc, &zero_enc, &zero_enc2]);
+    let h_enc: FheUint8 = poseidon_p8::poseidon_p8_arith_mixed_rf2_rp1([
+        MixedInput::Enc(msg_enc.clone()),
+        MixedInput::Enc(r_enc.clone()),
+        MixedInput::Clear(0u8),
+        MixedInput::Clear(0u8),
+    ])?;
     let h_enc: FheUint8 = &h_enc % (q_sch as u8); // NOTE: ensures h in [0, q-1]
 
     // Compute signature: FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q)
@@ -109,7 +118,7 @@ fn main_p8() -> Result<(), Box<dyn std::error::Error>> {
     let h_dec: u8 = (&h_enc).clone().decrypt(&client_key);
 
     //  DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
-    let hx_dec: u16 = (x_sch as u16 * h_dec as u16) % (q_sch as u16);
+    let hx_dec: u16 = (x_sch.expose_secret() as u16 * h_dec as u16) % (q_sch as u16);
     let hx_enc_dec: u16 = hx_enc_up.clone().decrypt(&client_key);  // hx_enc_up not used anymore
     let r_enc_dec: u8 = (&r_enc).clone().decrypt(&client_key);
     let k_enc_dec: u16 = (&k_enc_up).clone().decrypt(&client_key);
@@ -128,7 +137,7 @@ fn main_p8() -> Result<(), Box<dyn std::error::Error>> {
     let r_v = (&r_v_left * &r_v_right) % &p_sch;
     let r_v = r_v.to_u8().expect("r_v is too large");
     // Compute clear hash of the message: h_v = H(m, r_v) (mod q)
-    let h_v = poseidon_p8::poseidon_p8_clear_rf2_rp1([msg, r_v, 0u8, 0u8]);
+    let h_v = poseidon_p8::poseidon_p8_clear_rf2_rp1([msg, r_v, 0u8, 0u8])?;
     let h_v = h_v % (q_sch as u8);
     // Verify if h_v == h_dec
     // DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG DEBUG
@@ -0,0 +1,293 @@
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{ClearHash, FheHash, MixedInput};
+
+/*
+ * Merkle trees over the crate's hashes, in the clear and under FHE, e.g. to
+ * sign a commitment to a batch of messages with a single FHE signature.
+ *
+ * Each node hashes up to `ARITY - 1` children (3 for Poseidon with t = 4),
+ * with the `HashType.MERKLETREE` domain separation of
+ * `scripts/poseidon/hash.py` in the first input:
+ *   node = H(2^k - 1, child_0, ..., child_{k - 1})
+ * for a node of k children. The last node of a level may have fewer children
+ * than the others; there are no padding leaves, so the tag binds the number
+ * of leaves into the root, and no proof exists for a position past the last
+ * leaf. There is at least one level of hashing, so a root is never a leaf.
+ *
+ * Under FHE, the tag is public: nodes are hashed with `FheHash::hash_mixed`,
+ * and the zero inputs after the children of a short node stay in the clear.
+ */
+
+// Number of children per node
+pub fn arity<H: ClearHash>() -> usize {
+    H::ARITY - 1
+}
+
+// MERKLETREE domain tag 2^k - 1 of a node of k children, as its first input
+fn domain_tag<H: ClearHash>(n_children: usize) -> Result<H::Clear>
+where
+    H::Clear: From<u8>,
+{
+    let tag = (1u32 << n_children) - 1;
+    let tag = u8::try_from(tag).map_err(|_| SchnorrFheError::WidthOverflow {
+        what: "Merkle domain tag",
+        bits: u8::BITS,
+    })?;
+    Ok(H::Clear::from(tag))
+}
+
+fn check_not_empty(n_leaves: usize) -> Result<()> {
+    match n_leaves {
+        0 => Err(SchnorrFheError::InvalidParams(
+            "a Merkle tree needs at least one leaf".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn hash_node_clear<H: ClearHash>(children: &[H::Clear]) -> Result<H::Clear>
+where
+    H::Clear: From<u8>,
+{
+    let mut inputs: Vec<H::Clear> = vec![domain_tag::<H>(children.len())?];
+    inputs.extend_from_slice(children);
+    H::hash_clear(&inputs)
+}
+
+fn hash_node_enc<H: FheHash>(children: &[H::Enc]) -> Result<H::Enc>
+where
+    H::Clear: From<u8>,
+{
+    let mut inputs = vec![MixedInput::Clear(domain_tag::<H>(children.len())?)];
+    inputs.extend(children.iter().cloned().map(MixedInput::Enc));
+    H::hash_mixed(&inputs)
+}
+
+// Merkle tree in the clear
+pub struct MerkleTree<H: ClearHash> {
+    // levels[0] are the leaves; the last level is the root
+    levels: Vec<Vec<H::Clear>>,
+    n_leaves: usize,
+}
+
+// Inclusion proof of the leaf at `index`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof<C> {
+    pub index: usize,
+    // From the leaves up: the other children of each node on the path, in order
+    pub siblings: Vec<Vec<C>>,
+}
+
+impl<H: ClearHash> MerkleTree<H>
+where
+    H::Clear: From<u8>,
+{
+    pub fn new(leaves: &[H::Clear]) -> Result<Self> {
+        check_not_empty(leaves.len())?;
+        let arity = arity::<H>();
+        let mut levels: Vec<Vec<H::Clear>> = vec![leaves.to_vec()];
+        while levels.len() == 1 || levels[levels.len() - 1].len() > 1 {
+            let next: Vec<H::Clear> = levels[levels.len() - 1]
+                .chunks(arity)
+                .map(hash_node_clear::<H>)
+                .collect::<Result<_>>()?;
+            levels.push(next);
+        }
+        Ok(MerkleTree {
+            levels,
+            n_leaves: leaves.len(),
+        })
+    }
+
+    pub fn root(&self) -> H::Clear {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn n_leaves(&self) -> usize {
+        self.n_leaves
+    }
+
+    pub fn prove(&self, index: usize) -> Result<MerkleProof<H::Clear>> {
+        if index >= self.n_leaves {
+            return Err(SchnorrFheError::InvalidParams(format!(
+                "leaf index out of range: {}",
+                index
+            )));
+        }
+        let arity = arity::<H>();
+        let mut siblings: Vec<Vec<H::Clear>> = Vec::new();
+        let mut i = index;
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            let first = i - i % arity;
+            let last = (first + arity).min(level.len());
+            let others: Vec<H::Clear> = (first..last)
+                .filter(|j| *j != i)
+                .map(|j| level[j])
+                .collect();
+            siblings.push(others);
+            i /= arity;
+        }
+        Ok(MerkleProof { index, siblings })
+    }
+}
+
+// Check that `leaf` is at `proof.index` in the tree with root `root`
+pub fn verify_proof<H: ClearHash>(
+    root: H::Clear,
+    leaf: H::Clear,
+    proof: &MerkleProof<H::Clear>,
+) -> Result<()>
+where
+    H::Clear: From<u8> + PartialEq,
+{
+    let arity = arity::<H>();
+    let mut node = leaf;
+    let mut i = proof.index;
+    for others in proof.siblings.iter() {
+        // Up to `arity` children, with the node among them
+        if others.len() >= arity || i % arity > others.len() {
+            return Err(SchnorrFheError::VerificationFailed);
+        }
+        let mut children: Vec<H::Clear> = others.clone();
+        children.insert(i % arity, node);
+        node = hash_node_clear::<H>(&children)?;
+        i /= arity;
+    }
+    // The index must fit in the depth of the proof
+    match i == 0 && node == root {
+        true => Ok(()),
+        false => Err(SchnorrFheError::VerificationFailed),
+    }
+}
+
+// Root of the Merkle tree over encrypted leaves, e.g. message digests
+pub fn root_enc<H: FheHash>(leaves: &[H::Enc]) -> Result<H::Enc>
+where
+    H::Clear: From<u8>,
+{
+    check_not_empty(leaves.len())?;
+    let arity = arity::<H>();
+    let mut level: Vec<H::Enc> = leaves.to_vec();
+    loop {
+        level = level
+            .chunks(arity)
+            .map(hash_node_enc::<H>)
+            .collect::<Result<_>>()?;
+        if level.len() == 1 {
+            return Ok(level.swap_remove(0));
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mimc::MimcP8;
+    use crate::poseidon_p8::{self, PoseidonP8, PoseidonP8Rf2Rp1};
+    use crate::utils;
+    use tfhe::prelude::*;
+    use tfhe::{set_server_key, FheUint8};
+
+    #[test]
+    fn test_merkle_root() {
+        assert_eq!(arity::<PoseidonP8>(), 3);
+
+        // One level: H(3, a, b)
+        let tree = MerkleTree::<PoseidonP8>::new(&[10u8, 20u8]).unwrap();
+        assert_eq!(
+            tree.root(),
+            poseidon_p8::poseidon_p8_clear([3u8, 10u8, 20u8, 0u8]).unwrap()
+        );
+
+        // Two levels: H(3, H(7, a, b, c), H(1, d))
+        let tree = MerkleTree::<PoseidonP8>::new(&[1u8, 2u8, 3u8, 4u8]).unwrap();
+        let nodes = [
+            poseidon_p8::poseidon_p8_clear([7u8, 1u8, 2u8, 3u8]).unwrap(),
+            poseidon_p8::poseidon_p8_clear([1u8, 4u8, 0u8, 0u8]).unwrap(),
+        ];
+        let expected = poseidon_p8::poseidon_p8_clear([3u8, nodes[0], nodes[1], 0u8]);
+        assert_eq!(tree.root(), expected.unwrap());
+
+        // Trailing zero leaves are leaves, not padding
+        let root = |leaves: &[u8]| MerkleTree::<PoseidonP8>::new(leaves).unwrap().root();
+        assert_ne!(root(&[5u8]), root(&[5u8, 0u8]));
+        assert_ne!(root(&[1u8, 2u8, 3u8]), root(&[1u8, 2u8, 3u8, 0u8]));
+
+        // No leaves
+        assert!(matches!(
+            MerkleTree::<PoseidonP8>::new(&[]),
+            Err(SchnorrFheError::InvalidParams(_))
+        ));
+    }
+
+    // NOTE: with the toy p = 199, the root is a single 8-bit element, so a
+    // wrong leaf still verifies with probability ~1/p; we only check that
+    // most do not
+    #[test]
+    fn test_merkle_proof() {
+        let leaves: Vec<u8> = (0..10u8).map(|x| x * 17 + 3).collect();
+        let tree = MerkleTree::<PoseidonP8>::new(&leaves).unwrap();
+        let root = tree.root();
+        let mut n_bad_verified = 0;
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = tree.prove(i).unwrap();
+            assert_eq!(proof.siblings.len(), 3);
+            assert_eq!(verify_proof::<PoseidonP8>(root, *leaf, &proof), Ok(()));
+            // Wrong leaf, or the proof of another index
+            let other = tree.prove((i + 1) % leaves.len()).unwrap();
+            n_bad_verified += verify_proof::<PoseidonP8>(root, leaf + 1, &proof).is_ok() as usize;
+            n_bad_verified += verify_proof::<PoseidonP8>(root, *leaf, &other).is_ok() as usize;
+        }
+        assert!(n_bad_verified < leaves.len() / 2);
+        // Out-of-range index
+        let mut proof = tree.prove(0).unwrap();
+        proof.index += 27;
+        assert_eq!(
+            verify_proof::<PoseidonP8>(root, leaves[0], &proof),
+            Err(SchnorrFheError::VerificationFailed)
+        );
+        assert!(tree.prove(leaves.len()).is_err());
+
+        // No proof of a zero leaf past the last one: the node has 3 children
+        // in the proof, but 2 in the tree
+        let tree = MerkleTree::<PoseidonP8>::new(&[10u8, 20u8]).unwrap();
+        let proof = MerkleProof {
+            index: 2,
+            siblings: vec![vec![10u8, 20u8]],
+        };
+        assert_eq!(
+            verify_proof::<PoseidonP8>(tree.root(), 0u8, &proof),
+            Err(SchnorrFheError::VerificationFailed)
+        );
+    }
+
+    // The encrypted root must match the clear root, with or without a short
+    // last node
+    #[test]
+    fn test_merkle_root_enc() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        for n_leaves in [1, 3, 5] {
+            let leaves: Vec<u8> = (0..n_leaves as u8).map(|x| x * 31 + 1).collect();
+            let leaves_enc: Vec<FheUint8> = leaves
+                .iter()
+                .map(|x| FheUint8::encrypt(*x, &client_key))
+                .collect();
+
+            let root: u8 = root_enc::<PoseidonP8Rf2Rp1>(&leaves_enc)
+                .unwrap()
+                .decrypt(&client_key);
+            let tree = MerkleTree::<PoseidonP8Rf2Rp1>::new(&leaves).unwrap();
+            assert_eq!(root, tree.root());
+            let root: u8 = root_enc::<MimcP8>(&leaves_enc)
+                .unwrap()
+                .decrypt(&client_key);
+            assert_eq!(root, MerkleTree::<MimcP8>::new(&leaves).unwrap().root());
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use tfhe::prelude::*;
+use tfhe::FheUint8;
+
+use crate::backend::Backend;
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{ClearHash, MixedInput};
+
+/*
+ * Encoding of arbitrary byte-string messages into field elements.
+ *
+ * Encoding: the message is prefixed with its length in bytes (u32, little
+ * endian), and the resulting bytes are bit-packed into chunks of
+ * `ELEM_BITS = floor(log2(p))` bits (7 bits for p = 199), zero-padding the
+ * last chunk. Every element is then < 2^ELEM_BITS <= p, and the encoding is
+ * injective: the length prefix tells where the message ends, so two messages
+ * that differ only by trailing zero bytes encode differently.
+ *
+ * Hashing: the elements are absorbed by chaining the `ARITY`-input hash as a
+ * compression function, with the running digest in the first input and
+ * `ARITY - 1` message elements in the others:
+ *   d_0 = 0, d_{i+1} = H(d_i, m_{i * (t-1)}, ..., m_{(i+1) * (t-1) - 1})
+ * The FHE and clear paths absorb identically, so the verifier can re-encode and
+ * re-hash the clear message.
+ *
+ * An encrypted message shorter than the encoding of the empty message (the
+ * length prefix alone) is rejected as malformed.
+ */
+
+// Bits per field element, i.e. floor(log2(POSEIDON_P_8))
+pub const ELEM_BITS: u32 = u8::BITS - 1 - constants::POSEIDON_P_8.leading_zeros();
+
+// Number of elements of the shortest encoding, i.e. of the length prefix
+pub const MIN_ENCODED_LEN: usize = u32::BITS.div_ceil(ELEM_BITS) as usize;
+
+// Length-prefixed, bit-packed encoding of `msg` into field elements < p
+pub fn encode_message(msg: &[u8]) -> Result<Vec<u8>> {
+    let len = u32::try_from(msg.len()).map_err(|_| SchnorrFheError::WidthOverflow {
+        what: "message length",
+        bits: u32::BITS,
+    })?;
+    let bytes: Vec<u8> = len.to_le_bytes().iter().chain(msg.iter()).copied().collect();
+
+    let mask: u16 = (1 << ELEM_BITS) - 1;
+    let mut elems: Vec<u8> = Vec::new();
+    let mut acc: u16 = 0;
+    let mut acc_bits: u32 = 0;
+    for byte in bytes {
+        acc |= (byte as u16) << acc_bits;
+        acc_bits += 8;
+        while acc_bits >= ELEM_BITS {
+            elems.push((acc & mask) as u8);
+            acc >>= ELEM_BITS;
+            acc_bits -= ELEM_BITS;
+        }
+    }
+    if acc_bits > 0 {
+        elems.push(acc as u8);
+    }
+    Ok(elems)
+}
+
+// Inverse of `encode_message`; None if `elems` is not a valid encoding
+pub fn decode_message(elems: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut acc: u32 = 0;
+    let mut acc_bits: u32 = 0;
+    for elem in elems {
+        if (*elem as u32) >> ELEM_BITS != 0 {
+            return None;
+        }
+        acc |= (*elem as u32) << acc_bits;
+        acc_bits += ELEM_BITS;
+        while acc_bits >= 8 {
+            bytes.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    }
+    if bytes.len() < 4 {
+        return None;
+    }
+    let len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let msg = &bytes[4..];
+    if len > msg.len() || msg[len..].iter().any(|b| *b != 0) || acc != 0 {
+        return None;
+    }
+    // The encoding must be the canonical (shortest) one
+    if encode_message(&msg[..len]).ok()?.len() != elems.len() {
+        return None;
+    }
+    Some(msg[..len].to_vec())
+}
+
+// Encode and encrypt a message element-wise (e.g. with the public key)
+pub fn encrypt_message<K>(msg: &[u8], key: &K) -> Result<Vec<FheUint8>>
+where
+    FheUint8: FheEncrypt<u8, K>,
+{
+    Ok(encode_message(msg)?
+        .into_iter()
+        .map(|x| FheUint8::encrypt(x, key))
+        .collect())
+}
+
+// Hash of encoded message elements under FHE, generic over the backend (see
+// `backend.rs`) and the `arity`-input hash of mixed inputs, e.g.
+// `FheHash::hash_mixed`; the simulator and the cost model absorb through it too
+pub fn hash_message_enc<B: Backend>(
+    elems: &[B::U8],
+    arity: usize,
+    hash: impl Fn(&[MixedInput<u8, B::U8>]) -> Result<B::U8>,
+) -> Result<B::U8> {
+    if elems.len() < MIN_ENCODED_LEN {
+        return Err(SchnorrFheError::MalformedCiphertext(format!(
+            "encrypted message of {} elements, shorter than its length prefix",
+            elems.len()
+        )));
+    }
+    let _phase = crate::phase!("message_hash", n_elems = elems.len());
+    let mut digest = B::U8::encrypt_trivial(0u8);
+    for chunk in elems.chunks(arity - 1) {
+        let mut inputs: Vec<MixedInput<u8, B::U8>> = vec![MixedInput::Enc(digest)];
+        inputs.extend(chunk.iter().map(|x| MixedInput::Enc(x.clone())));
+        digest = hash(&inputs)?;
+    }
+    Ok(digest)
+}
+
+// Same as `hash_message_enc` but in the clear
+pub fn hash_message_clear<H: ClearHash<Clear = u8>>(elems: &[u8]) -> Result<u8> {
+    let mut digest: u8 = 0;
+    for chunk in elems.chunks(H::ARITY - 1) {
+        let mut inputs: Vec<u8> = vec![digest];
+        inputs.extend_from_slice(chunk);
+        digest = H::hash_clear(&inputs)?;
+    }
+    Ok(digest)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Tfhe;
+    use crate::hash::FheHash;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+    use crate::utils;
+    use tfhe::set_server_key;
+
+    #[test]
+    fn test_encode_message() {
+        assert_eq!(ELEM_BITS, 7);
+        let msgs: Vec<&[u8]> = vec![b"", b"\x00", b"\x00\x00", b"\xff", b"hello world", &[0xAA; 100]];
+        let mut encodings: Vec<Vec<u8>> = Vec::new();
+        for msg in msgs {
+            let elems = encode_message(msg).unwrap();
+            assert!(elems.iter().all(|x| *x < constants::POSEIDON_P_8));
+            assert_eq!(decode_message(&elems), Some(msg.to_vec()));
+            assert!(!encodings.contains(&elems), "encoding is not injective");
+            encodings.push(elems);
+        }
+        // Non-canonical encodings are rejected
+        let mut elems = encode_message(b"abc").unwrap();
+        elems.push(0);
+        assert_eq!(decode_message(&elems), None);
+        assert_eq!(encode_message(b"").unwrap().len(), MIN_ENCODED_LEN);
+    }
+
+    #[test]
+    fn test_hash_message() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let msg = b"Schnorr-FHE";
+        let elems_enc = encrypt_message(msg, &client_key).unwrap();
+        let (arity, hash) = (PoseidonP8Rf2Rp1::ARITY, PoseidonP8Rf2Rp1::hash_mixed);
+        let digest_enc = hash_message_enc::<Tfhe>(&elems_enc, arity, hash).unwrap();
+        let digest_enc: u8 = digest_enc.decrypt(&client_key);
+        let digest = hash_message_clear::<PoseidonP8Rf2Rp1>(&encode_message(msg).unwrap()).unwrap();
+        assert_eq!(digest_enc, digest);
+        let other = encode_message(b"Schnorr-FHF").unwrap();
+        assert_ne!(digest, hash_message_clear::<PoseidonP8Rf2Rp1>(&other).unwrap());
+
+        // A truncated encrypted message is rejected
+        let truncated = hash_message_enc::<Tfhe>(&elems_enc[..MIN_ENCODED_LEN - 1], arity, hash);
+        assert!(matches!(truncated, Err(SchnorrFheError::MalformedCiphertext(_))));
+    }
+}
@@ -0,0 +1,139 @@
+use tfhe::prelude::*;
+use tfhe::{FheUint16, FheUint8};
+
+use crate::constants;
+use crate::error::Result;
+use crate::hash::{self, ClearHash, FheHash};
+use crate::lut;
+use crate::utils;
+
+/*
+ * MiMC over the 8-bit dev field (p = `POSEIDON_P_8`).
+ *
+ * The MiMC cipher E_k(x) iterates x -> (x + k + c_i)^alpha and returns x + k.
+ * Note that alpha = 3 (the textbook choice) is not a permutation here since
+ * gcd(3, p - 1) = 3, so we use alpha = `POSEIDON_ALPHA` = 5. Inputs are
+ * absorbed with the Miyaguchi-Preneel construction:
+ *   h_0 = 0, h_{i+1} = E_{h_i}(m_i) + h_i + m_i (mod p)
+ *
+ * Under FHE, both the key h_i and the message m_i are encrypted, so each round
+ * is one FHE add (in FheUint16), one `%` back into the field, and one lookup
+ * for v -> (v + c_i)^alpha (mod p).
+ * Constants and test vectors: `scripts/arith_hash_gen.py`.
+ */
+
+const T: usize = constants::POSEIDON_T;
+
+// MiMC cipher E_k(x) under FHE, with both x and k encrypted and (mod p)
+fn mimc_p8_encrypt(x: &FheUint8, k: &FheUint8) -> FheUint8 {
+    let p8 = constants::POSEIDON_P_8;
+    let k_up: FheUint16 = k.clone().cast_into();
+    let mut x: FheUint8 = x.clone();
+    for c in MIMC_RC.iter() {
+        let x_up: FheUint16 = x.cast_into();
+        let sum: FheUint8 = ((&x_up + &k_up) % p8 as u16).cast_into();
+        x = lut::fhe_lut_add_modexp_8(&sum, *c, constants::POSEIDON_ALPHA, p8);
+    }
+    // NOTE: the final `+ k` is folded into the Miyaguchi-Preneel sum
+    x
+}
+
+pub fn mimc_p8(inputs: [&FheUint8; T]) -> FheUint8 {
+    let p8 = constants::POSEIDON_P_8;
+    let mut h: FheUint8 = lut::fhe_lut_mod_8(inputs[0], p8);
+    // h_1 = E_0(m_0) + 0 + m_0; the first key is a clear zero, so the rounds on
+    // m_0 are a single lookup each
+    let m0 = h.clone();
+    for c in MIMC_RC.iter() {
+        h = lut::fhe_lut_add_modexp_8(&h, *c, constants::POSEIDON_ALPHA, p8);
+    }
+    let sum: FheUint16 = FheUint16::cast_from(h) + FheUint16::cast_from(m0);
+    h = (sum % p8 as u16).cast_into();
+
+    for m in inputs.iter().skip(1) {
+        let m = lut::fhe_lut_mod_8(*m, p8);
+        let e = mimc_p8_encrypt(&m, &h);
+        // h_{i+1} = (E'(m) + k) + h + m with k = h
+        let h_up: FheUint16 = h.cast_into();
+        let sum: FheUint16 = FheUint16::cast_from(e) + &h_up + &h_up + FheUint16::cast_from(m);
+        h = (sum % p8 as u16).cast_into();
+    }
+    h
+}
+
+// Same as `mimc_p8` but in the clear
+pub fn mimc_p8_clear(inputs: [u8; T]) -> u8 {
+    let p = constants::POSEIDON_P_8 as u64;
+    let mut h: u64 = 0;
+    for m in inputs.iter() {
+        let m = *m as u64 % p;
+        let mut x = m;
+        for c in MIMC_RC.iter() {
+            x = utils::clear_modexp_64(x + h + *c as u64, constants::POSEIDON_ALPHA as u64, p);
+        }
+        let e = (x + h) % p;
+        h = (e + h + m) % p;
+    }
+    h as u8
+}
+
+pub struct MimcP8;
+
+impl ClearHash for MimcP8 {
+    type Clear = u8;
+    const NAME: &'static str = "mimc_p8";
+    const ARITY: usize = T;
+
+    fn hash_clear(inputs: &[u8]) -> Result<u8> {
+        Ok(mimc_p8_clear(hash::pad_clear(inputs)?))
+    }
+}
+
+impl FheHash for MimcP8 {
+    type Enc = FheUint8;
+
+    fn hash_enc(inputs: &[FheUint8]) -> Result<FheUint8> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        Ok(mimc_p8(std::array::from_fn(|i| &padded[i])))
+    }
+}
+
+//////////////////////////////// MiMC constants ////////////////////////////////
+
+// NOTE: toy round number for the 8-bit dev field; no security claim
+pub const MIMC_ROUNDS: usize = 8;
+
+// Round constants (c_0 = 0 by convention); all values are mod POSEIDON_P_8
+const MIMC_RC: [u8; MIMC_ROUNDS] = [0, 47, 121, 87, 66, 187, 15, 100];
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::set_server_key;
+
+    // Expected outputs from `python scripts/arith_hash_gen.py --p 199 --t 4`
+    #[test]
+    fn test_mimc_p8_clear() {
+        assert_eq!(mimc_p8_clear([1, 0, 2, 8]), 161);
+        assert_eq!(mimc_p8_clear([0xAA, 5, 0, 0]), 186);
+    }
+
+    #[test]
+    fn test_mimc_p8() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let inputs: [&FheUint8; T] = [
+            &FheUint8::encrypt(0xAAu8, &client_key),
+            &FheUint8::encrypt(5u8, &client_key),
+            &FheUint8::encrypt(0u8, &client_key),
+            &FheUint8::encrypt(0u8, &client_key),
+        ];
+        let output: u8 = mimc_p8(inputs).decrypt(&client_key);
+        assert_eq!(output, 186);
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt::Debug;
+use std::ops::Rem;
+
+use tfhe::prelude::*;
+
+use crate::backend::FheUint;
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+
+/*
+ * Modular arithmetic with a width check.
+ *
+ * The (mod p) circuits compute a * b (mod p) as (a * b) % p in a W-bit
+ * container, which is only correct if the product does not wrap around 2^W.
+ * `Modulus<C>` is a modulus p for the W-bit containers with clear scalars C,
+ * and can only be built if (p - 1)^2 + p < 2^W: the product of two reduced
+ * values fits, and so does a reduced value plus p (added before a
+ * subtraction, see `ModInt::sub`). A container too small for a parameter set
+ * is then a `SchnorrFheError::WidthOverflow` when the modulus is built, not
+ * wrong signatures.
+ *
+ * `ModInt<E, C>` is a value of the backend type E (see `backend.rs`) reduced
+ * (mod p). Every operation reduces its result, so both operands of a multiply
+ * are always reduced. `ModInt::from_reduced` wraps a value the caller knows
+ * is reduced (e.g. the output of a (mod p) lookup) without the cost of a
+ * reduction.
+ *
+ * The parameter sets of `constants.rs` are checked at compile time against
+ * the containers the circuits use for them (see below).
+ */
+
+// Clear scalars of the W-bit containers
+pub trait ClearUint: Copy + Debug + PartialEq + Into<u64> + Rem<Output = Self> {
+    const BITS: u32;
+}
+
+impl ClearUint for u8 {
+    const BITS: u32 = u8::BITS;
+}
+impl ClearUint for u16 {
+    const BITS: u32 = u16::BITS;
+}
+impl ClearUint for u32 {
+    const BITS: u32 = u32::BITS;
+}
+impl ClearUint for u64 {
+    const BITS: u32 = u64::BITS;
+}
+
+// Whether (p - 1)^2 + p < 2^bits, in u128 arithmetic (bits <= 64)
+pub const fn fits(p: u64, bits: u32) -> bool {
+    let p = p as u128;
+    p >= 2 && (p - 1) * (p - 1) + p < 1u128 << bits
+}
+
+// Schnorr (mod q) arithmetic on 16 bits (see `schnorr::s_from_nonce`)
+const _: () = assert!(fits(constants::SCH_Q as u64, u16::BITS));
+// Poseidon (mod p) arithmetic on 16 bits for the 8-bit field
+const _: () = assert!(fits(constants::POSEIDON_P_8 as u64, u16::BITS));
+// Poseidon (mod p) arithmetic on 64 bits for the 32-bit field
+const _: () = assert!(fits(constants::POSEIDON_P_32 as u64, u64::BITS));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Modulus<C> {
+    p: C,
+}
+
+impl<C: ClearUint> Modulus<C> {
+    pub fn new(p: C) -> Result<Self> {
+        if p.into() < 2 {
+            return Err(SchnorrFheError::InvalidParams(format!(
+                "modulus {:?} is less than 2",
+                p
+            )));
+        }
+        if !fits(p.into(), C::BITS) {
+            return Err(SchnorrFheError::WidthOverflow {
+                what: "(p - 1)^2 + p",
+                bits: C::BITS,
+            });
+        }
+        Ok(Modulus { p })
+    }
+
+    pub fn value(&self) -> C {
+        self.p
+    }
+}
+
+#[derive(Clone)]
+pub struct ModInt<E, C> {
+    value: E,
+    modulus: Modulus<C>,
+}
+
+impl<C: ClearUint, E: FheUint<C>> ModInt<E, C> {
+    // x (mod p), for any x in the container
+    pub fn new(x: E, modulus: Modulus<C>) -> Self {
+        ModInt {
+            value: x % modulus.p,
+            modulus,
+        }
+    }
+
+    // x as is; the caller guarantees x < p
+    pub fn from_reduced(x: E, modulus: Modulus<C>) -> Self {
+        ModInt { value: x, modulus }
+    }
+
+    // Trivial (public) encryption of x (mod p)
+    pub fn trivial(x: C, modulus: Modulus<C>) -> Self {
+        ModInt {
+            value: E::encrypt_trivial(x % modulus.p),
+            modulus,
+        }
+    }
+
+    pub fn modulus(&self) -> Modulus<C> {
+        self.modulus
+    }
+
+    pub fn value(&self) -> &E {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> E {
+        self.value
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        self.check_modulus(other);
+        ModInt::new(self.value.clone() + &other.value, self.modulus)
+    }
+
+    pub fn add_clear(&self, c: C) -> Self {
+        ModInt::new(self.value.clone() + c % self.modulus.p, self.modulus)
+    }
+
+    // a - b (mod p) as (a + p - b) % p, which never goes below zero
+    pub fn sub(&self, other: &Self) -> Self {
+        self.check_modulus(other);
+        let value = self.value.clone() + self.modulus.p - &other.value;
+        ModInt::new(value, self.modulus)
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        self.check_modulus(other);
+        ModInt::new(self.value.clone() * &other.value, self.modulus)
+    }
+
+    pub fn mul_clear(&self, c: C) -> Self {
+        ModInt::new(self.value.clone() * (c % self.modulus.p), self.modulus)
+    }
+
+    pub fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    // if_true if cond, else if_false
+    pub fn select(cond: &E::Bool, if_true: &Self, if_false: &Self) -> Self {
+        if_true.check_modulus(if_false);
+        ModInt {
+            value: cond.if_then_else(&if_true.value, &if_false.value),
+            modulus: if_true.modulus,
+        }
+    }
+
+    fn check_modulus(&self, other: &Self) {
+        assert_eq!(self.modulus, other.modulus, "operands of different moduli");
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimUint16;
+
+    #[test]
+    fn test_modulus_width() {
+        // (p - 1)^2 + p < 2^16 up to p = 256
+        assert!(Modulus::new(256u16).is_ok());
+        assert_eq!(
+            Modulus::new(257u16),
+            Err(SchnorrFheError::WidthOverflow {
+                what: "(p - 1)^2 + p",
+                bits: 16
+            })
+        );
+        assert!(Modulus::new(65521u16).is_err());
+        assert!(Modulus::new(constants::POSEIDON_P_32 as u64).is_ok());
+        assert!(Modulus::new(constants::POSEIDON_P_32).is_err());
+        assert!(matches!(
+            Modulus::new(1u8),
+            Err(SchnorrFheError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_modint_ops() {
+        let q = Modulus::new(256u16).unwrap();
+        let a: ModInt<SimUint16, u16> = ModInt::new(SimUint16(65535), q);
+        let b: ModInt<SimUint16, u16> = ModInt::trivial(300, q);
+        assert_eq!((a.value().0, b.value().0), (255, 44));
+        // The largest product and the largest sum before a subtraction fit
+        assert_eq!(a.mul(&a).into_inner().0, 255 * 255 % 256);
+        assert_eq!(b.sub(&a).into_inner().0, 44 + 256 - 255);
+        assert_eq!(a.add(&b).into_inner().0, (255 + 44) % 256);
+        assert_eq!(a.mul_clear(2).into_inner().0, 255 * 2 % 256);
+    }
+}
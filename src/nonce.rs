@@ -0,0 +1,289 @@
+use rand::rngs::OsRng;
+use rand::Rng;
+use tfhe::prelude::*;
+
+use crate::backend::Backend;
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{ClearHash, MixedInput};
+
+/*
+ * Deterministic nonce derivation, k in [1, q - 1], without bias.
+ *
+ * Reducing a single hash output, k = H(d, x_sch) (mod q - 1) + 1, is biased
+ * unless q - 1 divides the hash field size p: with p = 199 and q = 11, the
+ * nonces 1..9 each have probability 20/199 and the nonce 10 has 19/199. Over
+ * many signatures a biased nonce leaks the key (lattice attacks on the hidden
+ * number problem).
+ *
+ * Instead, NONCE_ELEMS hash outputs are squeezed with a counter as extra input
+ *   h_i = H(d, x_sch, i, a), i = 1..NONCE_ELEMS
+ * and combined into one wide value, uniform in [0, p^NONCE_ELEMS):
+ *   w = h_1 * p^(NONCE_ELEMS - 1) + ... + h_NONCE_ELEMS
+ *   k = w (mod q - 1) + 1
+ * The statistical distance of k to uniform is then below (q - 1) / p^2, e.g.
+ * about 2.3e-5 instead of 4.5e-3 for the 8-bit set (see `bias`). The nonzero
+ * counter also separates the nonce hashes from the challenge hash H(d, r).
+ *
+ * The last input a is zero for deterministic nonces, i.e. the hash input is
+ * just zero-padded. Hedged nonces (as in RFC 6979 section 3.6 or the aux_rand
+ * of BIP-340) take fresh randomness a in [0, p) from the signer instead,
+ * encrypted with each request (see `aux_rand` and `schnorr::NonceMode`): a
+ * fault injected in one signature no longer yields two signatures with the
+ * same nonce, and k stays secret even if the randomness is bad, since it also
+ * depends on x_sch.
+ *
+ * Under FHE, w is computed in the double-width container (16 bits for the
+ * 8-bit field, 64 bits for the 32-bit field), which must hold p^NONCE_ELEMS;
+ * this is checked at compile time for the parameter sets of `constants.rs`.
+ */
+
+// Hash outputs per nonce
+pub const NONCE_ELEMS: usize = 2;
+
+// Whether p^n <= 2^bits, i.e. any wide value of n field elements fits
+pub const fn wide_fits(p: u64, n: u32, bits: u32) -> bool {
+    match (p as u128).checked_pow(n) {
+        Some(w) => w <= 1u128 << bits,
+        None => false,
+    }
+}
+
+const _: () = assert!(wide_fits(
+    constants::POSEIDON_P_8 as u64,
+    NONCE_ELEMS as u32,
+    u16::BITS
+));
+const _: () = assert!(wide_fits(
+    constants::POSEIDON_P_32 as u64,
+    NONCE_ELEMS as u32,
+    u64::BITS
+));
+
+fn check_params(p: u64, q: u64, n: usize, bits: u32) -> Result<()> {
+    if n == 0 || q < 2 {
+        return Err(SchnorrFheError::InvalidParams(format!(
+            "nonce from {} hash outputs (mod {} - 1)",
+            n, q
+        )));
+    }
+    if !wide_fits(p, n as u32, bits) {
+        return Err(SchnorrFheError::WidthOverflow {
+            what: "p^NONCE_ELEMS",
+            bits,
+        });
+    }
+    Ok(())
+}
+
+// Fresh auxiliary randomness a in [0, p) for a hedged nonce, to be encrypted
+// by the signer
+pub fn aux_rand() -> u8 {
+    OsRng.gen_range(0..constants::POSEIDON_P_8)
+}
+
+// The hash outputs FHE(h_i) = H(d, FHE(x_sch), i, a), i = 1..NONCE_ELEMS;
+// a = `MixedInput::Clear(0)` for deterministic nonces
+pub fn hash_nonce<C: Clone + From<u8>, E: Clone>(
+    d: &MixedInput<C, E>,
+    x_sch_enc: &E,
+    aux: &MixedInput<C, E>,
+    hash: impl Fn(&[MixedInput<C, E>]) -> Result<E>,
+) -> Result<Vec<E>> {
+    (1..=NONCE_ELEMS as u8)
+        .map(|i| {
+            hash(&[
+                d.clone(),
+                MixedInput::Enc(x_sch_enc.clone()),
+                MixedInput::Clear(C::from(i)),
+                aux.clone(),
+            ])
+        })
+        .collect()
+}
+
+// Same as `hash_nonce` in the clear
+pub fn hash_nonce_clear<C: Copy + From<u8>>(
+    d: C,
+    x_sch: C,
+    aux: C,
+    hash: impl Fn(&[C]) -> Result<C>,
+) -> Result<Vec<C>> {
+    (1..=NONCE_ELEMS as u8)
+        .map(|i| hash(&[d, x_sch, C::from(i), aux]))
+        .collect()
+}
+
+// FHE(k) = FHE(w) (mod q - 1) + 1 from the 8-bit hash outputs FHE(h_i) of
+// field p, with w in 16 bits; also returns k cast up, for the (mod q)
+// arithmetic
+pub fn reduce_wide_16<B: Backend>(h: &[B::U8], p: u16, q: u16) -> Result<(B::U8, B::U16)> {
+    check_params(p as u64, q as u64, h.len(), u16::BITS)?;
+    let mut w: B::U16 = h[0].clone().cast_into();
+    for h_i in &h[1..] {
+        let h_i: B::U16 = h_i.clone().cast_into();
+        w = w * p + &h_i;
+    }
+    let k_enc_up: B::U16 = w % (q - 1) + 1; // ensures k in [1, q-1]
+    Ok((k_enc_up.clone().cast_into(), k_enc_up))
+}
+
+// Same as `reduce_wide_16` for 32-bit hash outputs, with w in 64 bits
+pub fn reduce_wide_64<B: Backend>(h: &[B::U32], p: u64, q: u64) -> Result<(B::U32, B::U64)> {
+    check_params(p, q, h.len(), u64::BITS)?;
+    let mut w: B::U64 = h[0].clone().cast_into();
+    for h_i in &h[1..] {
+        let h_i: B::U64 = h_i.clone().cast_into();
+        w = w * p + &h_i;
+    }
+    let k_enc_up: B::U64 = w % (q - 1) + 1; // ensures k in [1, q-1]
+    Ok((k_enc_up.clone().cast_into(), k_enc_up))
+}
+
+// Same as `reduce_wide_16` and `reduce_wide_64` in the clear
+pub fn reduce_wide_clear(h: &[u64], p: u64, q: u64) -> Result<u64> {
+    check_params(p, q, h.len(), u64::BITS)?;
+    let w = h.iter().fold(0u128, |w, &h_i| w * p as u128 + h_i as u128);
+    Ok((w % (q as u128 - 1)) as u64 + 1)
+}
+
+// FHE(k) of the 8-bit signer (`schnorr::sign_circuit`), from the digest d
+pub fn derive_nonce<B: Backend>(
+    d: &MixedInput<u8, B::U8>,
+    x_sch_enc: &B::U8,
+    aux: &MixedInput<u8, B::U8>,
+    hash: impl Fn(&[MixedInput<u8, B::U8>]) -> Result<B::U8>,
+) -> Result<(B::U8, B::U16)> {
+    let h = hash_nonce(d, x_sch_enc, aux, hash)?;
+    reduce_wide_16::<B>(&h, constants::POSEIDON_P_8 as u16, constants::SCH_Q as u16)
+}
+
+// Same as `derive_nonce` in the clear
+pub fn derive_nonce_clear<H: ClearHash<Clear = u8>>(d: u8, x_sch: u8, aux: u8) -> Result<u8> {
+    let h = hash_nonce_clear(d, x_sch, aux, H::hash_clear)?;
+    let h: Vec<u64> = h.into_iter().map(u64::from).collect();
+    let k = reduce_wide_clear(&h, constants::POSEIDON_P_8 as u64, constants::SCH_Q as u64)?;
+    Ok(k as u8)
+}
+
+// Statistical distance to uniform on [1, q - 1] of w (mod q - 1) + 1, for w
+// uniform in [0, p^n)
+pub fn bias(p: u64, q: u64, n: u32) -> f64 {
+    let size = (p as u128).pow(n);
+    let m = q as u128 - 1;
+    // `rem` residues are hit `count + 1` times, the others `count` times
+    let (count, rem) = (size / m, size % m);
+    let size = size as f64;
+    let uniform = 1.0 / m as f64;
+    let over = rem as f64 * ((count + 1) as f64 / size - uniform);
+    let under = (m - rem) as f64 * (uniform - count as f64 / size);
+    (over + under) / 2.0
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash;
+    use crate::poseidon_p8::{self, PoseidonP8Rf2Rp1};
+    use crate::sim::{Sim, SimUint32, SimUint8};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_bias() {
+        let (p, q) = (constants::POSEIDON_P_8 as u64, constants::SCH_Q as u64);
+        // One element: 9 nonces of probability 20/199, one of 19/199
+        assert!((bias(p, q, 1) - 9.0 * (20.0 / 199.0 - 0.1)).abs() < 1e-12);
+        assert!(bias(p, q, NONCE_ELEMS as u32) < (q - 1) as f64 / (p * p) as f64);
+        assert!(bias(p, q, NONCE_ELEMS as u32) < bias(p, q, 1) / 10.0);
+        // No bias when q - 1 divides p^n
+        assert_eq!(bias(10, 11, 2), 0.0);
+    }
+
+    #[test]
+    fn test_reduce_wide() {
+        let (p, q) = (constants::POSEIDON_P_8, constants::SCH_Q);
+        for h in [[0u8, 0], [198, 198], [17, 5]] {
+            let expected = ((h[0] as u64 * p as u64 + h[1] as u64) % (q as u64 - 1)) as u8 + 1;
+            let h_clear = [h[0] as u64, h[1] as u64];
+            assert_eq!(
+                reduce_wide_clear(&h_clear, p as u64, q as u64),
+                Ok(expected as u64)
+            );
+            let (k, k_up) =
+                reduce_wide_16::<Sim>(&[SimUint8(h[0]), SimUint8(h[1])], p as u16, q as u16)
+                    .unwrap();
+            assert_eq!((k.0, k_up.0), (expected, expected as u16));
+        }
+
+        // The largest wide value of the 32-bit field fits in 64 bits
+        let p = constants::POSEIDON_P_32 as u64;
+        let h_max = constants::POSEIDON_P_32 - 1;
+        let q = constants::SCH_Q_16 as u64;
+        let (k, _) = reduce_wide_64::<Sim>(&[SimUint32(h_max), SimUint32(h_max)], p, q).unwrap();
+        let expected = reduce_wide_clear(&[h_max as u64, h_max as u64], p, q).unwrap();
+        assert_eq!(k.0 as u64, expected);
+
+        assert_eq!(
+            reduce_wide_16::<Sim>(&[SimUint8(0); 3], 199, 11).err(),
+            Some(SchnorrFheError::WidthOverflow {
+                what: "p^NONCE_ELEMS",
+                bits: 16
+            })
+        );
+        assert!(reduce_wide_clear(&[], p, q).is_err());
+    }
+
+    // Chi-square test of the clear nonces over all digests and keys
+    #[test]
+    fn test_nonce_uniform() {
+        let m = constants::SCH_Q as usize - 1;
+        let mut counts = vec![0u64; m];
+        for d in 0..constants::POSEIDON_P_8 {
+            for x_sch in 1..constants::SCH_Q {
+                let k = derive_nonce_clear::<PoseidonP8Rf2Rp1>(d, x_sch, 0).unwrap();
+                assert!((1..constants::SCH_Q).contains(&k));
+                counts[k as usize - 1] += 1;
+            }
+        }
+        let n: u64 = counts.iter().sum();
+        let expected = n as f64 / m as f64;
+        let chi2: f64 = counts
+            .iter()
+            .map(|&c| (c as f64 - expected).powi(2) / expected)
+            .sum();
+        // 99.9% quantile of the chi-square distribution with 9 degrees of freedom
+        assert!(chi2 < 27.88, "chi2 = {} for counts {:?}", chi2, counts);
+    }
+
+    #[test]
+    fn test_derive_nonce_sim() {
+        let hash = |inputs: &[MixedInput<u8, SimUint8>]| {
+            poseidon_p8::poseidon_p8_lut_mixed_impl::<Sim>(hash::pad_mixed(inputs)?, 2, 1)
+        };
+        for (d, x_sch, aux) in [(0u8, 1u8, 0u8), (42, 7, 0), (198, 10, 0), (42, 7, 123)] {
+            let aux_enc = MixedInput::Enc(SimUint8(aux));
+            let (k, k_up) =
+                derive_nonce::<Sim>(&MixedInput::Clear(d), &SimUint8(x_sch), &aux_enc, hash)
+                    .unwrap();
+            let expected = derive_nonce_clear::<PoseidonP8Rf2Rp1>(d, x_sch, aux).unwrap();
+            assert_eq!((k.0, k_up.0), (expected, expected as u16));
+        }
+
+        // Deterministic nonces are those of a zero-padded hash input
+        let h = hash_nonce_clear(42, 7, 0, PoseidonP8Rf2Rp1::hash_clear).unwrap();
+        for (i, h_i) in h.into_iter().enumerate() {
+            let padded = PoseidonP8Rf2Rp1::hash_clear(&[42, 7, i as u8 + 1]).unwrap();
+            assert_eq!(h_i, padded);
+        }
+
+        // Hedged nonces vary with a
+        let nonces: HashSet<u8> = (0..constants::POSEIDON_P_8)
+            .map(|aux| derive_nonce_clear::<PoseidonP8Rf2Rp1>(42, 7, aux).unwrap())
+            .collect();
+        assert_eq!(nonces.len(), constants::SCH_Q as usize - 1);
+    }
+}
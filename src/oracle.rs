@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+use tfhe::ClientKey;
+
+use crate::backend::FheUint;
+use crate::poseidon::Lane;
+use crate::utils;
+
+/*
+ * Debug oracle: checks every intermediate value of the FHE circuits against
+ * the clear reference.
+ *
+ * A `DebugOracle` shares the `ClientKey` (so it is for debugging only; the
+ * key is behind an `Arc` rather than copied for every run) and is
+ * opt-in: within `DebugOracle::run`, the circuits consult it after every step
+ * (the signing phases, every Poseidon round, every modexp iteration). Each
+ * check decrypts the inputs and the output of the step, recomputes the step in
+ * the clear from the decrypted inputs, and records a `Divergence` if the
+ * output differs. Since every step is checked against its own inputs, the
+ * first divergence is the step that introduced the error, e.g. an overflow
+ * of the FHE bit width.
+ *
+ * The oracle is thread-local, like the server key: only the steps on the
+ * calling thread are checked (not e.g. the lanes run on an `FhePool`).
+ * `DebugOracle::clear` checks the `Sim` backend (see `sim.rs`), whose values
+ * need no key; outside of `run`, the checks cost nothing.
+ */
+
+pub struct DebugOracle {
+    client_key: Option<Arc<ClientKey>>,
+}
+
+// A step whose output differs from the clear computation on its inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub phase: &'static str,
+    // Round or iteration within the phase, if any
+    pub round: Option<usize>,
+    // State element (e.g. Poseidon lane), if any
+    pub index: Option<usize>,
+    pub got: u64,
+    pub expected: u64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.phase)?;
+        if let Some(round) = self.round {
+            write!(f, " round {}", round)?;
+        }
+        if let Some(index) = self.index {
+            write!(f, " element {}", index)?;
+        }
+        write!(f, ": got {}, expected {}", self.got, self.expected)
+    }
+}
+
+// Outcome of a `DebugOracle::run`
+#[derive(Clone, Debug, Default)]
+pub struct OracleReport {
+    // Number of values checked
+    pub n_checks: usize,
+    // In the order they were found
+    pub divergences: Vec<Divergence>,
+}
+
+impl OracleReport {
+    pub fn first_divergence(&self) -> Option<&Divergence> {
+        self.divergences.first()
+    }
+}
+
+struct Active {
+    client_key: Option<Arc<ClientKey>>,
+    report: OracleReport,
+}
+
+thread_local! {
+    // The oracle of the enclosing `DebugOracle::run` on this thread
+    static ACTIVE: RefCell<Option<Active>> = const { RefCell::new(None) };
+}
+
+impl DebugOracle {
+    pub fn new(client_key: Arc<ClientKey>) -> Self {
+        DebugOracle {
+            client_key: Some(client_key),
+        }
+    }
+
+    // Oracle for the `Sim` backend only
+    pub fn clear() -> Self {
+        DebugOracle { client_key: None }
+    }
+
+    // Run `f`, checking the circuit steps on this thread
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> (R, OracleReport) {
+        let active = Active {
+            client_key: self.client_key.clone(),
+            report: OracleReport::default(),
+        };
+        let outer = ACTIVE.with(|a| a.replace(Some(active)));
+        let result = f();
+        let active = ACTIVE.with(|a| a.replace(outer)).expect("oracle is active");
+        (result, active.report)
+    }
+}
+
+// Clear value of `x` if an oracle is active (decrypted with its key)
+pub fn reveal<C, E: FheUint<C>>(x: &E) -> Option<C> {
+    ACTIVE.with(|a| {
+        let active = a.borrow();
+        x.reveal(active.as_ref()?.client_key.as_deref())
+    })
+}
+
+// Clear values of all of `xs`, e.g. the inputs of a step
+pub fn reveal_all<C: Into<u64>, E: FheUint<C>>(xs: &[E]) -> Option<Vec<u64>> {
+    xs.iter().map(|x| reveal(x).map(Into::into)).collect()
+}
+
+pub fn reveal_lanes<C: Into<u64>, E: FheUint<C>>(lanes: &[Lane<E>]) -> Option<Vec<u64>> {
+    lanes
+        .iter()
+        .map(|lane| match lane {
+            Lane::Clear(x) => Some(*x),
+            Lane::Enc(x) => reveal(x).map(Into::into),
+        })
+        .collect()
+}
+
+// Check the output `value` of a step against `expected`, the clear computation
+// of the step on its revealed inputs (None if they are not known)
+pub fn check<C: Into<u64>, E: FheUint<C>>(
+    phase: &'static str,
+    round: Option<usize>,
+    value: &E,
+    expected: Option<u64>,
+) {
+    if let Some(expected) = expected {
+        let got = reveal(value).map(Into::into);
+        record(phase, round, None, got, expected);
+    }
+}
+
+// Same as `check` for each element of a state
+pub fn check_lanes<C: Into<u64>, E: FheUint<C>>(
+    phase: &'static str,
+    round: Option<usize>,
+    lanes: &[Lane<E>],
+    expected: Option<Vec<u64>>,
+) {
+    if let Some(expected) = expected {
+        for (j, (lane, expected)) in lanes.iter().zip(expected).enumerate() {
+            let got = match lane {
+                Lane::Clear(x) => Some(*x),
+                Lane::Enc(x) => reveal(x).map(Into::into),
+            };
+            record(phase, round, Some(j), got, expected);
+        }
+    }
+}
+
+fn record(
+    phase: &'static str,
+    round: Option<usize>,
+    index: Option<usize>,
+    got: Option<u64>,
+    expected: u64,
+) {
+    ACTIVE.with(|a| {
+        let mut a = a.borrow_mut();
+        let (Some(active), Some(got)) = (a.as_mut(), got) else {
+            return;
+        };
+        active.report.n_checks += 1;
+        if got != expected {
+            let divergence = Divergence {
+                phase,
+                round,
+                index,
+                got,
+                expected,
+            };
+            if active.report.divergences.is_empty() {
+                utils::log(&format!("DebugOracle: first divergence at {}", divergence));
+            }
+            active.report.divergences.push(divergence);
+        }
+    });
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+    use crate::modint::{ModInt, Modulus};
+    use crate::schnorr;
+    use crate::signing_key::SigningKey;
+    use crate::sim::{self, SimUint16, SimUint64};
+    use tfhe::{set_server_key, FheUint8};
+
+    #[test]
+    fn test_oracle_sim_sign() {
+        let x_sch = SigningKey::new(7).unwrap();
+        let (sig, report) = DebugOracle::clear().run(|| sim::sign_sim(&x_sch, b"msg", true, 2, 1));
+        assert_eq!(
+            sig,
+            schnorr::sign_clear::<crate::poseidon_p8::PoseidonP8Rf2Rp1>(&x_sch, b"msg")
+        );
+        assert!(sig.is_ok());
+        assert!(report.n_checks > 0);
+        assert_eq!(report.first_divergence(), None);
+
+        // No checks outside of `run`
+        let (_, report) = DebugOracle::clear().run(|| ());
+        sim::sign_sim(&x_sch, b"msg", true, 2, 1).unwrap();
+        assert_eq!(report.n_checks, 0);
+    }
+
+    #[test]
+    fn test_oracle_finds_overflow() {
+        // The squaring of 60000 overflows the 16-bit container (which
+        // `Modulus::new(65521u16)` refuses, see `modint.rs`)
+        let (_, report) = DebugOracle::clear().run(|| {
+            let x = SimUint16(60000);
+            let expected = 60000u64 * 60000 % 65521;
+            check("square", Some(0), &((x * &x) % 65521), Some(expected));
+        });
+        let first = report.first_divergence().expect("overflow not found");
+        assert_eq!((first.phase, first.round), ("square", Some(0)));
+        assert_eq!(first.expected, 60000u64 * 60000 % 65521);
+
+        // A 32-bit modulus fits in 64 bits: no divergence (the failure of
+        // `utils::test_fhe2_modexp_clear` was in its expected value)
+        let (g_clear, k, p) = (3196401078u64, 28192u64, 3552575077u64);
+        let g = ModInt::new(SimUint64(g_clear), Modulus::new(p).unwrap());
+        let (r, report) = DebugOracle::clear().run(|| utils::fhe2_modexp_64(&g, &SimUint64(k)));
+        assert_eq!(report.n_checks, 2 * 64);
+        assert_eq!(report.first_divergence(), None);
+        assert_eq!(r.value().0, utils::clear_modexp_64(g_clear, k, p));
+    }
+
+    #[test]
+    fn test_oracle_sign_enc() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let x_sch = SigningKey::new(5).unwrap();
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
+        let client_key = Arc::new(client_key);
+        let oracle = DebugOracle::new(client_key.clone());
+        let msg = schnorr::MessageVisibility::Public(b"msg");
+        let nonce_mode = schnorr::NonceMode::Deterministic;
+        let (sig_enc, report) = oracle.run(|| {
+            schnorr::sign_enc::<crate::poseidon_p8::PoseidonP8Rf2Rp1>(&x_sch_enc, msg, nonce_mode)
+        });
+        // 4 phases, and 3 rounds of 4 lanes per hash (two nonce hashes and
+        // the challenge hash)
+        assert_eq!(report.n_checks, 4 + 3 * 3 * constants::POSEIDON_T);
+        assert_eq!(report.first_divergence(), None);
+        assert_eq!(
+            sig_enc.unwrap().decrypt(&client_key),
+            schnorr::sign_clear::<crate::poseidon_p8::PoseidonP8Rf2Rp1>(&x_sch, b"msg").unwrap()
+        );
+    }
+}
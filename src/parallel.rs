@@ -0,0 +1,154 @@
+use std::cell::Cell;
+
+use rayon::prelude::*;
+use tfhe::{set_server_key, ServerKey};
+
+use crate::error::{Result, SchnorrFheError};
+
+/*
+ * Data-parallel execution of independent FHE ops: the circuits `map` over the
+ * S-boxes (lanes) of a full round and the rows of an MDS matrix
+ * multiplication, which do not depend on each other.
+ *
+ * tfhe's server key is thread-local, so FHE ops can only run on threads where
+ * it was set. `FhePool` is a rayon thread pool whose workers all have the
+ * server key; `map` and `join` run in parallel when called inside
+ * `FhePool::install`, and sequentially otherwise (e.g. on a thread that only
+ * called `set_server_key`).
+ * The pool size bounds the CPU use of everything installed in it, including
+ * tfhe's own rayon parallelism within each op.
+ */
+
+thread_local! {
+    // Whether this thread is an `FhePool` worker (and so has the server key)
+    static IS_POOL_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+pub struct FhePool {
+    pool: rayon::ThreadPool,
+}
+
+impl FhePool {
+    pub fn new(n_threads: usize, server_key: &ServerKey) -> Result<Self> {
+        if n_threads == 0 {
+            return Err(SchnorrFheError::InvalidParams(
+                "need at least one thread".to_string(),
+            ));
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n_threads)
+            .thread_name(|i| format!("fhe-pool-{}", i))
+            .build()
+            .map_err(|e| {
+                SchnorrFheError::InvalidParams(format!("cannot build the thread pool: {}", e))
+            })?;
+        pool.broadcast(|_| {
+            set_server_key(server_key.clone());
+            IS_POOL_WORKER.with(|x| x.set(true));
+        });
+        Ok(FhePool { pool })
+    }
+
+    pub fn n_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    // Run `f` on the pool; `map` calls within `f` run in parallel
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        self.pool.install(f)
+    }
+}
+
+// Map `f` over independent items, in order; parallel within an `FhePool`
+pub fn map<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    if IS_POOL_WORKER.with(|x| x.get()) {
+        items.into_par_iter().map(f).collect()
+    } else {
+        items.into_iter().map(f).collect()
+    }
+}
+
+// Run `f` with `map` and `join` sequential on this thread, even within an
+// `FhePool`, e.g. for a dry run whose ops are counted per thread (see
+// `cost::count_ops`)
+pub fn sequential<R>(f: impl FnOnce() -> R) -> R {
+    let is_pool_worker = IS_POOL_WORKER.with(|x| x.replace(false));
+    let result = f();
+    IS_POOL_WORKER.with(|x| x.set(is_pool_worker));
+    result
+}
+
+// Run two independent closures; in parallel within an `FhePool`
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    if IS_POOL_WORKER.with(|x| x.get()) {
+        rayon::join(a, b)
+    } else {
+        (a(), b())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+    use crate::poseidon_p8;
+    use crate::utils;
+    use tfhe::prelude::*;
+    use tfhe::FheUint8;
+
+    #[test]
+    fn test_map() {
+        let (_client_key, server_key, _public_key) = utils::init_keys();
+        let pool = FhePool::new(3, &server_key).unwrap();
+        assert_eq!(pool.n_threads(), 3);
+
+        let items: Vec<u32> = (0..100).collect();
+        let expected: Vec<u32> = items.iter().map(|x| x * x).collect();
+        assert_eq!(map(items.clone(), |x| x * x), expected);
+        assert_eq!(pool.install(|| map(items, |x| x * x)), expected);
+        assert!(pool.install(|| IS_POOL_WORKER.with(|x| x.get())));
+        assert_eq!(join(|| 1, || 2), (1, 2));
+        assert_eq!(pool.install(|| join(|| 1, || 2)), (1, 2));
+    }
+
+    // Poseidon on the pool must match the sequential result
+    #[test]
+    fn test_fhe_pool_poseidon() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key.clone());
+        let pool = FhePool::new(2, &server_key).unwrap();
+
+        let clear: [u8; constants::POSEIDON_T] = [1u8, 0u8, 2u8, 8u8];
+        let inputs: Vec<FheUint8> = clear
+            .iter()
+            .map(|x| FheUint8::encrypt(*x, &client_key))
+            .collect();
+        let inputs: [&FheUint8; constants::POSEIDON_T] = std::array::from_fn(|i| &inputs[i]);
+
+        let output: u8 = pool
+            .install(|| poseidon_p8::poseidon_p8_lut_rf2_rp1(inputs))
+            .unwrap()
+            .decrypt(&client_key);
+        assert_eq!(output, 13);
+        let output: u8 = pool
+            .install(|| poseidon_p8::poseidon_p8_rf2_rp1(inputs))
+            .unwrap()
+            .decrypt(&client_key);
+        assert_eq!(output, 13);
+    }
+}
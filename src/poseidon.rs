@@ -3,32 +3,139 @@ use num_traits::ToPrimitive;
 use tfhe::prelude::*;
 use tfhe::{FheUint32, FheUint64};
 
+use crate::backend::{Backend, FheUint, Tfhe};
 use crate::constants;
+use crate::cost::{self, CountingUint64};
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{self, ClearHash, FheHash, MixedInput};
+use crate::modint::{ModInt, Modulus};
+use crate::oracle;
+use crate::parallel;
+use crate::trace;
 use crate::utils;
 
-fn apply_mds_matrix_u64(
-    inputs: Vec<FheUint64>,
+fn apply_mds_matrix_u64<E: FheUint<u64>>(
+    inputs: Vec<E>,
     mds_matrix: &[[u64; constants::POSEIDON_T]; constants::POSEIDON_T],
-    prime: u64,
-) -> Vec<FheUint64> {
+    modulus: Modulus<u64>,
+) -> Vec<E> {
     /* For a t x t MDS matrix, there is:
      * - t inputs and t outputs
      * - t x t multiplications
      * - t x (t-1) additions
      * - t x (2t-1) (mod p) operations
      */
-    let mut outputs: Vec<FheUint64> = Vec::new();
-    for i in 0..inputs.len() {
+    parallel::map((0..inputs.len()).collect(), |i| {
         // Since we cannot initialize an FheUint zero without an encryption key
         // we will initialize the output as the first multiplication
-        let mut output: FheUint64 = &(&inputs[0] * mds_matrix[i][0]) % prime;
-        for j in 1..inputs.len() {
-            let mult = &(&inputs[j] * mds_matrix[i][j]) % prime;
-            output = &(&output + &mult) % prime;
+        let input = |j: usize| ModInt::from_reduced(inputs[j].clone(), modulus);
+        let mut output = input(0).mul_clear(mds_matrix[i][0]);
+        for (j, m) in mds_matrix[i].iter().enumerate().skip(1) {
+            let mult = input(j).mul_clear(*m);
+            output = output.add(&mult);
+        }
+        output.into_inner()
+    })
+}
+
+/*
+ * Poseidon state element, tracked in the clear until it mixes with a
+ * ciphertext. E.g. in H(m, x_sch, 0, 0) the zero lanes are public, so their
+ * first-round constant add and S-box are clear computations, and their MDS
+ * terms fold into one clear constant per row. Since the MDS matrix is dense,
+ * every lane is encrypted after the first round with an encrypted input.
+ *
+ * Clear values are always reduced (mod p), so u64 fits both fields. So are the
+ * encrypted ones: the round functions take them as `ModInt`s (see `modint.rs`).
+ */
+#[derive(Clone)]
+pub enum Lane<E> {
+    Clear(u64),
+    Enc(E),
+}
+
+impl<E> Lane<E> {
+    pub fn is_clear(&self) -> bool {
+        matches!(self, Lane::Clear(_))
+    }
+
+    // The ciphertext of an encrypted lane
+    pub fn into_enc(self) -> Option<E> {
+        match self {
+            Lane::Clear(_) => None,
+            Lane::Enc(x) => Some(x),
         }
-        outputs.push(output);
     }
-    outputs
+}
+
+// x -> (x + rc) (mod p), followed by the S-box x -> x^alpha (mod p) if `sbox`
+fn add_rc_sbox_lane_u64<E: FheUint<u64>>(
+    lane: &Lane<E>,
+    rc: u64,
+    sbox: bool,
+    modulus: Modulus<u64>,
+) -> Lane<E> {
+    let alpha = constants::POSEIDON_ALPHA as u64;
+    let prime = modulus.value();
+    match lane {
+        Lane::Clear(x) => {
+            let x = (x + rc) % prime;
+            Lane::Clear(if sbox {
+                utils::clear_modexp_64(x, alpha, prime)
+            } else {
+                x
+            })
+        }
+        Lane::Enc(x) => {
+            // addition of FheUint64 and u64, then mod prime
+            let x = ModInt::from_reduced(x.clone(), modulus).add_clear(rc);
+            Lane::Enc(if sbox {
+                utils::fhe_modexp_64(&x, alpha).into_inner()
+            } else {
+                x.into_inner()
+            })
+        }
+    }
+}
+
+// Same as `apply_mds_matrix_u64`, but the clear terms of each row are summed in
+// the clear and added once; rows with only clear terms stay clear
+fn apply_mds_matrix_lanes_u64<E: FheUint<u64>>(
+    lanes: Vec<Lane<E>>,
+    mds_matrix: &[[u64; constants::POSEIDON_T]; constants::POSEIDON_T],
+    modulus: Modulus<u64>,
+) -> Vec<Lane<E>> {
+    let prime = modulus.value();
+    if lanes.iter().all(|x| !x.is_clear()) {
+        let inputs: Vec<E> = lanes.into_iter().filter_map(Lane::into_enc).collect();
+        return apply_mds_matrix_u64(inputs, mds_matrix, modulus)
+            .into_iter()
+            .map(Lane::Enc)
+            .collect();
+    }
+    parallel::map(mds_matrix.iter().collect(), |row| {
+        // u128 since the product of two 32-bit field elements fills a u64
+        let mut clear_sum: u128 = 0;
+        let mut enc_sum: Option<ModInt<E, u64>> = None;
+        for (m, lane) in row.iter().zip(lanes.iter()) {
+            match lane {
+                Lane::Clear(x) => clear_sum = (clear_sum + *m as u128 * *x as u128) % prime as u128,
+                Lane::Enc(x) => {
+                    let mult = ModInt::from_reduced(x.clone(), modulus).mul_clear(*m);
+                    enc_sum = Some(match enc_sum {
+                        Some(sum) => sum.add(&mult),
+                        None => mult,
+                    });
+                }
+            }
+        }
+        let clear_sum = clear_sum as u64;
+        match enc_sum {
+            None => Lane::Clear(clear_sum),
+            Some(sum) if clear_sum == 0 => Lane::Enc(sum.into_inner()),
+            Some(sum) => Lane::Enc(sum.add_clear(clear_sum).into_inner()),
+        }
+    })
 }
 
 fn apply_mds_matrix_biguint(
@@ -48,20 +155,41 @@ fn apply_mds_matrix_biguint(
     outputs
 }
 
-fn pick_round_constants_p32(r_full: usize, r_partial: usize) -> Vec<u64> {
+// Check (r_full, r_partial) against the number of round constants: r_full
+// must be even, and there is one constant per state element and round (only
+// the full rounds and r_full = 2, r_partial = 1 have constants)
+pub fn check_rounds(n_rc: usize, r_full: usize, r_partial: usize) -> Result<()> {
+    if !r_full.is_multiple_of(2) {
+        return Err(SchnorrFheError::InvalidParams(format!(
+            "r_full must be even, got {}",
+            r_full
+        )));
+    }
+    if n_rc != (r_full + r_partial) * constants::POSEIDON_T {
+        return Err(SchnorrFheError::HashParamMismatch(format!(
+            "no round constants for r_full = {}, r_partial = {}",
+            r_full, r_partial
+        )));
+    }
+    Ok(())
+}
+
+fn pick_round_constants_p32(r_full: usize, r_partial: usize) -> Result<Vec<u64>> {
     // Pick the round constants based on the number of full and partial rounds
-    if r_full == 2 && r_partial == 1 {
+    let rc_list = if r_full == 2 && r_partial == 1 {
         T4_P32_RC_R1_TEST.to_vec()
     } else {
         T4_P32_RC.to_vec()
-    }
+    };
+    check_rounds(rc_list.len(), r_full, r_partial)?;
+    Ok(rc_list)
 }
 
-pub fn poseidon_p32_rf2_rp1(inputs: [&FheUint32; constants::POSEIDON_T]) -> FheUint32 {
+pub fn poseidon_p32_rf2_rp1(inputs: [&FheUint32; constants::POSEIDON_T]) -> Result<FheUint32> {
     poseidon_p32_impl(inputs, 2, 1)
 }
 
-pub fn poseidon_p32(inputs: [&FheUint32; constants::POSEIDON_T]) -> FheUint32 {
+pub fn poseidon_p32(inputs: [&FheUint32; constants::POSEIDON_T]) -> Result<FheUint32> {
     poseidon_p32_impl(
         inputs,
         constants::POSEIDON_R_FULL,
@@ -73,11 +201,8 @@ pub fn poseidon_p32_impl(
     inputs: [&FheUint32; constants::POSEIDON_T],
     r_full: usize,
     r_partial: usize,
-) -> FheUint32 {
-    let rc_list = pick_round_constants_p32(r_full, r_partial);
-    assert_eq!(rc_list.len(), (r_full + r_partial) * constants::POSEIDON_T);
-    // Check r_full is even
-    assert_eq!(r_full % 2, 0);
+) -> Result<FheUint32> {
+    let rc_list = pick_round_constants_p32(r_full, r_partial)?;
 
     // Apply mod prime to all inputs (refs in, values out)
     // Note that we dont yet need to move up bit-width since inputs must fit in
@@ -94,62 +219,168 @@ pub fn poseidon_p32_impl(
     //   (3 + 3) % 2^2 % 3 = 2, but (3 % 3 + 3 % 3) % 3 = 0
     // - (a * b) % p != (a % p * b % p) % p since the product can overflow too
     // To handle overflow, we will use a larger type for the intermediate operations
-    // and then cast down the results before returning.
+    // and then cast down the results before returning; `Modulus::new` checks
+    // that the 64-bit type is large enough for the prime (see `modint.rs`).
     // Now convert the inputs to FheUint64.
-    let p32_64 = constants::POSEIDON_P_32 as u64;
-    let mut inputs_64: Vec<FheUint64> = inputs.iter().map(|x| x.clone().cast_into()).collect();
-    let mut rc_counter: usize = 0;
+    let modulus = Modulus::new(constants::POSEIDON_P_32 as u64)?;
+    let state: Vec<Lane<FheUint64>> = inputs
+        .into_iter()
+        .map(|x| Lane::Enc(x.cast_into()))
+        .collect();
+    Ok(lane_to_fhe_u32(poseidon_p32_lanes(
+        state, &rc_list, r_full, r_partial, modulus,
+    )))
+}
 
-    // Full rounds: first half
-    for _i in 0..r_full / 2 {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            // inner &: addition of FheUint64 and u64; outer &: mod p32_64
-            let new_val = &(&inputs_64[j] + rc_list[rc_counter]) % p32_64;
-            rc_counter += 1;
+// Same as `poseidon_p32`, but clear inputs (e.g. zero padding) are kept in the
+// clear until they mix with the encrypted ones; see `Lane`
+pub fn poseidon_p32_mixed_rf2_rp1(
+    inputs: [MixedInput<u32, FheUint32>; constants::POSEIDON_T],
+) -> Result<FheUint32> {
+    poseidon_p32_mixed_impl::<Tfhe>(inputs, 2, 1)
+}
 
-            // 2. Apply S-Box
-            let new_val = utils::fhe_modexp_64(&new_val, constants::POSEIDON_ALPHA as u64, p32_64);
-            inputs_64[j] = new_val;
-        }
-        // 3. Apply MDS matrix
-        inputs_64 = apply_mds_matrix_u64(inputs_64, &T4_P32_MDS_MATRIX, p32_64);
-    }
+pub fn poseidon_p32_mixed(
+    inputs: [MixedInput<u32, FheUint32>; constants::POSEIDON_T],
+) -> Result<FheUint32> {
+    poseidon_p32_mixed_impl::<Tfhe>(
+        inputs,
+        constants::POSEIDON_R_FULL,
+        constants::POSEIDON_R_PARTIAL,
+    )
+}
 
-    // Partial rounds
-    for _i in 0..r_partial {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            let new_val = &(&inputs_64[j] + rc_list[rc_counter]) % p32_64;
-            inputs_64[j] = new_val;
-            rc_counter += 1;
-        }
-        // 2. Apply S-Box
-        inputs_64[0] =
-            utils::fhe_modexp_64(&inputs_64[0], constants::POSEIDON_ALPHA as u64, p32_64);
-        // 3. Apply MDS matrix
-        inputs_64 = apply_mds_matrix_u64(inputs_64, &T4_P32_MDS_MATRIX, p32_64);
-    }
+// Generic over the backend (see `backend.rs`)
+pub fn poseidon_p32_mixed_impl<B: Backend>(
+    inputs: [MixedInput<u32, B::U32>; constants::POSEIDON_T],
+    r_full: usize,
+    r_partial: usize,
+) -> Result<B::U32> {
+    let rc_list = pick_round_constants_p32(r_full, r_partial)?;
 
-    // Full rounds: second half
-    for _i in 0..r_full / 2 {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            // inner &: addition of FheUint64 and u64; outer &: mod p32_64
-            let new_val = &(&inputs_64[j] + rc_list[rc_counter]) % p32_64;
-            rc_counter += 1;
+    let p32 = constants::POSEIDON_P_32;
+    let modulus = Modulus::new(p32 as u64)?;
+    let state: Vec<Lane<B::U64>> = inputs
+        .iter()
+        .map(|x| match x {
+            MixedInput::Clear(x) => Lane::Clear((x % p32) as u64),
+            MixedInput::Enc(x) => {
+                let x: B::U32 = x.clone() % p32;
+                Lane::Enc(x.cast_into())
+            }
+        })
+        .collect();
+    Ok(lane_to_fhe_u32(poseidon_p32_lanes(
+        state, &rc_list, r_full, r_partial, modulus,
+    )))
+}
 
-            // 2. Apply S-Box
-            let new_val = utils::fhe_modexp_64(&new_val, constants::POSEIDON_ALPHA as u64, p32_64);
-            inputs_64[j] = new_val;
-        }
-        // 3. Apply MDS matrix
-        inputs_64 = apply_mds_matrix_u64(inputs_64, &T4_P32_MDS_MATRIX, p32_64);
+// The Poseidon permutation on (mod p) lanes; returns the second element
+fn poseidon_p32_lanes<E: FheUint<u64>>(
+    mut state: Vec<Lane<E>>,
+    rc_list: &[u64],
+    r_full: usize,
+    r_partial: usize,
+    modulus: Modulus<u64>,
+) -> Lane<E> {
+    let t = constants::POSEIDON_T;
+
+    // Full rounds (first half), partial rounds, full rounds (second half)
+    for r in 0..r_full + r_partial {
+        let is_full_round = r < r_full / 2 || r >= r_full / 2 + r_partial;
+        let rc = &rc_list[r * t..(r + 1) * t];
+        let p32_64 = modulus.value();
+        state = traced_round(
+            r,
+            is_full_round,
+            state,
+            |s| poseidon_round_clear(s, rc, is_full_round, &T4_P32_MDS_MATRIX, p32_64),
+            |lanes: Vec<Lane<CountingUint64>>| {
+                poseidon_p32_round(lanes, rc, is_full_round, modulus)
+            },
+            |state| poseidon_p32_round(state, rc, is_full_round, modulus),
+        );
     }
 
-    // Return the first element
-    let hash: FheUint32 = inputs_64[1].clone().cast_into();
-    hash
+    // Return the second element
+    state.swap_remove(1)
+}
+
+// One round of `poseidon_p32_lanes` with the round constants `rc`
+fn poseidon_p32_round<E: FheUint<u64>>(
+    state: Vec<Lane<E>>,
+    rc: &[u64],
+    is_full_round: bool,
+    modulus: Modulus<u64>,
+) -> Vec<Lane<E>> {
+    // 1. Add round constants
+    // 2. Apply S-Box (partial rounds: only on the first element)
+    let state = parallel::map(state.into_iter().enumerate().collect(), |(j, lane)| {
+        add_rc_sbox_lane_u64(&lane, rc[j], is_full_round || j == 0, modulus)
+    });
+    // 3. Apply MDS matrix
+    apply_mds_matrix_lanes_u64(state, &T4_P32_MDS_MATRIX, modulus)
+}
+
+// Round `r` of a Poseidon permutation on `state` (see `trace::traced_step`):
+// traced as a `poseidon_round` phase with the ops of `dry_run`, the same round
+// on `Counting` lanes, and checked by the debug oracle against `clear`, the
+// round on the revealed lanes
+pub fn traced_round<C: Into<u64>, E: FheUint<C>, D: Default, R>(
+    r: usize,
+    is_full_round: bool,
+    state: Vec<Lane<E>>,
+    clear: impl FnOnce(&[u64]) -> Vec<u64>,
+    dry_run: impl FnOnce(Vec<Lane<D>>) -> R,
+    round: impl FnOnce(Vec<Lane<E>>) -> Vec<Lane<E>>,
+) -> Vec<Lane<E>> {
+    let expected = oracle::reveal_lanes(&state).map(|s| clear(&s));
+    let lanes = cost::counting_lanes(&state);
+    trace::traced_step(
+        crate::phase!("poseidon_round", round = r, full = is_full_round),
+        || dry_run(lanes),
+        || round(state),
+        |state| oracle::check_lanes("poseidon_round", Some(r), state, expected),
+    )
+}
+
+// One Poseidon round on clear (mod p) lanes, in u128 arithmetic: the
+// reference of the round functions for the debug oracle (see `oracle.rs`)
+pub fn poseidon_round_clear<R: Copy + Into<u64>>(
+    state: &[u64],
+    rc: &[R],
+    is_full_round: bool,
+    mds_matrix: &[[R; constants::POSEIDON_T]; constants::POSEIDON_T],
+    prime: u64,
+) -> Vec<u64> {
+    let alpha = constants::POSEIDON_ALPHA as u64;
+    let state: Vec<u128> = state
+        .iter()
+        .enumerate()
+        .map(|(j, x)| {
+            let x = (*x as u128 + rc[j].into() as u128) % prime as u128;
+            let exp = if is_full_round || j == 0 { alpha } else { 1 };
+            utils::clear_modexp_64(x as u64, exp, prime) as u128
+        })
+        .collect();
+    mds_matrix
+        .iter()
+        .map(|row| {
+            let sum: u128 = row
+                .iter()
+                .zip(&state)
+                .map(|(m, x)| (*m).into() as u128 * x % prime as u128)
+                .sum();
+            (sum % prime as u128) as u64
+        })
+        .collect()
+}
+
+fn lane_to_fhe_u32<E32: FheUint<u32>, E: CastInto<E32>>(lane: Lane<E>) -> E32 {
+    match lane {
+        Lane::Clear(x) => E32::encrypt_trivial(x as u32),
+        Lane::Enc(x) => x.cast_into(),
+    }
 }
 
 /*
@@ -164,11 +395,11 @@ pub fn poseidon_p32_impl(
  * can handle any bit-width.
  */
 
-pub fn poseidon_p32_clear_rf2_rp1(inputs: [u32; constants::POSEIDON_T]) -> u32 {
+pub fn poseidon_p32_clear_rf2_rp1(inputs: [u32; constants::POSEIDON_T]) -> Result<u32> {
     poseidon_p32_clear_impl(inputs, 2, 1)
 }
 
-pub fn poseidon_p32_clear(inputs: [u32; constants::POSEIDON_T]) -> u32 {
+pub fn poseidon_p32_clear(inputs: [u32; constants::POSEIDON_T]) -> Result<u32> {
     poseidon_p32_clear_impl(
         inputs,
         constants::POSEIDON_R_FULL,
@@ -180,10 +411,8 @@ pub fn poseidon_p32_clear_impl(
     inputs: [u32; constants::POSEIDON_T],
     r_full: usize,
     r_partial: usize,
-) -> u32 {
-    let rc_list = pick_round_constants_p32(r_full, r_partial);
-    assert_eq!(rc_list.len(), (r_full + r_partial) * constants::POSEIDON_T);
-    assert_eq!(r_full % 2, 0);
+) -> Result<u32> {
+    let rc_list = pick_round_constants_p32(r_full, r_partial)?;
 
     // Apply mod prime to all inputs (refs in, values out)
     // Note that we dont yet need to move up bit-width since inputs must fit in
@@ -250,8 +479,62 @@ pub fn poseidon_p32_clear_impl(
     }
 
     // Return the first element
-    let hash: u32 = inputs[1].to_u32().expect("Failed to convert to u32");
-    hash
+    inputs[1].to_u32().ok_or(SchnorrFheError::WidthOverflow {
+        what: "Poseidon hash",
+        bits: u32::BITS,
+    })
+}
+
+// Poseidon over the 32-bit field
+pub struct PoseidonP32;
+
+impl ClearHash for PoseidonP32 {
+    type Clear = u32;
+    const NAME: &'static str = "poseidon_p32";
+    const ARITY: usize = constants::POSEIDON_T;
+
+    fn hash_clear(inputs: &[u32]) -> Result<u32> {
+        poseidon_p32_clear(hash::pad_clear(inputs)?)
+    }
+}
+
+impl FheHash for PoseidonP32 {
+    type Enc = FheUint32;
+
+    fn hash_enc(inputs: &[FheUint32]) -> Result<FheUint32> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        poseidon_p32(std::array::from_fn(|i| &padded[i]))
+    }
+
+    fn hash_mixed(inputs: &[MixedInput<u32, FheUint32>]) -> Result<FheUint32> {
+        poseidon_p32_mixed(hash::pad_mixed(inputs)?)
+    }
+}
+
+// Same as `PoseidonP32` but with r_full = 2, r_partial = 1 (for fast testing)
+pub struct PoseidonP32Rf2Rp1;
+
+impl ClearHash for PoseidonP32Rf2Rp1 {
+    type Clear = u32;
+    const NAME: &'static str = "poseidon_p32_rf2_rp1";
+    const ARITY: usize = constants::POSEIDON_T;
+
+    fn hash_clear(inputs: &[u32]) -> Result<u32> {
+        poseidon_p32_clear_rf2_rp1(hash::pad_clear(inputs)?)
+    }
+}
+
+impl FheHash for PoseidonP32Rf2Rp1 {
+    type Enc = FheUint32;
+
+    fn hash_enc(inputs: &[FheUint32]) -> Result<FheUint32> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        poseidon_p32_rf2_rp1(std::array::from_fn(|i| &padded[i]))
+    }
+
+    fn hash_mixed(inputs: &[MixedInput<u32, FheUint32>]) -> Result<FheUint32> {
+        poseidon_p32_mixed_rf2_rp1(hash::pad_mixed(inputs)?)
+    }
 }
 
 ////////////////////////////// Poseidon constants //////////////////////////////
@@ -350,8 +633,8 @@ mod tests {
             FheUint64::encrypt(2u64, &client_key),
             FheUint64::encrypt(8u64, &client_key),
         ];
-        let output =
-            apply_mds_matrix_u64(inputs, &T4_P32_MDS_MATRIX, constants::POSEIDON_P_32 as u64);
+        let modulus = Modulus::new(constants::POSEIDON_P_32 as u64).unwrap();
+        let output = apply_mds_matrix_u64(inputs, &T4_P32_MDS_MATRIX, modulus);
         let output: Vec<u64> = output.iter().map(|x| x.decrypt(&client_key)).collect();
 
         // Print output
@@ -386,7 +669,7 @@ mod tests {
             &FheUint32::encrypt(8u32, &client_key),
         ];
         // let output = poseidon_p32(inputs.iter().collect(), 2, 1);
-        let output = poseidon_p32_rf2_rp1(inputs);
+        let output = poseidon_p32_rf2_rp1(inputs).unwrap();
         let output: u32 = output.decrypt(&client_key);
 
         // Expected output based on T4_P32_MDS_MATRIX @ [1, 0, 2, 8]
@@ -402,6 +685,32 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    // Clear lanes (here the message and a zero) must not change the hash
+    #[test]
+    fn test_poseidon_p32_mixed_rf2_rp1() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let inputs: [MixedInput<u32, FheUint32>; constants::POSEIDON_T] = [
+            MixedInput::Clear(1u32),
+            MixedInput::Enc(FheUint32::encrypt(0u32, &client_key)),
+            MixedInput::Enc(FheUint32::encrypt(2u32, &client_key)),
+            MixedInput::Clear(8u32),
+        ];
+        let output: u32 = poseidon_p32_mixed_rf2_rp1(inputs)
+            .unwrap()
+            .decrypt(&client_key);
+        // see `test_poseidon_p32_rf2_rp1` above for expected result
+        assert_eq!(output, 1725970220);
+
+        let inputs: [MixedInput<u32, FheUint32>; constants::POSEIDON_T] =
+            [1u32, 0u32, 2u32, 8u32].map(MixedInput::Clear);
+        let output: u32 = poseidon_p32_mixed_rf2_rp1(inputs)
+            .unwrap()
+            .decrypt(&client_key);
+        assert_eq!(output, 1725970220);
+    }
+
     // Test Poseidon end-to-end
     // NOTE: this hash run takes ~13284.12s on M2 Max.
     #[test]
@@ -416,7 +725,7 @@ mod tests {
             &FheUint32::encrypt(2u32, &client_key),
             &FheUint32::encrypt(8u32, &client_key),
         ];
-        let output = poseidon_p32(inputs);
+        let output = poseidon_p32(inputs).unwrap();
         let output: u32 = output.decrypt(&client_key);
 
         // Expected output based on T4_P32_MDS_MATRIX @ [1, 0, 2, 8]
@@ -437,7 +746,7 @@ mod tests {
     fn test_poseidon_p32_clear_full() {
         // Inputs
         let inputs: [u32; constants::POSEIDON_T] = [1u32, 0u32, 2u32, 8u32];
-        let output = poseidon_p32_clear(inputs);
+        let output = poseidon_p32_clear(inputs).unwrap();
         // see `test_poseidon_p32_full` above for expected result
         let expected: u32 = 1502657535;
         assert_eq!(output, expected);
@@ -447,7 +756,7 @@ mod tests {
     fn test_poseidon_p32_clear_rf2_rp1() {
         // Inputs
         let inputs: [u32; constants::POSEIDON_T] = [1u32, 0u32, 2u32, 8u32];
-        let output = poseidon_p32_clear_rf2_rp1(inputs);
+        let output = poseidon_p32_clear_rf2_rp1(inputs).unwrap();
         // see `test_poseidon_p32_rf2_rp1` above for expected result
         let expected: u32 = 1725970220;
         assert_eq!(output, expected);
@@ -468,7 +777,7 @@ mod tests {
             &FheUint32::encrypt(2u32, &client_key),
             &FheUint32::encrypt(8u32, &client_key),
         ];
-        let output = poseidon_p32(inputs);
+        let output = poseidon_p32(inputs).unwrap();
         let output: u32 = output.decrypt(&client_key);
 
         // Expected output based on T4_P32_MDS_MATRIX @ [1, 0, 2, 8]
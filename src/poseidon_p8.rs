@@ -3,7 +3,15 @@ use num_traits::ToPrimitive;
 use tfhe::prelude::*;
 use tfhe::{FheUint16, FheUint8};
 
+use crate::backend::{Backend, FheUint, Tfhe};
 use crate::constants;
+use crate::cost::{Counting, CountingUint16};
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{self, ClearHash, FheHash, MixedInput};
+use crate::lut;
+use crate::modint::{ModInt, Modulus};
+use crate::parallel;
+use crate::poseidon::{self, Lane};
 use crate::utils;
 
 /* DEBT: This is a copy of the poseidon module from schnorr-fhe/src/poseidon.rs
@@ -11,29 +19,137 @@ use crate::utils;
  * code to handle any bit-width.
  */
 
-fn apply_mds_matrix_u16(
-    inputs: Vec<FheUint16>,
+fn apply_mds_matrix_u16<E: FheUint<u16>>(
+    inputs: Vec<E>,
     mds_matrix: &[[u16; constants::POSEIDON_T]; constants::POSEIDON_T],
-    prime: u16,
-) -> Vec<FheUint16> {
+    modulus: Modulus<u16>,
+) -> Vec<E> {
     /* For a t x t MDS matrix, there is:
      * - t inputs and t outputs
      * - t x t multiplications
      * - t x (t-1) additions
      * - t x (2t-1) (mod p) operations
      */
-    let mut outputs: Vec<FheUint16> = Vec::new();
-    for i in 0..inputs.len() {
+    parallel::map((0..inputs.len()).collect(), |i| {
         // Since we cannot initialize an FheUint zero without an encryption key
         // we will initialize the output as the first multiplication
-        let mut output: FheUint16 = &(&inputs[0] * mds_matrix[i][0]) % prime;
-        for j in 1..inputs.len() {
-            let mult = &(&inputs[j] * mds_matrix[i][j]) % prime;
-            output = &(&output + &mult) % prime;
+        let input = |j: usize| ModInt::from_reduced(inputs[j].clone(), modulus);
+        let mut output = input(0).mul_clear(mds_matrix[i][0]);
+        for (j, m) in mds_matrix[i].iter().enumerate().skip(1) {
+            let mult = input(j).mul_clear(*m);
+            output = output.add(&mult);
+        }
+        output.into_inner()
+    })
+}
+
+// x -> (x + rc) (mod p), followed by the S-box x -> x^alpha (mod p) if `sbox`
+fn add_rc_sbox_lane_u16<E: FheUint<u16>>(
+    lane: &Lane<E>,
+    rc: u16,
+    sbox: bool,
+    modulus: Modulus<u16>,
+) -> Lane<E> {
+    let alpha = constants::POSEIDON_ALPHA;
+    let prime = modulus.value();
+    match lane {
+        Lane::Clear(x) => {
+            let x = (x + rc as u64) % prime as u64;
+            Lane::Clear(if sbox {
+                utils::clear_modexp_64(x, alpha as u64, prime as u64)
+            } else {
+                x
+            })
+        }
+        Lane::Enc(x) => {
+            // addition of FheUint16 and u16, then mod prime
+            let x = ModInt::from_reduced(x.clone(), modulus).add_clear(rc);
+            Lane::Enc(if sbox {
+                utils::fhe_modexp_16(&x, alpha as u16).into_inner()
+            } else {
+                x.into_inner()
+            })
         }
-        outputs.push(output);
     }
-    outputs
+}
+
+// Same as `apply_mds_matrix_u16`, but the clear terms of each row are summed in
+// the clear and added once; rows with only clear terms stay clear
+fn apply_mds_matrix_lanes_u16<E: FheUint<u16>>(
+    lanes: Vec<Lane<E>>,
+    mds_matrix: &[[u16; constants::POSEIDON_T]; constants::POSEIDON_T],
+    modulus: Modulus<u16>,
+) -> Vec<Lane<E>> {
+    let prime = modulus.value();
+    if lanes.iter().all(|x| !x.is_clear()) {
+        let inputs: Vec<E> = lanes.into_iter().filter_map(Lane::into_enc).collect();
+        return apply_mds_matrix_u16(inputs, mds_matrix, modulus)
+            .into_iter()
+            .map(Lane::Enc)
+            .collect();
+    }
+    parallel::map(mds_matrix.iter().collect(), |row| {
+        let mut clear_sum: u64 = 0;
+        let mut enc_sum: Option<ModInt<E, u16>> = None;
+        for (m, lane) in row.iter().zip(lanes.iter()) {
+            match lane {
+                Lane::Clear(x) => clear_sum = (clear_sum + *m as u64 * x) % prime as u64,
+                Lane::Enc(x) => {
+                    let mult = ModInt::from_reduced(x.clone(), modulus).mul_clear(*m);
+                    enc_sum = Some(match enc_sum {
+                        Some(sum) => sum.add(&mult),
+                        None => mult,
+                    });
+                }
+            }
+        }
+        let clear_sum = clear_sum as u16;
+        match enc_sum {
+            None => Lane::Clear(clear_sum as u64),
+            Some(sum) if clear_sum == 0 => Lane::Enc(sum.into_inner()),
+            Some(sum) => Lane::Enc(sum.add_clear(clear_sum).into_inner()),
+        }
+    })
+}
+
+// LUT variant of `apply_mds_matrix_lanes_u16` (see `lut::fhe_lut_apply_matrix_8`);
+// the clear sum of each row is added inside the final reduction lookup
+fn apply_mds_matrix_lanes_lut<B: Backend>(
+    lanes: Vec<Lane<B::U8>>,
+    mds_matrix: &[[u16; constants::POSEIDON_T]; constants::POSEIDON_T],
+    prime: u8,
+) -> Vec<Lane<B::U8>> {
+    if lanes.iter().all(|x| !x.is_clear()) {
+        let inputs: Vec<B::U8> = lanes.into_iter().filter_map(Lane::into_enc).collect();
+        return lut::fhe_lut_apply_matrix_8::<B, { constants::POSEIDON_T }>(inputs, mds_matrix, prime)
+            .into_iter()
+            .map(Lane::Enc)
+            .collect();
+    }
+    parallel::map(mds_matrix.iter().collect(), |row| {
+        let mut clear_sum: u64 = 0;
+        let mut enc_sum: Option<B::U16> = None;
+        for (m, lane) in row.iter().zip(lanes.iter()) {
+            match lane {
+                Lane::Clear(x) => clear_sum = (clear_sum + *m as u64 * x) % prime as u64,
+                Lane::Enc(x) => {
+                    let mult: B::U16 =
+                        lut::fhe_lut_scalar_modmul_8(x, *m as u8, prime).cast_into();
+                    enc_sum = Some(match enc_sum {
+                        Some(sum) => sum + &mult,
+                        None => mult,
+                    });
+                }
+            }
+        }
+        match enc_sum {
+            None => Lane::Clear(clear_sum),
+            Some(sum) => {
+                let output = (sum + clear_sum as u16) % prime as u16;
+                Lane::Enc(output.cast_into())
+            }
+        }
+    })
 }
 
 fn apply_mds_matrix_biguint(
@@ -53,20 +169,22 @@ fn apply_mds_matrix_biguint(
     outputs
 }
 
-fn pick_round_constants_p8(r_full: usize, r_partial: usize) -> Vec<u16> {
+fn pick_round_constants_p8(r_full: usize, r_partial: usize) -> Result<Vec<u16>> {
     // Pick the round constants based on the number of full and partial rounds
-    if r_full == 2 && r_partial == 1 {
+    let rc_list = if r_full == 2 && r_partial == 1 {
         T4_P8_RC_R1_TEST.to_vec()
     } else {
         T4_P8_RC.to_vec()
-    }
+    };
+    poseidon::check_rounds(rc_list.len(), r_full, r_partial)?;
+    Ok(rc_list)
 }
 
-pub fn poseidon_p8_rf2_rp1(inputs: [&FheUint8; constants::POSEIDON_T]) -> FheUint8 {
+pub fn poseidon_p8_rf2_rp1(inputs: [&FheUint8; constants::POSEIDON_T]) -> Result<FheUint8> {
     poseidon_p8_impl(inputs, 2, 1)
 }
 
-pub fn poseidon_p8(inputs: [&FheUint8; constants::POSEIDON_T]) -> FheUint8 {
+pub fn poseidon_p8(inputs: [&FheUint8; constants::POSEIDON_T]) -> Result<FheUint8> {
     poseidon_p8_impl(
         inputs,
         constants::POSEIDON_R_FULL,
@@ -78,11 +196,8 @@ pub fn poseidon_p8_impl(
     inputs: [&FheUint8; constants::POSEIDON_T],
     r_full: usize,
     r_partial: usize,
-) -> FheUint8 {
-    let rc_list = pick_round_constants_p8(r_full, r_partial);
-    assert_eq!(rc_list.len(), (r_full + r_partial) * constants::POSEIDON_T);
-    // Check r_full is even
-    assert_eq!(r_full % 2, 0);
+) -> Result<FheUint8> {
+    let rc_list = pick_round_constants_p8(r_full, r_partial)?;
 
     // Apply mod prime to all inputs (refs in, values out)
     // Note that we dont yet need to move up bit-width since inputs must fit in
@@ -99,61 +214,236 @@ pub fn poseidon_p8_impl(
     //   (3 + 3) % 2^2 % 3 = 2, but (3 % 3 + 3 % 3) % 3 = 0
     // - (a * b) % p != (a % p * b % p) % p since the product can overflow too
     // To handle overflow, we will use a larger type for the intermediate operations
-    // and then cast down the results before returning.
+    // and then cast down the results before returning; `Modulus::new` checks
+    // that the 16-bit type is large enough for the prime (see `modint.rs`).
     // Now convert the inputs to FheUint16.
-    let p8_16 = constants::POSEIDON_P_8 as u16;
-    let mut inputs_16: Vec<FheUint16> = inputs.iter().map(|x| x.clone().cast_into()).collect();
-    let mut rc_counter: usize = 0;
+    let modulus = Modulus::new(constants::POSEIDON_P_8 as u16)?;
+    let state: Vec<Lane<FheUint16>> = inputs
+        .into_iter()
+        .map(|x| Lane::Enc(x.cast_into()))
+        .collect();
+    Ok(lane_to_fhe_u8(poseidon_p8_lanes(state, &rc_list, r_full, r_partial, modulus)))
+}
 
-    // Full rounds: first half
-    for _i in 0..r_full / 2 {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            // inner &: addition of FheUint16 and u16; outer &: mod p8_16
-            let new_val = &(&inputs_16[j] + rc_list[rc_counter]) % p8_16;
-            rc_counter += 1;
+// Same as `poseidon_p8`, but clear inputs (e.g. zero padding) are kept in the
+// clear until they mix with the encrypted ones; see `poseidon::Lane`
+pub fn poseidon_p8_arith_mixed_rf2_rp1(
+    inputs: [MixedInput<u8, FheUint8>; constants::POSEIDON_T],
+) -> Result<FheUint8> {
+    poseidon_p8_arith_mixed_impl::<Tfhe>(inputs, 2, 1)
+}
 
-            // 2. Apply S-Box
-            let new_val = utils::fhe_modexp_16(&new_val, constants::POSEIDON_ALPHA as u16, p8_16);
-            inputs_16[j] = new_val;
-        }
-        // 3. Apply MDS matrix
-        inputs_16 = apply_mds_matrix_u16(inputs_16, &T4_P8_MDS_MATRIX, p8_16);
+pub fn poseidon_p8_arith_mixed(
+    inputs: [MixedInput<u8, FheUint8>; constants::POSEIDON_T],
+) -> Result<FheUint8> {
+    poseidon_p8_arith_mixed_impl::<Tfhe>(
+        inputs,
+        constants::POSEIDON_R_FULL,
+        constants::POSEIDON_R_PARTIAL,
+    )
+}
+
+// Generic over the backend (see `backend.rs`)
+pub fn poseidon_p8_arith_mixed_impl<B: Backend>(
+    inputs: [MixedInput<u8, B::U8>; constants::POSEIDON_T],
+    r_full: usize,
+    r_partial: usize,
+) -> Result<B::U8> {
+    let rc_list = pick_round_constants_p8(r_full, r_partial)?;
+
+    let p8 = constants::POSEIDON_P_8;
+    let modulus = Modulus::new(p8 as u16)?;
+    let state: Vec<Lane<B::U16>> = inputs
+        .iter()
+        .map(|x| match x {
+            MixedInput::Clear(x) => Lane::Clear((x % p8) as u64),
+            MixedInput::Enc(x) => {
+                let x: B::U8 = x.clone() % p8;
+                Lane::Enc(x.cast_into())
+            }
+        })
+        .collect();
+    Ok(lane_to_fhe_u8(poseidon_p8_lanes(state, &rc_list, r_full, r_partial, modulus)))
+}
+
+// The Poseidon permutation on (mod p) lanes; returns the second element
+fn poseidon_p8_lanes<E: FheUint<u16>>(
+    mut state: Vec<Lane<E>>,
+    rc_list: &[u16],
+    r_full: usize,
+    r_partial: usize,
+    modulus: Modulus<u16>,
+) -> Lane<E> {
+    let t = constants::POSEIDON_T;
+
+    // Full rounds (first half), partial rounds, full rounds (second half)
+    for r in 0..r_full + r_partial {
+        let is_full_round = r < r_full / 2 || r >= r_full / 2 + r_partial;
+        let rc = &rc_list[r * t..(r + 1) * t];
+        let p8 = modulus.value() as u64;
+        state = poseidon::traced_round(
+            r,
+            is_full_round,
+            state,
+            |s| poseidon::poseidon_round_clear(s, rc, is_full_round, &T4_P8_MDS_MATRIX, p8),
+            |lanes: Vec<Lane<CountingUint16>>| poseidon_p8_round(lanes, rc, is_full_round, modulus),
+            |state| poseidon_p8_round(state, rc, is_full_round, modulus),
+        );
     }
 
-    // Partial rounds
-    for _i in 0..r_partial {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            let new_val = &(&inputs_16[j] + rc_list[rc_counter]) % p8_16;
-            inputs_16[j] = new_val;
-            rc_counter += 1;
-        }
-        // 2. Apply S-Box
-        inputs_16[0] = utils::fhe_modexp_16(&inputs_16[0], constants::POSEIDON_ALPHA as u16, p8_16);
-        // 3. Apply MDS matrix
-        inputs_16 = apply_mds_matrix_u16(inputs_16, &T4_P8_MDS_MATRIX, p8_16);
+    // Return the second element
+    state.swap_remove(1)
+}
+
+// One round of `poseidon_p8_lanes` with the round constants `rc`
+fn poseidon_p8_round<E: FheUint<u16>>(
+    state: Vec<Lane<E>>,
+    rc: &[u16],
+    is_full_round: bool,
+    modulus: Modulus<u16>,
+) -> Vec<Lane<E>> {
+    // 1. Add round constants
+    // 2. Apply S-Box (partial rounds: only on the first element)
+    let state = parallel::map(state.into_iter().enumerate().collect(), |(j, lane)| {
+        add_rc_sbox_lane_u16(&lane, rc[j], is_full_round || j == 0, modulus)
+    });
+    // 3. Apply MDS matrix
+    apply_mds_matrix_lanes_u16(state, &T4_P8_MDS_MATRIX, modulus)
+}
+
+fn lane_to_fhe_u8<E8: FheUint<u8>, E: CastInto<E8>>(lane: Lane<E>) -> E8 {
+    match lane {
+        Lane::Clear(x) => E8::encrypt_trivial(x as u8),
+        Lane::Enc(x) => x.cast_into(),
     }
+}
 
-    // Full rounds: second half
-    for _i in 0..r_full / 2 {
-        for j in 0..inputs.len() {
-            // 1. Add round constants
-            // inner &: addition of FheUint16 and u16; outer &: mod p8_16
-            let new_val = &(&inputs_16[j] + rc_list[rc_counter]) % p8_16;
-            rc_counter += 1;
+// Same as `lane_to_fhe_u8` for the 8-bit lanes of the LUT variant
+fn lut_lane_to_fhe_u8<E: FheUint<u8>>(lane: Lane<E>) -> E {
+    match lane {
+        Lane::Clear(x) => E::encrypt_trivial(x as u8),
+        Lane::Enc(x) => x,
+    }
+}
 
-            // 2. Apply S-Box
-            let new_val = utils::fhe_modexp_16(&new_val, constants::POSEIDON_ALPHA as u16, p8_16);
-            inputs_16[j] = new_val;
-        }
-        // 3. Apply MDS matrix
-        inputs_16 = apply_mds_matrix_u16(inputs_16, &T4_P8_MDS_MATRIX, p8_16);
+/*
+ * LUT variant of `poseidon_p8_impl` for small primes (see `lut.rs`).
+ *
+ * Since every state element is (mod p) with p < 2^8, each univariate step is a
+ * single PBS lookup on an FheUint8 instead of FHE mults and `%` on FheUint16:
+ * - round-constant add + S-box: x -> (x + rc)^alpha (mod p)
+ * - partial-round lanes without S-box: x -> (x + rc) (mod p)
+ * - MDS matrix: see `lut::fhe_lut_apply_matrix_8`
+ */
+
+pub fn poseidon_p8_lut_rf2_rp1(inputs: [&FheUint8; constants::POSEIDON_T]) -> Result<FheUint8> {
+    poseidon_p8_lut_impl(inputs, 2, 1)
+}
+
+pub fn poseidon_p8_lut(inputs: [&FheUint8; constants::POSEIDON_T]) -> Result<FheUint8> {
+    poseidon_p8_lut_impl(
+        inputs,
+        constants::POSEIDON_R_FULL,
+        constants::POSEIDON_R_PARTIAL,
+    )
+}
+
+pub fn poseidon_p8_lut_impl(
+    inputs: [&FheUint8; constants::POSEIDON_T],
+    r_full: usize,
+    r_partial: usize,
+) -> Result<FheUint8> {
+    let rc_list = pick_round_constants_p8(r_full, r_partial)?;
+
+    let p8 = constants::POSEIDON_P_8;
+    let state: Vec<Lane<FheUint8>> = inputs
+        .iter()
+        .map(|x| Lane::Enc(lut::fhe_lut_mod_8(*x, p8)))
+        .collect();
+    Ok(lut_lane_to_fhe_u8(poseidon_p8_lut_lanes::<Tfhe>(state, &rc_list, r_full, r_partial)))
+}
+
+/*
+ * Mixed clear/encrypted variant of `poseidon_p8_lut_impl`, e.g. for
+ * H(m, FHE(x_sch)) with a public message m.
+ *
+ * Clear inputs are tracked as clear lanes (see `poseidon::Lane`): their
+ * first-round constant add and S-box skip the lookup, and in the first MDS
+ * multiplication the clear terms of each row fold into a single constant that
+ * is added inside the reduction lookup. If all inputs are clear, the hash is
+ * computed in the clear and returned as a trivial encryption.
+ *
+ * Generic over the backend (see `backend.rs`).
+ */
+pub fn poseidon_p8_lut_mixed_impl<B: Backend>(
+    inputs: [MixedInput<u8, B::U8>; constants::POSEIDON_T],
+    r_full: usize,
+    r_partial: usize,
+) -> Result<B::U8> {
+    let rc_list = pick_round_constants_p8(r_full, r_partial)?;
+
+    let p8 = constants::POSEIDON_P_8;
+    let state: Vec<Lane<B::U8>> = inputs
+        .iter()
+        .map(|x| match x {
+            MixedInput::Clear(x) => Lane::Clear((x % p8) as u64),
+            MixedInput::Enc(x) => Lane::Enc(lut::fhe_lut_mod_8(x, p8)),
+        })
+        .collect();
+    Ok(lut_lane_to_fhe_u8(poseidon_p8_lut_lanes::<B>(state, &rc_list, r_full, r_partial)))
+}
+
+// The LUT permutation on (mod p) lanes, where the first and last `r_full / 2`
+// rounds are full rounds; returns the second element
+fn poseidon_p8_lut_lanes<B: Backend>(
+    mut state: Vec<Lane<B::U8>>,
+    rc_list: &[u16],
+    r_full: usize,
+    r_partial: usize,
+) -> Lane<B::U8> {
+    let t = constants::POSEIDON_T;
+    for r in 0..r_full + r_partial {
+        let is_full_round = r < r_full / 2 || r >= r_full / 2 + r_partial;
+        let rc = &rc_list[r * t..(r + 1) * t];
+        let p8 = constants::POSEIDON_P_8 as u64;
+        state = poseidon::traced_round(
+            r,
+            is_full_round,
+            state,
+            |s| poseidon::poseidon_round_clear(s, rc, is_full_round, &T4_P8_MDS_MATRIX, p8),
+            |lanes| poseidon_p8_lut_round::<Counting>(lanes, rc, is_full_round),
+            |state| poseidon_p8_lut_round::<B>(state, rc, is_full_round),
+        );
     }
 
-    // Return the first element
-    let hash: FheUint8 = inputs_16[1].clone().cast_into();
-    hash
+    // Return the second element
+    state.swap_remove(1)
+}
+
+// One round of the LUT permutation with the round constants `rc` (e.g. to
+// benchmark a single round)
+pub fn poseidon_p8_lut_round<B: Backend>(
+    state: Vec<Lane<B::U8>>,
+    rc: &[u16],
+    is_full_round: bool,
+) -> Vec<Lane<B::U8>> {
+    let p8 = constants::POSEIDON_P_8;
+    let alpha = constants::POSEIDON_ALPHA;
+
+    // 1. + 2. Add round constants and apply S-Box in one lookup
+    // (partial rounds: S-Box only on the first element)
+    let state = parallel::map(state.into_iter().enumerate().collect(), |(j, lane)| {
+        let exp = if is_full_round || j == 0 { alpha } else { 1 };
+        let rc = rc[j] as u8;
+        match lane {
+            Lane::Clear(x) => {
+                Lane::Clear(utils::clear_modexp_64(x + rc as u64, exp as u64, p8 as u64))
+            }
+            Lane::Enc(x) => Lane::Enc(lut::fhe_lut_add_modexp_8(&x, rc, exp, p8)),
+        }
+    });
+    // 3. Apply MDS matrix
+    apply_mds_matrix_lanes_lut::<B>(state, &T4_P8_MDS_MATRIX, p8)
 }
 
 /*
@@ -168,11 +458,11 @@ pub fn poseidon_p8_impl(
  * can handle any bit-width.
  */
 
-pub fn poseidon_p8_clear_rf2_rp1(inputs: [u8; constants::POSEIDON_T]) -> u8 {
+pub fn poseidon_p8_clear_rf2_rp1(inputs: [u8; constants::POSEIDON_T]) -> Result<u8> {
     poseidon_p8_clear_impl(inputs, 2, 1)
 }
 
-pub fn poseidon_p8_clear(inputs: [u8; constants::POSEIDON_T]) -> u8 {
+pub fn poseidon_p8_clear(inputs: [u8; constants::POSEIDON_T]) -> Result<u8> {
     poseidon_p8_clear_impl(
         inputs,
         constants::POSEIDON_R_FULL,
@@ -184,10 +474,8 @@ pub fn poseidon_p8_clear_impl(
     inputs: [u8; constants::POSEIDON_T],
     r_full: usize,
     r_partial: usize,
-) -> u8 {
-    let rc_list = pick_round_constants_p8(r_full, r_partial);
-    assert_eq!(rc_list.len(), (r_full + r_partial) * constants::POSEIDON_T);
-    assert_eq!(r_full % 2, 0);
+) -> Result<u8> {
+    let rc_list = pick_round_constants_p8(r_full, r_partial)?;
 
     // Apply mod prime to all inputs (refs in, values out)
     // Note that we dont yet need to move up bit-width since inputs must fit in
@@ -251,8 +539,66 @@ pub fn poseidon_p8_clear_impl(
     }
 
     // Return the first element
-    let hash: u8 = inputs[1].to_u8().expect("Failed to convert to u8");
-    hash
+    inputs[1].to_u8().ok_or(SchnorrFheError::WidthOverflow {
+        what: "Poseidon hash",
+        bits: u8::BITS,
+    })
+}
+
+// Poseidon over the 8-bit field, evaluated with lookup tables under FHE
+pub struct PoseidonP8;
+
+impl ClearHash for PoseidonP8 {
+    type Clear = u8;
+    const NAME: &'static str = "poseidon_p8";
+    const ARITY: usize = constants::POSEIDON_T;
+
+    fn hash_clear(inputs: &[u8]) -> Result<u8> {
+        poseidon_p8_clear(hash::pad_clear(inputs)?)
+    }
+}
+
+impl FheHash for PoseidonP8 {
+    type Enc = FheUint8;
+
+    fn hash_enc(inputs: &[FheUint8]) -> Result<FheUint8> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        poseidon_p8_lut(std::array::from_fn(|i| &padded[i]))
+    }
+
+    fn hash_mixed(inputs: &[MixedInput<u8, FheUint8>]) -> Result<FheUint8> {
+        poseidon_p8_lut_mixed_impl::<Tfhe>(
+            hash::pad_mixed(inputs)?,
+            constants::POSEIDON_R_FULL,
+            constants::POSEIDON_R_PARTIAL,
+        )
+    }
+}
+
+// Same as `PoseidonP8` but with r_full = 2, r_partial = 1 (for fast testing)
+pub struct PoseidonP8Rf2Rp1;
+
+impl ClearHash for PoseidonP8Rf2Rp1 {
+    type Clear = u8;
+    const NAME: &'static str = "poseidon_p8_rf2_rp1";
+    const ARITY: usize = constants::POSEIDON_T;
+
+    fn hash_clear(inputs: &[u8]) -> Result<u8> {
+        poseidon_p8_clear_rf2_rp1(hash::pad_clear(inputs)?)
+    }
+}
+
+impl FheHash for PoseidonP8Rf2Rp1 {
+    type Enc = FheUint8;
+
+    fn hash_enc(inputs: &[FheUint8]) -> Result<FheUint8> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        poseidon_p8_lut_rf2_rp1(std::array::from_fn(|i| &padded[i]))
+    }
+
+    fn hash_mixed(inputs: &[MixedInput<u8, FheUint8>]) -> Result<FheUint8> {
+        poseidon_p8_lut_mixed_impl::<Tfhe>(hash::pad_mixed(inputs)?, 2, 1)
+    }
 }
 
 ////////////////////////////// Poseidon constants //////////////////////////////
@@ -296,7 +642,7 @@ const T4_P8_RC: [u16; T4_P8_NUM_RC] = [
 
 // t x t MDS matrix; all values are mod POSEIDON_P_8
 // NOTE: we use u16 since modular multiplication can overflow u8
-const T4_P8_MDS_MATRIX: [[u16; constants::POSEIDON_T]; constants::POSEIDON_T] = [
+pub const T4_P8_MDS_MATRIX: [[u16; constants::POSEIDON_T]; constants::POSEIDON_T] = [
     [50, 40, 166, 57],
     [40, 166, 57, 25],
     [166, 57, 25, 177],
@@ -329,8 +675,8 @@ mod tests {
             FheUint16::encrypt(2u16, &client_key),
             FheUint16::encrypt(8u16, &client_key),
         ];
-        let output =
-            apply_mds_matrix_u16(inputs, &T4_P8_MDS_MATRIX, constants::POSEIDON_P_8 as u16);
+        let modulus = Modulus::new(constants::POSEIDON_P_8 as u16).unwrap();
+        let output = apply_mds_matrix_u16(inputs, &T4_P8_MDS_MATRIX, modulus);
         let output: Vec<u16> = output.iter().map(|x| x.decrypt(&client_key)).collect();
 
         // Print output
@@ -364,7 +710,7 @@ mod tests {
             &FheUint8::encrypt(2u8, &client_key),
             &FheUint8::encrypt(8u8, &client_key),
         ];
-        let output = poseidon_p8(inputs);
+        let output = poseidon_p8(inputs).unwrap();
         let output: u8 = output.decrypt(&client_key);
 
         // Expected output based on T4_P8_MDS_MATRIX @ [1, 0, 2, 8]
@@ -385,7 +731,7 @@ mod tests {
     fn test_poseidon_p8_clear_full() {
         // Inputs
         let inputs: [u8; constants::POSEIDON_T] = [1u8, 0u8, 2u8, 8u8];
-        let output = poseidon_p8_clear(inputs);
+        let output = poseidon_p8_clear(inputs).unwrap();
         // see `test_poseidon_p8_full` above for expected result
         let expected: u8 = 42;
         assert_eq!(output, expected);
@@ -405,7 +751,7 @@ mod tests {
             &FheUint8::encrypt(8u8, &client_key),
         ];
         // let output = poseidon_p8(inputs.iter().collect(), 2, 1);
-        let output = poseidon_p8_rf2_rp1(inputs);
+        let output = poseidon_p8_rf2_rp1(inputs).unwrap();
         let output: u8 = output.decrypt(&client_key);
 
         // Expected output based on T4_P8_MDS_MATRIX @ [1, 0, 2, 8]
@@ -421,11 +767,54 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    // Test the LUT variant against the clear reference (rf2_rp1 and full rounds)
+    #[test]
+    fn test_poseidon_p8_lut() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let clear: [u8; constants::POSEIDON_T] = [1u8, 0u8, 2u8, 8u8];
+        let inputs: [&FheUint8; constants::POSEIDON_T] = [
+            &FheUint8::encrypt(clear[0], &client_key),
+            &FheUint8::encrypt(clear[1], &client_key),
+            &FheUint8::encrypt(clear[2], &client_key),
+            &FheUint8::encrypt(clear[3], &client_key),
+        ];
+        let output: u8 = poseidon_p8_lut_rf2_rp1(inputs).unwrap().decrypt(&client_key);
+        assert_eq!(output, 13);
+        let output: u8 = poseidon_p8_lut(inputs).unwrap().decrypt(&client_key);
+        assert_eq!(output, poseidon_p8_clear(clear).unwrap());
+    }
+
+    // Every clear/encrypted split of the inputs must match the clear hash
+    #[test]
+    fn test_poseidon_p8_mixed() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let clear: [u8; constants::POSEIDON_T] = [1u8, 0u8, 2u8, 8u8];
+        for mask in 0..(1 << constants::POSEIDON_T) {
+            let inputs: [MixedInput<u8, FheUint8>; constants::POSEIDON_T] =
+                std::array::from_fn(|i| match (mask >> i) & 1 {
+                    0 => MixedInput::Clear(clear[i]),
+                    _ => MixedInput::Enc(FheUint8::encrypt(clear[i], &client_key)),
+                });
+            let output: u8 = poseidon_p8_lut_mixed_impl::<Tfhe>(inputs.clone(), 2, 1)
+                .unwrap()
+                .decrypt(&client_key);
+            assert_eq!(output, 13, "mask = {mask:04b}");
+            let output: u8 = poseidon_p8_arith_mixed_rf2_rp1(inputs)
+                .unwrap()
+                .decrypt(&client_key);
+            assert_eq!(output, 13, "mask = {mask:04b}");
+        }
+    }
+
     #[test]
     fn test_poseidon_p8_clear_rf2_rp1() {
         // Inputs
         let inputs: [u8; constants::POSEIDON_T] = [1u8, 0u8, 2u8, 8u8];
-        let output = poseidon_p8_clear_rf2_rp1(inputs);
+        let output = poseidon_p8_clear_rf2_rp1(inputs).unwrap();
         // see `test_poseidon_p8_rf2_rp1` above for expected result
         let expected: u8 = 13;
         assert_eq!(output, expected);
@@ -0,0 +1,167 @@
+use tfhe::shortint::parameters::parameters_wopbs_message_carry::{
+    WOPBS_PARAM_MESSAGE_1_CARRY_1_KS_PBS, WOPBS_PARAM_MESSAGE_2_CARRY_2_KS_PBS,
+};
+use tfhe::shortint::parameters::{
+    PBSParameters, PARAM_MESSAGE_1_CARRY_1_KS_PBS, PARAM_MESSAGE_2_CARRY_2_KS_PBS,
+};
+use tfhe::shortint::WopbsParameters;
+use tfhe::{Config, ConfigBuilder};
+
+use crate::error::{Result, SchnorrFheError};
+use crate::keystore::ParamSet;
+
+/*
+ * TFHE parameter profiles, instead of always `ConfigBuilder::default()`.
+ *
+ * A radix `FheUintN` is a vector of shortint blocks of `message_bits` each
+ * (with `carry_bits` of headroom), so the profile trades the number of blocks
+ * against the cost of one PBS: FheUint8 is 8 blocks with 1-bit messages and
+ * 4 with the default 2-bit ones. `cargo bench --bench fhe -- profiles/`
+ * compares the signing latency of every built-in profile.
+ *
+ * The circuits evaluate lookup tables (see `lut.rs`), so the keys need tfhe's
+ * function evaluation (WoPBS). tfhe 0.6 only has it for classic KS-PBS with
+ * 1- or 2-bit blocks, so these are the built-in profiles: tfhe's own shortint
+ * parameter sets, with tfhe's default failure probability. Wider blocks,
+ * multi-bit PBS or PBS-KS would need the circuits without lookup tables. For
+ * another failure probability, tune a `PBSParameters` and matching
+ * `WopbsParameters` and use `FheProfile::Custom`.
+ */
+
+// A tfhe parameter set with function evaluation
+// Profiles are picked once per key generation, so the size of `Custom` does
+// not matter, and boxing it would lose `Copy`
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FheProfile {
+    // Classic KS-PBS with 2-bit messages and carries (the default)
+    M2c2,
+    // Classic KS-PBS with 1-bit messages and carries
+    M1c1,
+    // Hand-tuned parameters, e.g. for another failure probability, with
+    // matching function evaluation parameters
+    Custom(PBSParameters, WopbsParameters),
+}
+
+// The profiles the circuits run with, the default first
+pub const BUILTIN_PROFILES: [FheProfile; 2] = [FheProfile::M2c2, FheProfile::M1c1];
+
+impl Default for FheProfile {
+    // What `ConfigBuilder::default()` uses
+    fn default() -> Self {
+        BUILTIN_PROFILES[0]
+    }
+}
+
+impl FheProfile {
+    // The built-in profile of a `name`
+    pub fn from_name(name: &str) -> Result<Self> {
+        BUILTIN_PROFILES
+            .into_iter()
+            .find(|profile| profile.name() == name)
+            .ok_or_else(|| SchnorrFheError::InvalidParams(format!("unknown FHE profile: {name}")))
+    }
+
+    // e.g. "m2c2", "m1c1"
+    pub fn name(&self) -> String {
+        match self {
+            FheProfile::M2c2 => "m2c2",
+            FheProfile::M1c1 => "m1c1",
+            FheProfile::Custom(..) => "custom",
+        }
+        .to_string()
+    }
+
+    pub fn params(&self) -> PBSParameters {
+        match *self {
+            FheProfile::M2c2 => PARAM_MESSAGE_2_CARRY_2_KS_PBS.into(),
+            FheProfile::M1c1 => PARAM_MESSAGE_1_CARRY_1_KS_PBS.into(),
+            FheProfile::Custom(params, _) => params,
+        }
+    }
+
+    // The function evaluation parameters, for the lookup tables of `lut.rs`
+    pub fn wopbs_params(&self) -> WopbsParameters {
+        match *self {
+            FheProfile::M2c2 => WOPBS_PARAM_MESSAGE_2_CARRY_2_KS_PBS,
+            FheProfile::M1c1 => WOPBS_PARAM_MESSAGE_1_CARRY_1_KS_PBS,
+            FheProfile::Custom(_, wopbs_params) => wopbs_params,
+        }
+    }
+
+    // With function evaluation
+    pub fn config(&self) -> Config {
+        ConfigBuilder::default()
+            .use_custom_parameters(self.params(), Some(self.wopbs_params()))
+            .build()
+    }
+
+    // The key file parameter set of keys generated with this profile
+    pub fn param_set(&self) -> ParamSet {
+        ParamSet::Tfhe(self.builtin_id().unwrap_or(ParamSet::TFHE_CUSTOM))
+    }
+
+    // The id of a built-in profile in the key files; fixed, so never reuse
+    // the id of a removed profile
+    fn builtin_id(&self) -> Option<u8> {
+        match self {
+            FheProfile::M2c2 => Some(0),
+            FheProfile::M1c1 => Some(1),
+            FheProfile::Custom(..) => None,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::prelude::*;
+    use tfhe::{generate_keys, set_server_key, FheUint8};
+
+    #[test]
+    fn test_profiles() {
+        for profile in BUILTIN_PROFILES {
+            assert_eq!(FheProfile::from_name(&profile.name()).unwrap(), profile);
+        }
+        // The key file ids do not depend on the order of BUILTIN_PROFILES
+        let m1c1 = FheProfile::from_name("m1c1").unwrap();
+        assert_eq!(FheProfile::default().param_set(), ParamSet::Tfhe(0));
+        assert_eq!(m1c1.param_set(), ParamSet::Tfhe(1));
+        assert_eq!(FheProfile::default().name(), "m2c2");
+        assert_eq!(
+            FheProfile::default().params(),
+            PARAM_MESSAGE_2_CARRY_2_KS_PBS.into()
+        );
+        // No function evaluation for these
+        for name in ["m3c3", "m5c5", "m2c2_small", "m2c2_multibit2"] {
+            assert!(matches!(
+                FheProfile::from_name(name),
+                Err(SchnorrFheError::InvalidParams(_))
+            ));
+        }
+
+        // Custom parameters, even equal to a built-in set, are their own set
+        let custom = FheProfile::Custom(
+            PARAM_MESSAGE_1_CARRY_1_KS_PBS.into(),
+            WOPBS_PARAM_MESSAGE_1_CARRY_1_KS_PBS,
+        );
+        assert_eq!(custom.name(), "custom");
+        assert_eq!(custom.param_set(), ParamSet::Tfhe(ParamSet::TFHE_CUSTOM));
+    }
+
+    #[test]
+    fn test_profile_keys() {
+        // Narrower blocks than the default, with a lookup table
+        let profile = FheProfile::from_name("m1c1").unwrap();
+        let (client_key, server_key) = generate_keys(profile.config());
+        set_server_key(server_key);
+        let a = FheUint8::encrypt(100u8, &client_key);
+        let b = FheUint8::encrypt(55u8, &client_key);
+        let c: u8 = (a + b).map(|v| v % 100).decrypt(&client_key);
+        assert_eq!(c, 55);
+    }
+}
@@ -0,0 +1,154 @@
+use tfhe::FheUint8;
+
+use crate::backend::Tfhe;
+use crate::constants;
+use crate::error::Result;
+use crate::hash::{self, ClearHash, FheHash};
+use crate::lut;
+use crate::utils;
+
+/*
+ * Rescue-Prime over the 8-bit dev field (p = `POSEIDON_P_8`, t = 4).
+ *
+ * Each round has two half-rounds: S-box x -> x^alpha on every element, MDS,
+ * add constants; then the inverse S-box x -> x^(1/alpha), MDS, add constants.
+ * Unlike Poseidon there are no partial rounds, so far fewer rounds are needed.
+ *
+ * Under FHE, x^(1/alpha) is a high-degree power (x^119 for p = 199), which is
+ * exactly where lookup tables shine: every S-box is one PBS regardless of the
+ * exponent (see `lut.rs`). Round constants are fused into the lookup of the
+ * following S-box.
+ *
+ * Same convention as `poseidon_p8`: the inputs are the initial state and the
+ * output is state element 1 after the permutation.
+ * Constants and test vectors: `scripts/arith_hash_gen.py`.
+ */
+
+const T: usize = constants::POSEIDON_T;
+
+pub fn rescue_p8(inputs: [&FheUint8; T]) -> FheUint8 {
+    let p8 = constants::POSEIDON_P_8;
+    let mut state: Vec<FheUint8> = inputs.iter().map(|x| lut::fhe_lut_mod_8(*x, p8)).collect();
+    // Constants still to be added to each element, fused into the next lookup
+    let mut pending: [u8; T] = [0; T];
+
+    for r in 0..RESCUE_ROUNDS {
+        for (half, exp) in [(0, constants::POSEIDON_ALPHA), (1, RESCUE_ALPHA_INV)] {
+            // 1. (Add previous constants and) apply S-Box
+            for j in 0..T {
+                state[j] = lut::fhe_lut_add_modexp_8(&state[j], pending[j], exp, p8);
+            }
+            // 2. Apply MDS matrix
+            state = lut::fhe_lut_apply_matrix_8::<Tfhe, T>(state, &RESCUE_MDS_MATRIX, p8);
+            // 3. Add round constants (deferred)
+            let offset = (2 * r + half) * T;
+            for j in 0..T {
+                pending[j] = RESCUE_RC[offset + j] as u8;
+            }
+        }
+    }
+
+    // Add the last round constants to the output element only
+    lut::fhe_lut_add_modexp_8(&state[1], pending[1], 1, p8)
+}
+
+// Same as `rescue_p8` but in the clear
+pub fn rescue_p8_clear(inputs: [u8; T]) -> u8 {
+    let p = constants::POSEIDON_P_8 as u64;
+    let mut state: Vec<u64> = inputs.iter().map(|x| *x as u64 % p).collect();
+
+    for r in 0..RESCUE_ROUNDS {
+        for (half, exp) in [(0, constants::POSEIDON_ALPHA), (1, RESCUE_ALPHA_INV)] {
+            state = state
+                .iter()
+                .map(|x| utils::clear_modexp_64(*x, exp as u64, p))
+                .collect();
+            state = (0..T)
+                .map(|i| (0..T).map(|j| RESCUE_MDS_MATRIX[i][j] as u64 * state[j]).sum::<u64>() % p)
+                .collect();
+            let offset = (2 * r + half) * T;
+            for j in 0..T {
+                state[j] = (state[j] + RESCUE_RC[offset + j] as u64) % p;
+            }
+        }
+    }
+
+    state[1] as u8
+}
+
+pub struct RescueP8;
+
+impl ClearHash for RescueP8 {
+    type Clear = u8;
+    const NAME: &'static str = "rescue_p8";
+    const ARITY: usize = T;
+
+    fn hash_clear(inputs: &[u8]) -> Result<u8> {
+        Ok(rescue_p8_clear(hash::pad_clear(inputs)?))
+    }
+}
+
+impl FheHash for RescueP8 {
+    type Enc = FheUint8;
+
+    fn hash_enc(inputs: &[FheUint8]) -> Result<FheUint8> {
+        let padded = hash::pad_enc::<Self>(inputs)?;
+        Ok(rescue_p8(std::array::from_fn(|i| &padded[i])))
+    }
+}
+
+/////////////////////////////// Rescue constants ///////////////////////////////
+
+// NOTE: toy round number for the 8-bit dev field; no security claim
+pub const RESCUE_ROUNDS: usize = 4;
+
+// 1/alpha (mod p - 1), i.e. 5 * 119 = 1 (mod 198)
+pub const RESCUE_ALPHA_INV: u8 = 119;
+
+// Cauchy MDS matrix M[i][j] = 1 / (i + t + j) (mod p); this is the same
+// construction (and hence the same matrix) as the Poseidon reference.
+const RESCUE_MDS_MATRIX: [[u16; T]; T] = [
+    [50, 40, 166, 57],
+    [40, 166, 57, 25],
+    [166, 57, 25, 177],
+    [57, 25, 177, 20],
+];
+
+// 2 * t round constants per round; all values are mod POSEIDON_P_8
+const RESCUE_RC: [u16; 2 * T * RESCUE_ROUNDS] = [
+    131, 113, 21, 128, 175, 18, 7, 36, 187, 26, 38, 64, 196, 162, 90, 161, 117, 191, 90, 41, 41,
+    187, 97, 73, 105, 187, 105, 90, 165, 185, 32, 122,
+];
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::prelude::*;
+    use tfhe::set_server_key;
+
+    // Expected outputs from `python scripts/arith_hash_gen.py --p 199 --t 4`
+    #[test]
+    fn test_rescue_p8_clear() {
+        assert_eq!(rescue_p8_clear([1, 0, 2, 8]), 160);
+        assert_eq!(rescue_p8_clear([0xAA, 5, 0, 0]), 113);
+    }
+
+    #[test]
+    fn test_rescue_p8() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let inputs: [&FheUint8; T] = [
+            &FheUint8::encrypt(1u8, &client_key),
+            &FheUint8::encrypt(0u8, &client_key),
+            &FheUint8::encrypt(2u8, &client_key),
+            &FheUint8::encrypt(8u8, &client_key),
+        ];
+        let output: u8 = rescue_p8(inputs).decrypt(&client_key);
+        assert_eq!(output, 160);
+    }
+}
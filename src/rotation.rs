@@ -0,0 +1,96 @@
+use tfhe::prelude::*;
+use tfhe::{ClientKey, KeySwitchingKey, ServerKey};
+
+use crate::error::{Result, SchnorrFheError};
+
+/*
+ * Rotation of the signer's FHE keys without decrypting FHE(x_sch).
+ *
+ * 1. The signer generates new FHE keys (e.g. `utils::init_keys_with`) and
+ *    issues a `RotationKey`: a key-switching key from the old FHE secret to
+ *    the new one. It sends it to the coordinator with the new server key.
+ * 2. The coordinator switches its E_old(x_sch) to E_new(x_sch)
+ *    (`Coordinator::rotate`) and signs under the new keys from then on.
+ *
+ * The key-switching key encrypts the old FHE secret under the new one, so
+ * the coordinator learns neither secret, nor x_sch. x_sch itself is
+ * unchanged, and so is the Schnorr public key y_sch: signatures from before
+ * and after the rotation verify against the same y_sch.
+ *
+ * Once every stored ciphertext is switched, the signer can destroy the old
+ * client key; the coordinator drops the rotation key with the old ciphertexts.
+ */
+
+pub struct RotationKey {
+    ksk: KeySwitchingKey,
+}
+
+impl RotationKey {
+    // Issued by the signer, who holds both FHE key pairs; both must use the
+    // same tfhe parameters (e.g. the same `FheProfile`)
+    pub fn new(old: (&ClientKey, &ServerKey), new: (&ClientKey, &ServerKey)) -> Result<Self> {
+        let ksk = KeySwitchingKey::new(old, new).map_err(|_| {
+            SchnorrFheError::InvalidParams(
+                "cannot rotate between FHE keys of different parameters".to_string(),
+            )
+        })?;
+        Ok(RotationKey { ksk })
+    }
+
+    // E_new(x) from E_old(x)
+    pub fn switch<E>(&self, ct: &E) -> E
+    where
+        KeySwitchingKey: FheKeyswitch<E>,
+    {
+        self.ksk.keyswitch(ct)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::Coordinator;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+    use crate::schnorr::{self, MessageVisibility, NonceMode};
+    use crate::utils;
+    use tfhe::FheUint8;
+
+    #[test]
+    fn test_rotate_coordinator() {
+        let (old_client_key, old_server_key, _public_key) = utils::init_keys();
+        let (new_client_key, new_server_key, _public_key) = utils::init_keys();
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&old_client_key);
+        let rotation_key = RotationKey::new(
+            (&old_client_key, &old_server_key),
+            (&new_client_key, &new_server_key),
+        )
+        .unwrap();
+        let coordinator =
+            Coordinator::<PoseidonP8Rf2Rp1>::new(old_server_key, x_sch_enc, 2).unwrap();
+
+        // The same signatures before and after, under the new client key
+        let msg: &[u8] = b"rotate";
+        let sign = |coordinator: &Coordinator<PoseidonP8Rf2Rp1>| {
+            coordinator
+                .sign(MessageVisibility::Public(msg), NonceMode::Deterministic)
+                .unwrap()
+        };
+        let sig_old = sign(&coordinator).decrypt(&old_client_key);
+        let coordinator = coordinator.rotate(&rotation_key, new_server_key).unwrap();
+        let sig_new = sign(&coordinator).decrypt(&new_client_key);
+        assert_eq!(sig_old, sig_new);
+        assert_eq!(
+            sig_new,
+            schnorr::sign_clear::<PoseidonP8Rf2Rp1>(&x_sch, msg).unwrap()
+        );
+        assert_eq!(
+            schnorr::verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig_new),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use tfhe::prelude::*;
+use tfhe::{ClientKey, FheUint16, FheUint8};
+
+use crate::backend::{Backend, FheUint, Tfhe};
+use crate::constants;
+use crate::cost::{Counting, CountingUint16, CountingUint8};
+use crate::error::{Result, SchnorrFheError};
+use crate::hash::{ClearHash, FheHash, MixedInput};
+use crate::lut;
+use crate::message;
+use crate::modint::{ModInt, Modulus};
+use crate::nonce;
+use crate::oracle;
+use crate::signing_key::SigningKey;
+use crate::trace;
+use crate::utils;
+
+/*
+ * Schnorr signing under FHE, generic over the hash function.
+ *
+ * For now this is the 8-bit dev parameter set (`constants::SCH_P`, `SCH_G`,
+ * `SCH_Q`), with FheUint8 values and FheUint16 for the (mod q) arithmetic.
+ *
+ * Messages are arbitrary byte strings, encoded into field elements and hashed
+ * down to a digest FHE(d) = H*(encode(m)) once (see `message.rs`).
+ *
+ * The message can be kept private (`MessageVisibility::Encrypted`) or be
+ * public (`MessageVisibility::Public`), in which case only the key is
+ * encrypted: d is computed in the clear and the hashes below take mixed
+ * clear/encrypted inputs (see `FheHash::hash_mixed`), which is cheaper.
+ *
+ * Signing (coordinator, all in FHE):
+ *   FHE(k) = (FHE(h_1) * p + FHE(h_2)) (mod q - 1) + 1
+ *     with FHE(h_i) = H(FHE(d), FHE(x_sch), i, FHE(a)), unbiased (see `nonce.rs`)
+ *   FHE(r) = g^FHE(k) (mod p)
+ *   FHE(h) = H(FHE(d), FHE(r)) (mod q)
+ *   FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q)
+ * Verification (anyone, in the clear):
+ *   r_v = g^s * y_sch^h (mod p), h_v = H(d, r_v) (mod q), check h == h_v
+ *   where d = H*(encode(m)) is recomputed from the clear message
+ *
+ * The nonce is deterministic (a = 0) or hedged with fresh randomness FHE(a)
+ * that the signer sends with the request (`NonceMode`). Either way the
+ * signature verifies the same; `sign_clear_hedged` is the clear reference of
+ * a hedged signature.
+ *
+ * The signer's secret key is a `SigningKey` (see `signing_key.rs`), never a
+ * plain integer; `sign_clear` reads it as the clear reference.
+ *
+ * The signer must be instantiated with an `FheHash` and the verifier with the
+ * matching `ClearHash`; see the consistency guarantee in `hash.rs`.
+ *
+ * Bad keys, messages or hash parameters are returned as a `SchnorrFheError`
+ * (see `error.rs`), and `verify` returns `VerificationFailed` on a bad
+ * signature.
+ */
+
+// Encrypted signature (FHE(s), FHE(h)) produced by the coordinator; generic
+// over the backend of `sign_circuit` (see `backend.rs`)
+#[derive(Clone)]
+pub struct EncryptedSignature<E = FheUint8> {
+    pub s_enc: E,
+    pub h_enc: E,
+}
+
+// Clear signature (s, h) obtained by the signer after decryption
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub s: u8,
+    pub h: u8,
+}
+
+impl EncryptedSignature {
+    pub fn decrypt(&self, client_key: &ClientKey) -> Signature {
+        let _phase = crate::phase!("decrypt");
+        Signature {
+            s: self.s_enc.decrypt(client_key),
+            h: self.h_enc.decrypt(client_key),
+        }
+    }
+}
+
+// Generate a plaintext Schnorr key pair (x_sch, y_sch = g^x_sch (mod p))
+pub fn keygen() -> Result<(SigningKey, u8)> {
+    let x_sch = SigningKey::generate();
+    let y_sch = public_key(&x_sch)?;
+    Ok((x_sch, y_sch))
+}
+
+// y_sch = g^x_sch (mod p)
+pub fn public_key(x_sch: &SigningKey) -> Result<u8> {
+    let g_sch = BigUint::from(constants::SCH_G);
+    let p_sch = BigUint::from(constants::SCH_P);
+    let y_sch = g_sch.modpow(&BigUint::from(x_sch.expose_secret()), &p_sch);
+    to_u8(&y_sch, "y_sch")
+}
+
+fn to_u8(x: &BigUint, what: &'static str) -> Result<u8> {
+    x.to_u8().ok_or(SchnorrFheError::WidthOverflow {
+        what,
+        bits: u8::BITS,
+    })
+}
+
+// The message to sign, as seen by the coordinator
+#[derive(Clone, Copy)]
+pub enum MessageVisibility<'a> {
+    // Clear byte-string message; only the signing key is encrypted
+    Public(&'a [u8]),
+    // Encrypted encoding of the message (see `message::encrypt_message`)
+    Encrypted(&'a [FheUint8]),
+}
+
+// How the nonce of a signature is derived (see `nonce.rs`)
+pub enum NonceMode<'a, E = FheUint8> {
+    // From the message and the key only
+    Deterministic,
+    // Also from FHE(a), fresh randomness encrypted by the signer for this
+    // signature (see `nonce::aux_rand`)
+    Hedged(&'a E),
+}
+
+impl<E> Clone for NonceMode<'_, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for NonceMode<'_, E> {}
+
+impl<E: Clone> NonceMode<'_, E> {
+    // The last input a of the nonce hashes, zero for deterministic nonces
+    pub fn aux(&self) -> MixedInput<u8, E> {
+        match self {
+            NonceMode::Deterministic => MixedInput::Clear(0),
+            NonceMode::Hedged(aux_enc) => MixedInput::Enc((*aux_enc).clone()),
+        }
+    }
+}
+
+// Sign m with FHE(x_sch) in FHE (coordinator side)
+pub fn sign_enc<H>(
+    x_sch_enc: &FheUint8,
+    msg: MessageVisibility,
+    nonce_mode: NonceMode,
+) -> Result<EncryptedSignature>
+where
+    H: FheHash<Enc = FheUint8, Clear = u8>,
+{
+    let x_sch_enc_up: FheUint16 = x_sch_enc.clone().cast_into();
+    sign_enc_with_key_up::<H>(x_sch_enc, &x_sch_enc_up, msg, nonce_mode)
+}
+
+// Same as `sign_enc`, with FHE(x_sch) already cast up to FheUint16 so it can
+// be reused across messages (see `coordinator.rs`)
+pub fn sign_enc_with_key_up<H>(
+    x_sch_enc: &FheUint8,
+    x_sch_enc_up: &FheUint16,
+    msg: MessageVisibility,
+    nonce_mode: NonceMode,
+) -> Result<EncryptedSignature>
+where
+    H: FheHash<Enc = FheUint8, Clear = u8>,
+{
+    let _phase = crate::phase!("sign", hash = H::NAME);
+    // Message digest d = H*(encode(m)), in the clear if m is public
+    let d: MixedInput<u8, FheUint8> = match msg {
+        MessageVisibility::Public(msg) => MixedInput::Clear(message::hash_message_clear::<H>(
+            &message::encode_message(msg)?,
+        )?),
+        MessageVisibility::Encrypted(msg_enc) => {
+            MixedInput::Enc(message::hash_message_enc::<Tfhe>(msg_enc, H::ARITY, H::hash_mixed)?)
+        }
+    };
+    sign_circuit::<Tfhe>(x_sch_enc, x_sch_enc_up, d, nonce_mode, H::hash_mixed)
+}
+
+// The signing circuit from the digest d on, generic over the backend (see
+// `backend.rs`) and the hash, e.g. to count its FHE ops (see `cost.rs`); each
+// step is traced and checked by the debug oracle, if any (see `trace.rs`,
+// `oracle.rs`)
+pub fn sign_circuit<B: Backend>(
+    x_sch_enc: &B::U8,
+    x_sch_enc_up: &B::U16,
+    d: MixedInput<u8, B::U8>,
+    nonce_mode: NonceMode<B::U8>,
+    hash: impl Fn(&[MixedInput<u8, B::U8>]) -> Result<B::U8>,
+) -> Result<EncryptedSignature<B::U8>> {
+    let (k_enc, k_enc_up) = nonce_step::<B>(&d, x_sch_enc, &nonce_mode.aux(), &hash)?;
+    let r_enc = modexp_step::<B>(&k_enc);
+    let h_enc = challenge_step::<B>(d, r_enc, &hash)?;
+    let s_enc = s_step::<B>(k_enc_up, x_sch_enc_up, &h_enc)?;
+    Ok(EncryptedSignature { s_enc, h_enc })
+}
+
+// Pseudo-random nonce FHE(k) in [1, q - 1], from two hash outputs
+// H(d, FHE(x_sch), i, a) (see `nonce.rs`)
+fn nonce_step<B: Backend>(
+    d: &MixedInput<u8, B::U8>,
+    x_sch_enc: &B::U8,
+    aux: &MixedInput<u8, B::U8>,
+    hash: impl Fn(&[MixedInput<u8, B::U8>]) -> Result<B::U8>,
+) -> Result<(B::U8, B::U16)> {
+    let phase = crate::phase!("nonce_hash");
+    let (p_hash, q_sch) = (constants::POSEIDON_P_8 as u16, constants::SCH_Q as u16);
+    let h_enc: Vec<B::U8> = nonce::hash_nonce(d, x_sch_enc, aux, hash)?;
+    let expected = oracle::reveal_all(&h_enc)
+        .map(|h| nonce::reduce_wide_clear(&h, p_hash as u64, q_sch as u64))
+        .transpose()?;
+    trace::traced_step(
+        phase,
+        || {
+            let h = vec![CountingUint8; h_enc.len()];
+            nonce::reduce_wide_16::<Counting>(&h, p_hash, q_sch)
+        },
+        || nonce::reduce_wide_16::<B>(&h_enc, p_hash, q_sch),
+        |k| {
+            if let Ok((k_enc, _)) = k {
+                oracle::check("nonce_hash", None, k_enc, expected);
+            }
+        },
+    )
+}
+
+// FHE(r) = g^FHE(k) (mod p), as a single lookup since g is public
+fn modexp_step<B: Backend>(k_enc: &B::U8) -> B::U8 {
+    let expected = oracle::reveal(k_enc).map(|k| {
+        utils::clear_modexp_64(constants::SCH_G as u64, k as u64, constants::SCH_P as u64)
+    });
+    trace::traced_step(
+        crate::phase!("modexp"),
+        || fixed_base_modexp(&CountingUint8),
+        || fixed_base_modexp(k_enc),
+        |r_enc| oracle::check("modexp", None, r_enc, expected),
+    )
+}
+
+// FHE(h) = H(d, FHE(r)) (mod q)
+fn challenge_step<B: Backend>(
+    d: MixedInput<u8, B::U8>,
+    r_enc: B::U8,
+    hash: impl Fn(&[MixedInput<u8, B::U8>]) -> Result<B::U8>,
+) -> Result<B::U8> {
+    let phase = crate::phase!("challenge_hash");
+    let h_enc: B::U8 = hash(&[d, MixedInput::Enc(r_enc)])?;
+    let expected = oracle::reveal(&h_enc).map(|h| (h % constants::SCH_Q) as u64);
+    Ok(trace::traced_step(
+        phase,
+        || challenge_from_hash(CountingUint8),
+        || challenge_from_hash(h_enc),
+        |h_enc| oracle::check("challenge_hash", None, h_enc, expected),
+    ))
+}
+
+// FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q)
+fn s_step<B: Backend>(k_enc_up: B::U16, x_sch_enc_up: &B::U16, h_enc: &B::U8) -> Result<B::U8> {
+    let q_sch = Modulus::new(constants::SCH_Q as u16)?;
+    let inputs = (
+        oracle::reveal(&k_enc_up),
+        oracle::reveal(x_sch_enc_up),
+        oracle::reveal(h_enc),
+    );
+    let expected = match inputs {
+        (Some(k), Some(x_sch), Some(h)) => {
+            let q_sch = constants::SCH_Q as u64;
+            Some((k as u64 + q_sch - (x_sch as u64 * h as u64) % q_sch) % q_sch)
+        }
+        _ => None,
+    };
+    Ok(trace::traced_step(
+        crate::phase!("s"),
+        || s_from_nonce::<Counting>(CountingUint16, &CountingUint16, &CountingUint8, q_sch),
+        || s_from_nonce::<B>(k_enc_up, x_sch_enc_up, h_enc, q_sch),
+        |s_enc| oracle::check("s", None, s_enc, expected),
+    ))
+}
+
+fn fixed_base_modexp<E: FheUint<u8>>(k_enc: &E) -> E {
+    lut::fhe_lut_fixed_base_modexp_8(k_enc, constants::SCH_G, constants::SCH_P)
+}
+
+fn challenge_from_hash<E: FheUint<u8>>(h_enc: E) -> E {
+    h_enc % constants::SCH_Q // NOTE: ensures h in [0, q-1]
+}
+
+// FHE(s) = (FHE(k) - FHE(x_sch) * FHE(h)) (mod q), in 2x bit width; k, x_sch
+// and h are already in [0, q - 1]
+fn s_from_nonce<B: Backend>(
+    k_enc_up: B::U16,
+    x_sch_enc_up: &B::U16,
+    h_enc: &B::U8,
+    q_sch: Modulus<u16>,
+) -> B::U8 {
+    let k_enc_up = ModInt::from_reduced(k_enc_up, q_sch);
+    let x_sch_enc_up = ModInt::from_reduced(x_sch_enc_up.clone(), q_sch);
+    let h_enc_up = ModInt::from_reduced(h_enc.clone().cast_into(), q_sch);
+    // `sub` adds q_sch to handle case if hx_enc_up > k_enc_up
+    let s_enc_up = k_enc_up.sub(&x_sch_enc_up.mul(&h_enc_up));
+    s_enc_up.into_inner().cast_into()
+}
+
+// Same as `sign_enc` with a deterministic nonce but in the clear (reference
+// for tests and debugging)
+pub fn sign_clear<H: ClearHash<Clear = u8>>(x_sch: &SigningKey, msg: &[u8]) -> Result<Signature> {
+    sign_clear_hedged::<H>(x_sch, msg, 0)
+}
+
+// Same as `sign_enc` with the nonce hedged with a, in the clear
+pub fn sign_clear_hedged<H: ClearHash<Clear = u8>>(
+    x_sch: &SigningKey,
+    msg: &[u8],
+    aux: u8,
+) -> Result<Signature> {
+    let x_sch = x_sch.expose_secret();
+    let q_sch = constants::SCH_Q as u16;
+    let d = message::hash_message_clear::<H>(&message::encode_message(msg)?)?;
+    let k = nonce::derive_nonce_clear::<H>(d, x_sch, aux)?;
+    let r =
+        BigUint::from(constants::SCH_G).modpow(&BigUint::from(k), &BigUint::from(constants::SCH_P));
+    let r = to_u8(&r, "r")?;
+    let h = H::hash_clear(&[d, r])? % constants::SCH_Q;
+    let hx = (x_sch as u16 * h as u16) % q_sch;
+    let s = ((k as u16 + q_sch - hx) % q_sch) as u8;
+    Ok(Signature { s, h })
+}
+
+// Verify (s, h) on m under y_sch, in the clear
+pub fn verify<H: ClearHash<Clear = u8>>(y_sch: u8, msg: &[u8], sig: &Signature) -> Result<()> {
+    let _phase = crate::phase!("verify");
+    let g_sch = BigUint::from(constants::SCH_G);
+    let p_sch = BigUint::from(constants::SCH_P);
+    // Compute r_v = g^s * y^h (mod p)
+    let r_v_left = g_sch.modpow(&BigUint::from(sig.s), &p_sch);
+    let r_v_right = BigUint::from(y_sch).modpow(&BigUint::from(sig.h), &p_sch);
+    let r_v = (&r_v_left * &r_v_right) % &p_sch;
+    let r_v = to_u8(&r_v, "r_v")?;
+    // Re-encode and hash the clear message: h_v = H(d, r_v) (mod q)
+    let d = message::hash_message_clear::<H>(&message::encode_message(msg)?)?;
+    let h_v = H::hash_clear(&[d, r_v])? % constants::SCH_Q;
+    match h_v == sig.h {
+        true => Ok(()),
+        false => Err(SchnorrFheError::VerificationFailed),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mimc::MimcP8;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+    use crate::utils;
+    use tfhe::set_server_key;
+
+    // NOTE: with the toy q = 11, a wrong s still verifies with probability
+    // ~1/q (the hash is reduced mod q), so we only check that most do not
+    #[test]
+    fn test_sign_clear_verify() {
+        for x_sch in 1..constants::SCH_Q {
+            let x_sch = SigningKey::new(x_sch).unwrap();
+            let y_sch = public_key(&x_sch).unwrap();
+            for aux in [1, 100, 198] {
+                let sig = sign_clear_hedged::<PoseidonP8Rf2Rp1>(&x_sch, b"msg", aux).unwrap();
+                assert_eq!(verify::<PoseidonP8Rf2Rp1>(y_sch, b"msg", &sig), Ok(()));
+            }
+            let sig = sign_clear::<PoseidonP8Rf2Rp1>(&x_sch, b"msg").unwrap();
+            assert_eq!(verify::<PoseidonP8Rf2Rp1>(y_sch, b"msg", &sig), Ok(()));
+            let n_bad_verified = (1..constants::SCH_Q)
+                .map(|d| Signature { s: (sig.s + d) % constants::SCH_Q, h: sig.h })
+                .filter(|bad| verify::<PoseidonP8Rf2Rp1>(y_sch, b"msg", bad).is_ok())
+                .count();
+            assert!(n_bad_verified < (constants::SCH_Q / 2) as usize);
+        }
+    }
+
+    // Sign under FHE with two different hashes and both message visibilities;
+    // each must match the clear reference and verify with its own clear hash
+    #[test]
+    fn test_sign_enc_verify() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        let (x_sch, y_sch) = keygen().unwrap();
+        let msg: &[u8] = b"an arbitrary byte-string message";
+        let x_sch_enc: FheUint8 = x_sch.encrypt(&client_key);
+        let msg_enc = message::encrypt_message(msg, &client_key).unwrap();
+
+        for visibility in [MessageVisibility::Encrypted(&msg_enc), MessageVisibility::Public(msg)] {
+            let deterministic = NonceMode::Deterministic;
+            let sig = sign_enc::<PoseidonP8Rf2Rp1>(&x_sch_enc, visibility, deterministic).unwrap();
+            let sig = sig.decrypt(&client_key);
+            assert_eq!(sig, sign_clear::<PoseidonP8Rf2Rp1>(&x_sch, msg).unwrap());
+            assert_eq!(verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig), Ok(()));
+
+            let sig = sign_enc::<MimcP8>(&x_sch_enc, visibility, deterministic).unwrap();
+            let sig = sig.decrypt(&client_key);
+            assert_eq!(sig, sign_clear::<MimcP8>(&x_sch, msg).unwrap());
+            assert_eq!(verify::<MimcP8>(y_sch, msg, &sig), Ok(()));
+
+            // Hedged with fresh randomness: matches the clear reference with
+            // the same a, and verifies like a deterministic signature
+            let aux = nonce::aux_rand();
+            let aux_enc = FheUint8::encrypt(aux, &client_key);
+            let hedged = NonceMode::Hedged(&aux_enc);
+            let sig = sign_enc::<PoseidonP8Rf2Rp1>(&x_sch_enc, visibility, hedged).unwrap();
+            let sig = sig.decrypt(&client_key);
+            let sig_clear = sign_clear_hedged::<PoseidonP8Rf2Rp1>(&x_sch, msg, aux).unwrap();
+            assert_eq!(sig, sig_clear);
+            assert_eq!(verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig), Ok(()));
+        }
+
+        // A truncated encrypted message is rejected instead of signed
+        let truncated = MessageVisibility::Encrypted(&msg_enc[..1]);
+        assert!(matches!(
+            sign_enc::<PoseidonP8Rf2Rp1>(&x_sch_enc, truncated, NonceMode::Deterministic),
+            Err(SchnorrFheError::MalformedCiphertext(_))
+        ));
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::path::Path;
+
+use rand::rngs::OsRng;
+use rand::Rng;
+use tfhe::prelude::*;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::keystore::{self, KeyKind, ParamSet};
+
+/*
+ * The signer's Schnorr secret key x_sch.
+ *
+ * `SigningKey` is the only way the protocol APIs take x_sch (`schnorr::
+ * sign_clear`, `sim::sign_sim`, ...), so the secret does not travel as a
+ * plain integer:
+ * - it is zeroized when dropped, and is not `Clone`
+ * - its `Debug` output is redacted, and it has no `Display`; log the public
+ *   key instead
+ * - the value is only read by `encrypt` (to FHE(x_sch) for the coordinator)
+ *   and by the clear reference code, through `expose_secret`
+ *
+ * At rest, a key is a `SigningKey` key file whose payload is sealed with a
+ * passphrase (`to_encrypted_bytes`, `save`; see `keystore.rs` for the format).
+ * A wrong passphrase, a tampered file or a key of another parameter set is a
+ * `SchnorrFheError::KeyStorage`.
+ *
+ * The key is generic over its width: u8 for the 8-bit dev set, u32 for the
+ * 32-bit set (see `KeyScalar`).
+ */
+
+// A secret key width, with the group order q of its parameter set
+pub trait KeyScalar: Copy + Zeroize + Into<u64> + TryFrom<u64> {
+    const Q: u64;
+    const BITS: u32;
+    const PARAMS: ParamSet;
+}
+
+impl KeyScalar for u8 {
+    const Q: u64 = constants::SCH_Q as u64;
+    const BITS: u32 = u8::BITS;
+    const PARAMS: ParamSet = ParamSet::Schnorr8;
+}
+
+impl KeyScalar for u32 {
+    const Q: u64 = constants::SCH_Q_16 as u64;
+    const BITS: u32 = u32::BITS;
+    const PARAMS: ParamSet = ParamSet::Schnorr32;
+}
+
+pub struct SigningKey<T: KeyScalar = u8> {
+    x_sch: T,
+}
+
+impl<T: KeyScalar> SigningKey<T> {
+    // x_sch must be in [1, q - 1]
+    pub fn new(x_sch: T) -> Result<Self> {
+        if !(1..T::Q).contains(&x_sch.into()) {
+            return Err(SchnorrFheError::InvalidParams(format!(
+                "signing key out of [1, {}]",
+                T::Q - 1
+            )));
+        }
+        Ok(SigningKey { x_sch })
+    }
+
+    // Uniformly random x_sch in [1, q - 1]
+    pub fn generate() -> Self {
+        let x_sch: u64 = OsRng.gen_range(1..T::Q);
+        match T::try_from(x_sch) {
+            Ok(x_sch) => SigningKey { x_sch },
+            Err(_) => unreachable!("q of a key width fits the width"),
+        }
+    }
+
+    // The secret value, for the clear reference code only
+    pub fn expose_secret(&self) -> T {
+        self.x_sch
+    }
+
+    // FHE(x_sch) under a client or public key
+    pub fn encrypt<E: FheEncrypt<T, K>, K>(&self, key: &K) -> E {
+        E::encrypt(self.x_sch, key)
+    }
+
+    // The key file of the key, sealed with `passphrase`
+    pub fn to_encrypted_bytes(&self, passphrase: &[u8]) -> Result<Vec<u8>> {
+        let secret = Zeroizing::new(self.x_sch.into().to_le_bytes());
+        let sealed = keystore::seal(passphrase, KeyKind::Signing, T::PARAMS, &secret[..])?;
+        Ok(keystore::encode(KeyKind::Signing, T::PARAMS, &sealed))
+    }
+
+    // Open a key file written by `to_encrypted_bytes`
+    pub fn from_encrypted_bytes(bytes: &[u8], passphrase: &[u8]) -> Result<Self> {
+        let sealed = keystore::decode(bytes, KeyKind::Signing, T::PARAMS)?;
+        let secret = keystore::open(passphrase, KeyKind::Signing, T::PARAMS, sealed)?;
+        let mut x_sch = Zeroizing::new([0u8; 8]);
+        if secret.len() != x_sch.len() {
+            return Err(SchnorrFheError::KeyStorage("bad key length".to_string()));
+        }
+        x_sch.copy_from_slice(&secret);
+        let x_sch = T::try_from(u64::from_le_bytes(*x_sch))
+            .map_err(|_| SchnorrFheError::KeyStorage("key too wide".to_string()))?;
+        Self::new(x_sch)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, passphrase: &[u8]) -> Result<()> {
+        keystore::write(path, &self.to_encrypted_bytes(passphrase)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>, passphrase: &[u8]) -> Result<Self> {
+        Self::from_encrypted_bytes(&keystore::read(path)?, passphrase)
+    }
+}
+
+impl<T: KeyScalar> Drop for SigningKey<T> {
+    fn drop(&mut self) {
+        self.x_sch.zeroize();
+    }
+}
+
+impl<T: KeyScalar> ZeroizeOnDrop for SigningKey<T> {}
+
+impl<T: KeyScalar> fmt::Debug for SigningKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SigningKey<u{}>(<redacted>)", T::BITS)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        for _ in 0..100 {
+            let x_sch = SigningKey::<u8>::generate().expose_secret();
+            assert!((1..constants::SCH_Q).contains(&x_sch));
+        }
+        assert!(SigningKey::new(0u8).is_err());
+        assert!(SigningKey::new(constants::SCH_Q).is_err());
+        assert!(SigningKey::new(constants::SCH_Q_16 - 1).is_ok());
+
+        // No leak through Debug
+        let key = SigningKey::new(7u8).unwrap();
+        assert_eq!(format!("{:?}", key), "SigningKey<u8>(<redacted>)");
+    }
+
+    #[test]
+    fn test_encrypted_at_rest() {
+        let key = SigningKey::new(7u8).unwrap();
+        let sealed = key.to_encrypted_bytes(b"correct horse").unwrap();
+        // Header, salt, nonce, the 8 key bytes, the tag and the checksum
+        assert_eq!(sealed.len(), 16 + 16 + 12 + 8 + 16 + 32);
+        let opened = SigningKey::<u8>::from_encrypted_bytes(&sealed, b"correct horse").unwrap();
+        assert_eq!(opened.expose_secret(), 7);
+
+        // Wrong passphrase, tampering, truncation and another parameter set fail
+        let wrong = SigningKey::<u8>::from_encrypted_bytes(&sealed, b"battery staple");
+        assert!(matches!(wrong, Err(SchnorrFheError::KeyStorage(_))));
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(SigningKey::<u8>::from_encrypted_bytes(&tampered, b"correct horse").is_err());
+        assert!(SigningKey::<u8>::from_encrypted_bytes(&sealed[..20], b"correct horse").is_err());
+        assert!(SigningKey::<u32>::from_encrypted_bytes(&sealed, b"correct horse").is_err());
+
+        // Through a file
+        let path = std::env::temp_dir().join("schnorr_fhe_test_signing_key.bin");
+        let key = SigningKey::new(40000u32).unwrap();
+        key.save(&path, b"pass").unwrap();
+        let loaded = SigningKey::<u32>::load(&path, b"pass").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.expose_secret(), 40000);
+    }
+}
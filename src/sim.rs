@@ -0,0 +1,366 @@
+use std::ops::{Add, BitAnd, Mul, Rem, Shr, ShrAssign, Sub};
+
+use tfhe::prelude::*;
+use tfhe::ClientKey;
+
+use crate::backend::{Backend, FheUint};
+use crate::constants;
+use crate::error::Result;
+use crate::hash::{self, MixedInput};
+use crate::message;
+use crate::poseidon_p8;
+use crate::schnorr::{self, NonceMode, Signature};
+use crate::signing_key::SigningKey;
+
+/*
+ * Plaintext simulation of the FHE circuits.
+ *
+ * `Sim` is a backend (see `backend.rs`) whose "ciphertexts" are the clear
+ * values, with the same semantics as tfhe's `FheUint*`: arithmetic wraps
+ * around at the bit width, `>>` takes the shift modulo the bit width, casts
+ * truncate or zero-extend, and `map` truncates the table output. Running a
+ * generic circuit on `Sim` exercises the exact FHE code path (casts, lanes,
+ * lookup tables, ...) in milliseconds and without keys, e.g. the whole
+ * signing pipeline (`sign_sim`).
+ *
+ * The tests below check `Sim` against the clear references; the slow tier
+ * (`cargo test -- --ignored`) checks it against real ciphertexts.
+ */
+
+// Simulated encrypted boolean
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimBool(pub bool);
+
+macro_rules! sim_uint {
+    ($name:ident, $clear:ty) => {
+        // Simulated FheUint of the same width, holding the clear value
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub struct $name(pub $clear);
+
+        impl FheTrivialEncrypt<$clear> for $name {
+            fn encrypt_trivial(value: $clear) -> Self {
+                $name(value)
+            }
+        }
+
+        sim_uint!(@binary $name, $clear, Add, add, wrapping_add);
+        sim_uint!(@binary $name, $clear, Sub, sub, wrapping_sub);
+        sim_uint!(@binary $name, $clear, Mul, mul, wrapping_mul);
+
+        impl Rem<$clear> for $name {
+            type Output = $name;
+            fn rem(self, rhs: $clear) -> $name {
+                $name(self.0 % rhs)
+            }
+        }
+
+        impl BitAnd<$clear> for $name {
+            type Output = $name;
+            fn bitand(self, rhs: $clear) -> $name {
+                $name(self.0 & rhs)
+            }
+        }
+
+        impl Shr<$clear> for $name {
+            type Output = $name;
+            fn shr(self, rhs: $clear) -> $name {
+                $name(self.0.wrapping_shr(rhs as u32))
+            }
+        }
+
+        impl ShrAssign<$clear> for $name {
+            fn shr_assign(&mut self, rhs: $clear) {
+                self.0 = self.0.wrapping_shr(rhs as u32);
+            }
+        }
+
+        impl IfThenElse<$name> for SimBool {
+            fn if_then_else(&self, ct_then: &$name, ct_else: &$name) -> $name {
+                match self.0 {
+                    true => *ct_then,
+                    false => *ct_else,
+                }
+            }
+        }
+
+        impl FheUint<$clear> for $name {
+            type Bool = SimBool;
+
+            fn eq(&self, other: $clear) -> SimBool {
+                SimBool(self.0 == other)
+            }
+
+            fn map<F: Fn(u64) -> u64>(&self, func: F) -> Self {
+                $name(func(self.0 as u64) as $clear)
+            }
+
+            fn reveal(&self, _client_key: Option<&ClientKey>) -> Option<$clear> {
+                Some(self.0)
+            }
+        }
+    };
+    (@binary $name:ident, $clear:ty, $tr:ident, $f:ident, $wrapping:ident) => {
+        impl $tr<$clear> for $name {
+            type Output = $name;
+            fn $f(self, rhs: $clear) -> $name {
+                $name(self.0.$wrapping(rhs))
+            }
+        }
+
+        impl<'a> $tr<&'a $name> for $name {
+            type Output = $name;
+            fn $f(self, rhs: &$name) -> $name {
+                $name(self.0.$wrapping(rhs.0))
+            }
+        }
+    };
+}
+
+sim_uint!(SimUint8, u8);
+sim_uint!(SimUint16, u16);
+sim_uint!(SimUint32, u32);
+sim_uint!(SimUint64, u64);
+
+macro_rules! sim_cast {
+    ($from:ident, $to:ident, $to_clear:ty) => {
+        impl CastFrom<$from> for $to {
+            fn cast_from(value: $from) -> $to {
+                $to(value.0 as $to_clear)
+            }
+        }
+    };
+}
+
+sim_cast!(SimUint8, SimUint16, u16);
+sim_cast!(SimUint16, SimUint8, u8);
+sim_cast!(SimUint32, SimUint64, u64);
+sim_cast!(SimUint64, SimUint32, u32);
+
+// Backend computing on clear values
+pub struct Sim;
+
+impl Backend for Sim {
+    type U8 = SimUint8;
+    type U16 = SimUint16;
+    type U32 = SimUint32;
+    type U64 = SimUint64;
+}
+
+// `poseidon_p8_lut` with (r_full, r_partial) rounds on `Sim`
+fn hash_sim(
+    inputs: &[MixedInput<u8, SimUint8>],
+    r_full: usize,
+    r_partial: usize,
+) -> Result<SimUint8> {
+    poseidon_p8::poseidon_p8_lut_mixed_impl::<Sim>(hash::pad_mixed(inputs)?, r_full, r_partial)
+}
+
+// `schnorr::sign_enc` with the `PoseidonP8` hashes of (r_full, r_partial)
+// rounds, simulated on clear values; the message digest is computed as for a
+// public or an encrypted message (`message::hash_message_enc`)
+pub fn sign_sim(
+    x_sch: &SigningKey,
+    msg: &[u8],
+    encrypted_msg: bool,
+    r_full: usize,
+    r_partial: usize,
+) -> Result<Signature> {
+    let _phase = crate::phase!("sign", hash = "simulated PoseidonP8");
+    let hash = |inputs: &[MixedInput<u8, SimUint8>]| hash_sim(inputs, r_full, r_partial);
+    let elems = message::encode_message(msg)?;
+    let d = match encrypted_msg {
+        false => {
+            let d = match (r_full, r_partial) {
+                (2, 1) => message::hash_message_clear::<poseidon_p8::PoseidonP8Rf2Rp1>(&elems)?,
+                _ => message::hash_message_clear::<poseidon_p8::PoseidonP8>(&elems)?,
+            };
+            MixedInput::Clear(d)
+        }
+        true => {
+            let elems: Vec<SimUint8> = elems.into_iter().map(SimUint8).collect();
+            let arity = constants::POSEIDON_T;
+            MixedInput::Enc(message::hash_message_enc::<Sim>(&elems, arity, &hash)?)
+        }
+    };
+    let x_sch = x_sch.expose_secret();
+    let (x_sch, x_sch_up) = (SimUint8(x_sch), SimUint16(x_sch as u16));
+    let sig = schnorr::sign_circuit::<Sim>(&x_sch, &x_sch_up, d, NonceMode::Deterministic, hash)?;
+    Ok(Signature {
+        s: sig.s_enc.0,
+        h: sig.h_enc.0,
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Tfhe;
+    use crate::modint::{ModInt, Modulus};
+    use crate::poseidon;
+    use crate::poseidon_p8::{PoseidonP8, PoseidonP8Rf2Rp1};
+    use crate::utils;
+    use tfhe::{set_server_key, FheUint16, FheUint8};
+
+    #[test]
+    fn test_sim_ops() {
+        // Wraparound at the bit width, as in tfhe
+        assert_eq!(SimUint8(250) + 10, SimUint8(4));
+        assert_eq!(SimUint8(0) - &SimUint8(1), SimUint8(255));
+        assert_eq!(SimUint16(300) * &SimUint16(300), SimUint16(24464));
+        assert_eq!(SimUint64(u64::MAX) + 1, SimUint64(0));
+        assert_eq!(SimUint8(0b1011) >> 9, SimUint8(0b101));
+        let mut x = SimUint32(40);
+        x >>= 3;
+        assert_eq!((x & 3, x % 3), (SimUint32(1), SimUint32(2)));
+
+        let is_five = FheUint::eq(&SimUint8(5), 5);
+        assert_eq!(
+            is_five.if_then_else(&SimUint8(1), &SimUint8(2)),
+            SimUint8(1)
+        );
+        assert_eq!(
+            FheUint::eq(&SimUint8(6), 5).if_then_else(&SimUint8(1), &SimUint8(2)),
+            SimUint8(2)
+        );
+
+        let x: SimUint8 = SimUint16(0x1234).cast_into();
+        assert_eq!(x, SimUint8(0x34));
+        let x: SimUint64 = SimUint32(u32::MAX).cast_into();
+        assert_eq!(x, SimUint64(u32::MAX as u64));
+        assert_eq!(FheUint::map(&SimUint8(100), |v| v * 3), SimUint8(44));
+    }
+
+    #[test]
+    fn test_sim_poseidon() {
+        let inputs = [1u8, 0, 2, 8];
+        let mixed = [
+            MixedInput::Enc(SimUint8(1)),
+            MixedInput::Clear(0),
+            MixedInput::Enc(SimUint8(2)),
+            MixedInput::Clear(8),
+        ];
+        for (r_full, r_partial) in [
+            (2, 1),
+            (constants::POSEIDON_R_FULL, constants::POSEIDON_R_PARTIAL),
+        ] {
+            let expected = poseidon_p8::poseidon_p8_clear_impl(inputs, r_full, r_partial);
+            let lut =
+                poseidon_p8::poseidon_p8_lut_mixed_impl::<Sim>(mixed.clone(), r_full, r_partial);
+            let arith =
+                poseidon_p8::poseidon_p8_arith_mixed_impl::<Sim>(mixed.clone(), r_full, r_partial);
+            let expected = expected.unwrap();
+            assert_eq!((lut.unwrap().0, arith.unwrap().0), (expected, expected));
+        }
+
+        // 32-bit field, through the 64-bit arithmetic
+        let inputs = [1u32, 0, 2, 8];
+        let mixed = inputs.map(|x| MixedInput::Enc(SimUint32(x)));
+        let output = poseidon::poseidon_p32_mixed_impl::<Sim>(
+            mixed,
+            constants::POSEIDON_R_FULL,
+            constants::POSEIDON_R_PARTIAL,
+        );
+        assert_eq!(
+            output.unwrap().0,
+            poseidon::poseidon_p32_clear(inputs).unwrap()
+        );
+    }
+
+    // Square-and-multiply and Montgomery ladder against the clear modexp,
+    // including exp = 0
+    #[test]
+    fn test_sim_modexp() {
+        let p = 199u16;
+        let (modulus_16, modulus_64) = (Modulus::new(p).unwrap(), Modulus::new(p as u64).unwrap());
+        let exps = (0..300u64).chain([0x7fff, 0xffff, 1 << 40, u64::MAX]);
+        for base in [0u16, 1, 2, 57, 198] {
+            let base_16 = ModInt::new(SimUint16(base), modulus_16);
+            let base_64 = ModInt::new(SimUint64(base as u64), modulus_64);
+            for exp in exps.clone() {
+                let expected = utils::clear_modexp_64(base as u64, exp, p as u64);
+                if exp <= u16::MAX as u64 {
+                    let exp_16 = SimUint16(exp as u16);
+                    let result = utils::fhe2_modexp_16(&base_16, &exp_16).into_inner();
+                    assert_eq!(result.0 as u64, expected);
+                    let result = utils::fhe2_modexp_16_ladder(&base_16, &exp_16).into_inner();
+                    assert_eq!(result.0 as u64, expected);
+                }
+                let exp_64 = SimUint64(exp);
+                let result = utils::fhe2_modexp_64(&base_64, &exp_64).into_inner();
+                assert_eq!(result.0, expected);
+                let result = utils::fhe2_modexp_64_ladder(&base_64, &exp_64).into_inner();
+                assert_eq!(result.0, expected);
+            }
+        }
+    }
+
+    // The whole signing pipeline, for every key and both message visibilities
+    #[test]
+    fn test_sign_sim() {
+        let msgs: [&[u8]; 3] = [b"", b"msg", b"an arbitrary byte-string message"];
+        for x_sch in 1..constants::SCH_Q {
+            let x_sch = SigningKey::new(x_sch).unwrap();
+            let y_sch = schnorr::public_key(&x_sch).unwrap();
+            for msg in msgs {
+                for encrypted_msg in [false, true] {
+                    let sig = sign_sim(&x_sch, msg, encrypted_msg, 2, 1).unwrap();
+                    let sig_clear = schnorr::sign_clear::<PoseidonP8Rf2Rp1>(&x_sch, msg);
+                    assert_eq!(sig, sig_clear.unwrap());
+                    assert!(schnorr::verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig).is_ok());
+                }
+            }
+        }
+
+        // Full rounds: as slow as the clear reference
+        let x_sch = SigningKey::new(7).unwrap();
+        let (r_full, r_partial) = (constants::POSEIDON_R_FULL, constants::POSEIDON_R_PARTIAL);
+        let sig = sign_sim(&x_sch, b"msg", true, r_full, r_partial).unwrap();
+        assert_eq!(
+            sig,
+            schnorr::sign_clear::<PoseidonP8>(&x_sch, b"msg").unwrap()
+        );
+
+        // No round constants for r_full = 4
+        assert!(sign_sim(&x_sch, b"msg", true, 4, 1).is_err());
+    }
+
+    // Slow tier: the same circuit on real ciphertexts must match `Sim`
+    #[test]
+    #[ignore]
+    fn test_sim_matches_tfhe() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        set_server_key(server_key);
+
+        // With a hedged nonce, so every input is encrypted
+        let (x_sch, d, aux) = (7u8, 123u8, 45u8);
+        let x_sch_enc = FheUint8::encrypt(x_sch, &client_key);
+        let x_sch_enc_up = FheUint16::encrypt(x_sch as u16, &client_key);
+        let d_enc = MixedInput::Enc(FheUint8::encrypt(d, &client_key));
+        let aux_enc = FheUint8::encrypt(aux, &client_key);
+        let hash_tfhe = |inputs: &[MixedInput<u8, FheUint8>]| {
+            poseidon_p8::poseidon_p8_lut_mixed_impl::<Tfhe>(hash::pad_mixed(inputs)?, 2, 1)
+        };
+        let hedged = NonceMode::Hedged(&aux_enc);
+        let sig =
+            schnorr::sign_circuit::<Tfhe>(&x_sch_enc, &x_sch_enc_up, d_enc, hedged, hash_tfhe)
+                .unwrap()
+                .decrypt(&client_key);
+
+        let hash = |inputs: &[MixedInput<u8, SimUint8>]| hash_sim(inputs, 2, 1);
+        let d_sim = MixedInput::Enc(SimUint8(d));
+        let (x_sch, x_sch_up, aux) = (SimUint8(x_sch), SimUint16(x_sch as u16), SimUint8(aux));
+        let hedged = NonceMode::Hedged(&aux);
+        let sig_sim = schnorr::sign_circuit::<Sim>(&x_sch, &x_sch_up, d_sim, hedged, hash).unwrap();
+        assert_eq!(
+            sig,
+            Signature {
+                s: sig_sim.s_enc.0,
+                h: sig_sim.h_enc.0
+            }
+        );
+    }
+}
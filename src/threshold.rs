@@ -0,0 +1,324 @@
+use rand::rngs::OsRng;
+use rand::Rng;
+use tfhe::prelude::*;
+use tfhe::FheUint16;
+use zeroize::Zeroize;
+
+use crate::backend::Backend;
+use crate::constants;
+use crate::error::{Result, SchnorrFheError};
+use crate::modint::{ModInt, Modulus};
+use crate::signing_key::SigningKey;
+use crate::utils;
+
+/*
+ * Threshold (t-of-n) shares of the Schnorr secret key x_sch, and their
+ * proactive refresh under FHE.
+ *
+ * `share` splits x_sch into Shamir shares x_i = f(i) (mod q) for parties
+ * i = 1..n, with f a random polynomial of degree t - 1 and f(0) = x_sch: any
+ * t shares determine x_sch, fewer reveal nothing about it. Each party sends
+ * its share encrypted, E(x_i), to the coordinator, which combines any t of
+ * them into E(x_sch) = sum_i lambda_i * E(x_i) (mod q) with the public
+ * Lagrange coefficients lambda_i (`combine`), and signs with it as usual.
+ *
+ * For long-lived deployments the shares are refreshed periodically: each
+ * party j contributes a sharing of zero z_ji = g_j(i), with g_j(0) = 0
+ * (`zero_sharing`), encrypted per share, and the coordinator adds them
+ * homomorphically, E(x_i') = E(x_i) + sum_j E(z_ji) (mod q) (`refresh`). The
+ * new shares lie on f + sum_j g_j, which is still x_sch at 0, so y_sch does
+ * not change and signatures from before and after the refresh verify under
+ * the same public key. Shares from before a refresh do not combine with
+ * shares from after it, so an attacker has to get t shares within one period.
+ *
+ * The encrypted shares are 16-bit, for the (mod q) arithmetic (see
+ * `modint.rs`). The party indices are the non-zero elements of Z_q, so with
+ * the 8-bit dev set (q = 11) there are at most 10 parties.
+ */
+
+// Party `index`'s clear share; zeroized on drop
+pub struct KeyShare {
+    index: u8,
+    value: u8,
+}
+
+// Party `index`'s encrypted share; reduced (mod q) by `combine` and
+// `refresh`, since the coordinator cannot check what a party sent
+#[derive(Clone)]
+pub struct EncryptedShare<E = FheUint16> {
+    pub index: u8,
+    pub value: E,
+}
+
+impl KeyShare {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    // The secret value, for the clear reference code only
+    pub fn expose_secret(&self) -> u8 {
+        self.value
+    }
+
+    // E(x_i) under a client or public key
+    pub fn encrypt<E: FheEncrypt<u16, K>, K>(&self, key: &K) -> EncryptedShare<E> {
+        EncryptedShare {
+            index: self.index,
+            value: E::encrypt(self.value as u16, key),
+        }
+    }
+}
+
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+// t-of-n shares of x_sch
+pub fn share(x_sch: &SigningKey, t: usize, n: usize) -> Result<Vec<KeyShare>> {
+    shamir(x_sch.expose_secret(), t, n)
+}
+
+// A party's t-of-n sharing of zero, to refresh the shares
+pub fn zero_sharing(t: usize, n: usize) -> Result<Vec<KeyShare>> {
+    shamir(0, t, n)
+}
+
+// Shares f(1), ..., f(n) of a random f of degree t - 1 with f(0) = secret
+fn shamir(secret: u8, t: usize, n: usize) -> Result<Vec<KeyShare>> {
+    let q = constants::SCH_Q;
+    if t == 0 || t > n || n >= q as usize {
+        return Err(SchnorrFheError::InvalidParams(format!(
+            "cannot share {}-of-{} with q = {}",
+            t, n, q
+        )));
+    }
+    // f(x) = secret + a_1 x + ... + a_{t-1} x^{t-1}, highest degree first
+    let mut coeffs: Vec<u8> = (1..t).map(|_| OsRng.gen_range(0..q)).collect();
+    coeffs.push(secret);
+    let shares = (1..=n as u8)
+        .map(|index| {
+            let value = coeffs
+                .iter()
+                .fold(0u16, |acc, a| (acc * index as u16 + *a as u16) % q as u16);
+            KeyShare {
+                index,
+                value: value as u8,
+            }
+        })
+        .collect();
+    coeffs.zeroize();
+    Ok(shares)
+}
+
+// lambda_i = prod_{j != i} j / (j - i) (mod q), so f(0) = sum_i lambda_i f(i)
+pub fn lagrange_at_zero(indices: &[u8]) -> Result<Vec<u16>> {
+    let q = constants::SCH_Q as u64;
+    for (k, i) in indices.iter().enumerate() {
+        if !(1..q).contains(&(*i as u64)) || indices[..k].contains(i) {
+            return Err(SchnorrFheError::InvalidParams(format!(
+                "bad share index {} in {:?}",
+                i, indices
+            )));
+        }
+    }
+    let lambdas = indices
+        .iter()
+        .map(|i| {
+            let (num, den) =
+                indices
+                    .iter()
+                    .filter(|j| *j != i)
+                    .fold((1u64, 1u64), |(num, den), j| {
+                        let (i, j) = (*i as u64, *j as u64);
+                        (num * j % q, den * ((j + q - i) % q) % q)
+                    });
+            // den^-1 = den^(q - 2) (mod q)
+            (num * utils::clear_modexp_64(den, q - 2, q) % q) as u16
+        })
+        .collect();
+    Ok(lambdas)
+}
+
+// The secret of t or more clear shares
+pub fn combine_clear(shares: &[KeyShare]) -> Result<SigningKey> {
+    let q = constants::SCH_Q as u16;
+    let indices: Vec<u8> = shares.iter().map(|share| share.index).collect();
+    let lambdas = lagrange_at_zero(&indices)?;
+    let x_sch = shares
+        .iter()
+        .zip(lambdas)
+        .fold(0u16, |acc, (share, lambda)| {
+            (acc + share.value as u16 * lambda) % q
+        });
+    SigningKey::new(x_sch as u8)
+}
+
+// E(x_sch) from t or more encrypted shares, e.g. for `Coordinator::new`
+pub fn combine<B: Backend>(shares: &[EncryptedShare<B::U16>]) -> Result<B::U8> {
+    let q_sch = Modulus::new(constants::SCH_Q as u16)?;
+    let indices: Vec<u8> = shares.iter().map(|share| share.index).collect();
+    let lambdas = lagrange_at_zero(&indices)?;
+    let x_sch_enc = shares
+        .iter()
+        .zip(lambdas)
+        .map(|(share, lambda)| ModInt::new(share.value.clone(), q_sch).mul_clear(lambda))
+        .reduce(|acc, term| acc.add(&term))
+        .ok_or_else(|| SchnorrFheError::InvalidParams("no shares to combine".to_string()))?;
+    Ok(x_sch_enc.into_inner().cast_into())
+}
+
+// E(x_i + sum_j z_ji) for every share, from every party's encrypted sharing of
+// zero; each sharing must have a share for every index of `shares`
+pub fn refresh<B: Backend>(
+    shares: &[EncryptedShare<B::U16>],
+    zero_sharings: &[Vec<EncryptedShare<B::U16>>],
+) -> Result<Vec<EncryptedShare<B::U16>>> {
+    let q_sch = Modulus::new(constants::SCH_Q as u16)?;
+    shares
+        .iter()
+        .map(|share| {
+            let mut value = ModInt::new(share.value.clone(), q_sch);
+            for zeros in zero_sharings {
+                let zero = zeros
+                    .iter()
+                    .find(|zero| zero.index == share.index)
+                    .ok_or_else(|| {
+                        SchnorrFheError::InvalidParams(format!(
+                            "sharing of zero without index {}",
+                            share.index
+                        ))
+                    })?;
+                value = value.add(&ModInt::new(zero.value.clone(), q_sch));
+            }
+            Ok(EncryptedShare {
+                index: share.index,
+                value: value.into_inner(),
+            })
+        })
+        .collect()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Tfhe;
+    use crate::coordinator::Coordinator;
+    use crate::parallel::FhePool;
+    use crate::poseidon_p8::PoseidonP8Rf2Rp1;
+    use crate::schnorr::{self, MessageVisibility, NonceMode};
+    use crate::sim::{Sim, SimUint16};
+    use std::sync::Arc;
+    use tfhe::FheUint8;
+
+    fn sim_encrypt(shares: &[KeyShare]) -> Vec<EncryptedShare<SimUint16>> {
+        shares
+            .iter()
+            .map(|share| EncryptedShare {
+                index: share.index(),
+                value: SimUint16(share.expose_secret() as u16),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_share_combine() {
+        for x in 1..constants::SCH_Q {
+            let x_sch = SigningKey::new(x).unwrap();
+            let shares = share(&x_sch, 3, 5).unwrap();
+            // Every 3 of the 5 shares, in the clear and on `Sim`
+            for skip in [[0, 1], [0, 4], [1, 3], [2, 3], [3, 4]] {
+                let subset: Vec<KeyShare> = shares
+                    .iter()
+                    .filter(|share| !skip.contains(&(share.index() as usize - 1)))
+                    .map(|share| KeyShare {
+                        index: share.index(),
+                        value: share.expose_secret(),
+                    })
+                    .collect();
+                assert_eq!(combine_clear(&subset).unwrap().expose_secret(), x);
+                let x_sim = combine::<Sim>(&sim_encrypt(&subset)).unwrap();
+                assert_eq!(x_sim.0, x);
+            }
+        }
+        assert!(share(&SigningKey::new(1).unwrap(), 4, 3).is_err());
+        assert!(share(&SigningKey::new(1).unwrap(), 2, constants::SCH_Q as usize).is_err());
+        assert!(lagrange_at_zero(&[1, 2, 1]).is_err());
+        assert!(lagrange_at_zero(&[0, 2]).is_err());
+    }
+
+    #[test]
+    fn test_refresh_sim() {
+        let x_sch = SigningKey::new(7).unwrap();
+        let shares = sim_encrypt(&share(&x_sch, 2, 4).unwrap());
+        let zero_sharings: Vec<Vec<EncryptedShare<SimUint16>>> = (0..4)
+            .map(|_| sim_encrypt(&zero_sharing(2, 4).unwrap()))
+            .collect();
+        let refreshed = refresh::<Sim>(&shares, &zero_sharings).unwrap();
+        for pair in refreshed.windows(2) {
+            assert_eq!(combine::<Sim>(pair).unwrap().0, 7);
+        }
+        // Shares that are not reduced (mod q) still combine
+        let q = constants::SCH_Q as u16;
+        let unreduced: Vec<EncryptedShare<SimUint16>> = refreshed
+            .iter()
+            .map(|share| EncryptedShare {
+                index: share.index,
+                value: SimUint16(share.value.0 + q),
+            })
+            .collect();
+        assert_eq!(combine::<Sim>(&unreduced[..2]).unwrap().0, 7);
+        let refreshed = refresh::<Sim>(&unreduced, &zero_sharings).unwrap();
+        assert_eq!(combine::<Sim>(&refreshed[2..]).unwrap().0, 7);
+
+        // A sharing of zero without share 4
+        let partial = vec![zero_sharings[0][..3].to_vec()];
+        assert!(refresh::<Sim>(&shares, &partial).is_err());
+    }
+
+    #[test]
+    fn test_refresh_sign() {
+        let (client_key, server_key, _public_key) = utils::init_keys();
+        let (x_sch, y_sch) = schnorr::keygen().unwrap();
+        let shares: Vec<EncryptedShare> = share(&x_sch, 2, 3)
+            .unwrap()
+            .iter()
+            .map(|share| share.encrypt(&client_key))
+            .collect();
+
+        // Sign with shares 1 and 2, refresh, then sign with shares 2 and 3,
+        // on one pool
+        let msg: &[u8] = b"refresh";
+        let pool = Arc::new(FhePool::new(2, &server_key).unwrap());
+        let sign = |x_sch_enc: FheUint8| {
+            Coordinator::<PoseidonP8Rf2Rp1>::with_pool(pool.clone(), x_sch_enc)
+                .sign(MessageVisibility::Public(msg), NonceMode::Deterministic)
+                .unwrap()
+                .decrypt(&client_key)
+        };
+        let sig_before = sign(combine::<Tfhe>(&shares[..2]).unwrap());
+        let zero_sharings: Vec<Vec<EncryptedShare>> = (0..3)
+            .map(|_| {
+                zero_sharing(2, 3)
+                    .unwrap()
+                    .iter()
+                    .map(|zero| zero.encrypt(&client_key))
+                    .collect()
+            })
+            .collect();
+        let refreshed = refresh::<Tfhe>(&shares, &zero_sharings).unwrap();
+        let sig_after = sign(combine::<Tfhe>(&refreshed[1..]).unwrap());
+
+        for sig in [sig_before, sig_after] {
+            assert_eq!(
+                schnorr::verify::<PoseidonP8Rf2Rp1>(y_sch, msg, &sig),
+                Ok(())
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+use std::time::Instant;
+
+use tracing::span::EnteredSpan;
+use tracing::Span;
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use tracing_subscriber::prelude::*;
+
+use crate::cost::{self, OpCounts};
+
+/*
+ * Structured tracing of the signing phases, with `tracing` spans:
+ *   sign > message_hash / nonce_hash / modexp / challenge_hash / s
+ *   poseidon_round (in each Poseidon hash, with the round index)
+ *   decrypt, verify (signer side)
+ *
+ * Each phase span records `duration_ms` when it ends, and `fhe_ops` for the
+ * FHE ops of its own steps. The op count comes from the cost model (see
+ * `cost.rs`): the phase dry-runs the same generic step functions on the
+ * `Counting` backend, in microseconds, and records the count on its span, so
+ * it does not depend on which threads ran what. The ops of nested phases
+ * (e.g. the rounds of a hash) are recorded on those; a parent phase with no
+ * steps of its own (e.g. `sign`) has no `fhe_ops`. The dry runs are skipped
+ * when no subscriber listens. Each step runs through `traced_step`, which
+ * also hands its output to the debug oracle check (see `oracle.rs`).
+ *
+ * `chrome_trace` exports a timeline of all spans as Chrome trace JSON (open it
+ * in Perfetto or chrome://tracing), e.g. for the days-long full-round jobs:
+ * `cargo run --release -- --trace=trace.json`.
+ */
+
+// An entered phase span; records its duration when dropped
+pub struct Phase {
+    span: EnteredSpan,
+    start: Instant,
+}
+
+impl Phase {
+    // `span` must declare the empty fields `duration_ms` and `fhe_ops`; see `phase!`
+    pub fn enter(span: Span) -> Self {
+        Phase {
+            span: span.entered(),
+            start: Instant::now(),
+        }
+    }
+
+    // Record the ops of this phase's own steps, given by a dry run of the
+    // steps on the `Counting` backend (see `cost::count_ops`)
+    pub fn record_ops(&self, count: impl FnOnce() -> OpCounts) {
+        if !self.span.is_disabled() {
+            self.span.record("fhe_ops", count().total());
+        }
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        self.span
+            .record("duration_ms", self.start.elapsed().as_secs_f64() * 1e3);
+    }
+}
+
+// Run one circuit step as `phase`: record on its span the ops of `dry_run`,
+// the same step on the `Counting` backend, then run `step` and pass its output
+// to `check`, e.g. against the clear step of the debug oracle (see
+// `oracle.rs`); the phase ends with the step
+pub fn traced_step<D, R>(
+    phase: Phase,
+    dry_run: impl FnOnce() -> D,
+    step: impl FnOnce() -> R,
+    check: impl FnOnce(&R),
+) -> R {
+    phase.record_ops(|| cost::count_ops(dry_run).1);
+    let output = step();
+    check(&output);
+    output
+}
+
+// Enter an info-level `Phase` span with the given name and extra fields
+#[macro_export]
+macro_rules! phase {
+    ($name:literal $(, $($field:tt)+)?) => {
+        $crate::trace::Phase::enter(tracing::info_span!(
+            $name,
+            duration_ms = tracing::field::Empty,
+            fhe_ops = tracing::field::Empty
+            $(, $($field)+)?
+        ))
+    };
+}
+
+// Install a global subscriber writing all spans to `path` as Chrome trace
+// JSON; the file is complete once the returned guard is dropped
+pub fn chrome_trace(path: impl AsRef<Path>) -> FlushGuard {
+    let (layer, guard) = ChromeLayerBuilder::new()
+        .file(path.as_ref())
+        .include_args(true)
+        .build();
+    tracing_subscriber::registry().with(layer).init();
+    guard
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//////////////////////////////////// Tests ////////////////////////////////////
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+    use crate::cost::{self, Counting, CountingUint8, PoseidonParams, PoseidonVariant};
+    use crate::parallel::{self, FhePool};
+    use crate::poseidon::Lane;
+    use crate::poseidon_p8;
+    use crate::signing_key::SigningKey;
+    use crate::sim;
+    use crate::utils;
+
+    // Trace a simulated signature and check the exported timeline
+    #[test]
+    fn test_chrome_trace() {
+        let path = std::env::temp_dir().join("schnorr_fhe_test_trace.json");
+        let (layer, guard) = ChromeLayerBuilder::new()
+            .file(&path)
+            .include_args(true)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            sim::sign_sim(&SigningKey::new(7).unwrap(), b"msg", true, 2, 1).unwrap();
+        });
+        drop(guard);
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for name in [
+            "\"sign\"",
+            "\"message_hash\"",
+            "\"nonce_hash\"",
+            "\"modexp\"",
+            "\"challenge_hash\"",
+            "\"s\"",
+            "\"poseidon_round\"",
+            "\"duration_ms\"",
+        ] {
+            assert!(trace.contains(name), "{} not in the trace", name);
+        }
+
+        // The wide reduction of the nonce hash (two casts up, * p, +,
+        // (mod q - 1) + 1 and a cast down), and the rounds of its hashes
+        assert!(trace.contains("\"fhe_ops\":\"7\""));
+        let lanes = vec![Lane::Enc(CountingUint8); constants::POSEIDON_T];
+        let rc = [0u16; constants::POSEIDON_T];
+        let (_, counts) =
+            cost::count_ops(|| poseidon_p8::poseidon_p8_lut_round::<Counting>(lanes, &rc, true));
+        assert!(trace.contains(&format!("\"fhe_ops\":\"{}\"", counts.total())));
+    }
+
+    #[test]
+    fn test_phase_ops() {
+        // Without a subscriber the dry runs are skipped
+        let phase = crate::phase!("untraced");
+        phase.record_ops(|| panic!("dry run without a subscriber"));
+
+        // A dry run on a pool counts the ops of all lanes, on this thread
+        let (_client_key, server_key, _public_key) = utils::init_keys();
+        let pool = FhePool::new(4, &server_key).unwrap();
+        let params = PoseidonParams::full(PoseidonVariant::P8Lut);
+        let count = || cost::count_poseidon(&params, 4).unwrap().total();
+        let expected = count();
+        let counts = pool.install(|| parallel::map(vec![(); 8], |_| count()));
+        assert_eq!(counts, vec![expected; 8]);
+    }
+}
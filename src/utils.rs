@@ -3,10 +3,16 @@ use chrono::Local;
 
 use tfhe::prelude::*;
 use tfhe::{
-    generate_keys, ClientKey, CompactPublicKey, ConfigBuilder, FheUint128,
+    generate_keys, ClientKey, CompactPublicKey, FheUint128,
     FheUint16, FheUint32, FheUint64, ServerKey,
 };
 
+use crate::backend;
+use crate::modint::ModInt;
+use crate::oracle;
+use crate::parallel;
+use crate::profile::FheProfile;
+
 // pub fn say_hello() {
 //     println!("Hello from utils!");
 // }
@@ -19,9 +25,13 @@ pub fn log(msg: &str) {
 
 pub fn init_keys() -> (ClientKey, ServerKey, CompactPublicKey) {
     // Generates the client secret key, server key for FHE ops, and the public key for encryption
-    log("Generating keys...");
-    let config = ConfigBuilder::default().build();
-    let (client_key, server_key) = generate_keys(config);
+    init_keys_with(&FheProfile::default())
+}
+
+// The keys of `init_keys` with the parameters of `profile` (see `profile.rs`)
+pub fn init_keys_with(profile: &FheProfile) -> (ClientKey, ServerKey, CompactPublicKey) {
+    log(&format!("Generating keys ({})...", profile.name()));
+    let (client_key, server_key) = generate_keys(profile.config());
     let public_key = CompactPublicKey::new(&client_key);
     log("Generated keys.");
     (client_key, server_key, public_key)
@@ -41,6 +51,22 @@ pub fn init_keys_gpu() -> (ClientKey, ServerKey, CompactPublicKey) {
 }
 */
 
+// Clear modexp on u64 (u128 intermediates never overflow)
+pub fn clear_modexp_64(base: u64, exp: u64, modval: u64) -> u64 {
+    let modval = modval as u128;
+    let mut result: u128 = 1 % modval;
+    let mut base: u128 = base as u128 % modval;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp % 2 == 1 {
+            result = (result * base) % modval;
+        }
+        base = (base * base) % modval;
+        exp /= 2;
+    }
+    result as u64
+}
+
 // Naive exponentiation of a FheUint
 pub fn fhe_exp_128_naive(base: &FheUint128, exp: u128) -> FheUint128 {
     let mut result = base.clone();
@@ -78,9 +104,8 @@ pub fn fhe_exp_64_naive(base: &FheUint64, exp: u64) -> FheUint64 {
 
 pub fn fhe_exp_64(base: &FheUint64, exp: u64) -> FheUint64 {
     if exp == 0 {
-        // If exp is 0 we need to return an FheUint64 with value 1,
-        // but we can't use the FheUint64::encrypt method because it requires a ClientKey
-        panic!("Exponent should be greater than 0");
+        // x^0 = 1, as a trivial (noiseless, public) encryption so no key is needed
+        return FheUint64::encrypt_trivial(1u64);
     }
     let mut result = base.clone();
     let mut exp = exp - 1; // -1 because init result is base
@@ -95,20 +120,24 @@ pub fn fhe_exp_64(base: &FheUint64, exp: u64) -> FheUint64 {
     result
 }
 
-pub fn fhe_modexp_64(base: &FheUint64, exp: u64, modval: u64) -> FheUint64 {
+// Generic over the backend (see `backend.rs`), e.g. with E = FheUint64; the
+// modulus is in `base` (see `modint.rs`)
+pub fn fhe_modexp_64<E: backend::FheUint<u64>>(
+    base: &ModInt<E, u64>,
+    exp: u64,
+) -> ModInt<E, u64> {
     if exp == 0 {
-        // If exp is 0 we need to return an FheUint64 with value 1,
-        // but we can't use the FheUint64::encrypt method because it requires a ClientKey
-        panic!("Exponent should be greater than 0");
+        // x^0 = 1, as a trivial (noiseless, public) encryption so no key is needed
+        return ModInt::trivial(1, base.modulus());
     }
     let mut result = base.clone();
     let mut exp = exp - 1; // -1 because init result is base
     let mut base = base.clone();
     while exp > 0 {
         if exp % 2 == 1 {
-            result = &(&result * &base) % modval;
+            result = result.mul(&base);
         }
-        base = &(&base * &base) % modval;
+        base = base.square();
         exp /= 2;
     }
     result
@@ -125,9 +154,8 @@ pub fn fhe_exp_32_naive(base: &FheUint32, exp: u32) -> FheUint32 {
 
 pub fn fhe_exp_32(base: &FheUint32, exp: u32) -> FheUint32 {
     if exp == 0 {
-        // If exp is 0 we need to return an FheUint32 with value 1,
-        // but we can't use the FheUint32::encrypt method because it requires a ClientKey
-        panic!("Exponent should be greater than 0");
+        // x^0 = 1, as a trivial (noiseless, public) encryption so no key is needed
+        return FheUint32::encrypt_trivial(1u32);
     }
     let mut result = base.clone();
     let mut exp = exp - 1; // -1 because init result is base
@@ -152,9 +180,8 @@ pub fn fhe_exp_16_naive(base: &FheUint16, exp: u16) -> FheUint16 {
 
 pub fn fhe_exp_16(base: &FheUint16, exp: u16) -> FheUint16 {
     if exp == 0 {
-        // If exp is 0 we need to return an FheUint16 with value 1,
-        // but we can't use the FheUint16::encrypt method because it requires a ClientKey
-        panic!("Exponent should be greater than 0");
+        // x^0 = 1, as a trivial (noiseless, public) encryption so no key is needed
+        return FheUint16::encrypt_trivial(1u16);
     }
     let mut result = base.clone();
     let mut exp = exp - 1; // -1 because init result is base
@@ -170,20 +197,24 @@ pub fn fhe_exp_16(base: &FheUint16, exp: u16) -> FheUint16 {
 }
 
 
-pub fn fhe_modexp_16(base: &FheUint16, exp: u16, modval: u16) -> FheUint16 {
+// Generic over the backend (see `backend.rs`), e.g. with E = FheUint16; the
+// modulus is in `base` (see `modint.rs`)
+pub fn fhe_modexp_16<E: backend::FheUint<u16>>(
+    base: &ModInt<E, u16>,
+    exp: u16,
+) -> ModInt<E, u16> {
     if exp == 0 {
-        // If exp is 0 we need to return an FheUint16 with value 1,
-        // but we can't use the FheUint16::encrypt method because it requires a ClientKey
-        panic!("Exponent should be greater than 0");
+        // x^0 = 1, as a trivial (noiseless, public) encryption so no key is needed
+        return ModInt::trivial(1, base.modulus());
     }
     let mut result = base.clone();
     let mut exp = exp - 1; // -1 because init result is base
     let mut base = base.clone();
     while exp > 0 {
         if exp % 2 == 1 {
-            result = &(&result * &base) % modval;
+            result = result.mul(&base);
         }
-        base = &(&base * &base) % modval;
+        base = base.square();
         exp /= 2;
     }
     result
@@ -199,49 +230,135 @@ pub fn fhe_modexp_16(base: &FheUint16, exp: u16, modval: u16) -> FheUint16 {
  * notes
  *   - for `fhe_exp(base: fhe, exp: fhe) -> fhe`, you will just have to
  *     operate without considerating for overflow (around the FheUint bitwidth)
- *   - for `fhe_exp(base: fhe, exp: fhe, modval: clear) -> fhe`, we will assume
- *     that the multiplications do NOT overflow the bitwidth of the FheUint
- *     (or else `% modval` wont be correct)
- *   - this means that for the exp impl, we won't worry about overflow,
- *     but the input FheUint should be large enough to handle the result
+ *   - for `fhe_exp(base: fhe, exp: fhe, modval: clear) -> fhe`, the
+ *     multiplications must NOT overflow the bitwidth of the FheUint (or else
+ *     `% modval` wont be correct); the base is a `ModInt`, whose modulus is
+ *     checked against the bitwidth when it is built (see `modint.rs`)
  */
 
-pub fn fhe2_modexp_64(base_enc: &FheUint64, exp_enc: &FheUint64, modval: u64) -> FheUint64 {
+// Square-and-multiply from a trivial FHE(1), so exp = 0 gives 1; within an
+// `FhePool` (see `parallel.rs`), the squaring of `base_accum` for the next
+// iteration runs concurrently with the conditional multiply into `result`
+pub fn fhe2_modexp_64<E: backend::FheUint<u64>>(
+    base_enc: &ModInt<E, u64>,
+    exp_enc: &E,
+) -> ModInt<E, u64> {
     // Fast exponentiation of FheUint64 with FheUint64 exponent
-    // NOTE: assumes that the multiplications do not overflow the bitwidth of the FheUint
-    // NOTE: assumes that exp != 0, or else we need to return FHE(1) but public key is required
-    let mut result = base_enc.clone();
+    let modval = base_enc.modulus().value();
+    let mut result = ModInt::trivial(1, base_enc.modulus());
     let mut base_accum = base_enc.clone();
-    let mut exp_enc = exp_enc - 1;
-
-    for _i in 0..64 {
-        let is_even = (&exp_enc & 1).eq(1);
-        let result_if_even = (&result * &base_accum) % modval;
-        result = is_even.if_then_else(&result_if_even, &result);
-        base_accum = (&base_accum * &base_accum) % modval;
+    let mut exp_enc = exp_enc.clone();
+
+    for i in 0..64 {
+        let clear = reveal_modexp_state(result.value(), base_accum.value(), &exp_enc);
+        let is_odd = (exp_enc.clone() & 1).eq(1);
+        let (result_if_odd, next_base_accum) =
+            parallel::join(|| result.mul(&base_accum), || base_accum.square());
+        result = ModInt::select(&is_odd, &result_if_odd, &result);
+        base_accum = next_base_accum;
         exp_enc >>= 1u64;
+        let (result, base_accum) = (result.value(), base_accum.value());
+        check_modexp_step("fhe2_modexp_64", i, result, base_accum, clear, modval);
     }
     result
 }
 
-pub fn fhe2_modexp_16(base_enc: &FheUint16, exp_enc: &FheUint16, modval: u16) -> FheUint16 {
+pub fn fhe2_modexp_16<E: backend::FheUint<u16>>(
+    base_enc: &ModInt<E, u16>,
+    exp_enc: &E,
+) -> ModInt<E, u16> {
     // Fast exponentiation of FheUint16 with FheUint16 exponent
-    // NOTE: assumes that the multiplications do not overflow the bitwidth of the FheUint
-    // NOTE: assumes that exp != 0, or else we need to return FHE(1) but public key is required
-    let mut result = base_enc.clone();
+    let modval = base_enc.modulus().value();
+    let mut result = ModInt::trivial(1, base_enc.modulus());
     let mut base_accum = base_enc.clone();
-    let mut exp_enc = exp_enc - 1;
-
-    for _i in 0..16 {
-        let is_even = (&exp_enc & 1).eq(1);
-        let result_if_even = (&result * &base_accum) % modval;
-        result = is_even.if_then_else(&result_if_even, &result);
-        base_accum = (&base_accum * &base_accum) % modval;
+    let mut exp_enc = exp_enc.clone();
+
+    for i in 0..16 {
+        let clear = reveal_modexp_state(result.value(), base_accum.value(), &exp_enc);
+        let is_odd = (exp_enc.clone() & 1).eq(1);
+        let (result_if_odd, next_base_accum) =
+            parallel::join(|| result.mul(&base_accum), || base_accum.square());
+        result = ModInt::select(&is_odd, &result_if_odd, &result);
+        base_accum = next_base_accum;
         exp_enc >>= 1u16;
+        let (result, base_accum) = (result.value(), base_accum.value());
+        check_modexp_step("fhe2_modexp_16", i, result, base_accum, clear, modval as u64);
     }
     result
 }
 
+// Montgomery ladder from the most significant bit, keeping (r0, r1) =
+// (b^e', b^(e' + 1)) for the exponent prefix e'. Every bit costs one multiply
+// and one squaring (concurrent within an `FhePool`) and the same selects,
+// whatever its value, so the op count does not depend on the exponent
+pub fn fhe2_modexp_64_ladder<E: backend::FheUint<u64>>(
+    base_enc: &ModInt<E, u64>,
+    exp_enc: &E,
+) -> ModInt<E, u64> {
+    let mut r0 = ModInt::trivial(1, base_enc.modulus());
+    let mut r1 = base_enc.clone();
+
+    for i in (0..64u64).rev() {
+        let bit = ((exp_enc.clone() >> i) & 1).eq(1);
+        // bit = 0: (r0, r1) <- (r0^2, r0 * r1); bit = 1: (r0, r1) <- (r0 * r1, r1^2)
+        let to_square = ModInt::select(&bit, &r1, &r0);
+        let (prod, square) = parallel::join(|| r0.mul(&r1), || to_square.square());
+        r0 = ModInt::select(&bit, &prod, &square);
+        r1 = ModInt::select(&bit, &square, &prod);
+    }
+    r0
+}
+
+pub fn fhe2_modexp_16_ladder<E: backend::FheUint<u16>>(
+    base_enc: &ModInt<E, u16>,
+    exp_enc: &E,
+) -> ModInt<E, u16> {
+    let mut r0 = ModInt::trivial(1, base_enc.modulus());
+    let mut r1 = base_enc.clone();
+
+    for i in (0..16u16).rev() {
+        let bit = ((exp_enc.clone() >> i) & 1).eq(1);
+        // bit = 0: (r0, r1) <- (r0^2, r0 * r1); bit = 1: (r0, r1) <- (r0 * r1, r1^2)
+        let to_square = ModInt::select(&bit, &r1, &r0);
+        let (prod, square) = parallel::join(|| r0.mul(&r1), || to_square.square());
+        r0 = ModInt::select(&bit, &prod, &square);
+        r1 = ModInt::select(&bit, &square, &prod);
+    }
+    r0
+}
+
+// Clear (result, base_accum, exp) before an iteration of `fhe2_modexp_*`, if
+// a debug oracle is active (see `oracle.rs`)
+fn reveal_modexp_state<C: Into<u64>, E: backend::FheUint<C>>(
+    result: &E,
+    base_accum: &E,
+    exp: &E,
+) -> Option<(u64, u64, u64)> {
+    let result = oracle::reveal(result)?.into();
+    let base_accum = oracle::reveal(base_accum)?.into();
+    Some((result, base_accum, oracle::reveal(exp)?.into()))
+}
+
+// Check an iteration of `fhe2_modexp_*` against the clear iteration on
+// `clear`, in u128 arithmetic
+fn check_modexp_step<C: Into<u64>, E: backend::FheUint<C>>(
+    phase: &'static str,
+    i: usize,
+    result: &E,
+    base_accum: &E,
+    clear: Option<(u64, u64, u64)>,
+    modval: u64,
+) {
+    let modval = modval as u128;
+    let expected = clear.map(|(result, base_accum, exp)| {
+        let (result, base_accum) = (result as u128, base_accum as u128);
+        let result = if exp & 1 == 1 { result * base_accum % modval } else { result };
+        (result as u64, (base_accum * base_accum % modval) as u64)
+    });
+    oracle::check(phase, Some(i), result, expected.map(|e| e.0));
+    oracle::check(phase, Some(i), base_accum, expected.map(|e| e.1));
+}
+
 
 pub fn fhe2_modexp_64_test(base_enc: u64, exp_enc: u64, modval: u64) -> u64 {
     // Fast exponentiation of FheUint64 with FheUint64 exponent
@@ -269,9 +386,20 @@ pub fn fhe2_modexp_64_test(base_enc: u64, exp_enc: u64, modval: u64) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modint::Modulus;
     use num::BigUint;
     use num_traits::ToPrimitive;
-    use tfhe::set_server_key;
+    use tfhe::{set_server_key, FheUint8};
+
+    // The keys must evaluate the lookup tables of `lut.rs`
+    #[test]
+    fn test_init_keys_lut() {
+        let (client_key, server_key, _public_key) = init_keys();
+        set_server_key(server_key);
+        let x = FheUint8::encrypt(230u8, &client_key);
+        let y: u8 = x.map(|v| v % 199).decrypt(&client_key);
+        assert_eq!(y, 31);
+    }
 
     #[test]
     fn test_fhe_exp() {
@@ -311,14 +439,55 @@ mod tests {
         let base = 3u16;
         let exp = 10u16;
         let modval = 101u16;
-        let base_enc = FheUint16::encrypt(base, &client_key);
+        let modulus = Modulus::new(modval).unwrap();
+        let base_enc = ModInt::new(FheUint16::encrypt(base, &client_key), modulus);
         let exp_enc = FheUint16::encrypt(exp, &client_key);
-        let result = fhe2_modexp_16(&base_enc, &exp_enc, modval);
+        let result = fhe2_modexp_16(&base_enc, &exp_enc).into_inner();
         let decrypted: u16 = result.decrypt(&client_key);
         let clear_result: u16 = base.pow(exp as u32) % modval;
         assert_eq!(decrypted, clear_result);
     }
 
+    // Square-and-multiply and ladder, in and out of an `FhePool`, including
+    // exp = 0
+    #[test]
+    fn test_fhe2_modexp_pool() {
+        let (client_key, server_key, _public_key) = init_keys();
+        set_server_key(server_key.clone());
+        let pool = parallel::FhePool::new(2, &server_key).unwrap();
+        let modval = 101u16;
+        let modulus_16 = Modulus::new(modval).unwrap();
+        let modulus_64 = Modulus::new(modval as u64).unwrap();
+        for (base, exp) in [(3u16, 10u16), (2u16, 1u16), (57u16, 0u16), (5u16, 65535u16)] {
+            let base_enc = ModInt::new(FheUint16::encrypt(base, &client_key), modulus_16);
+            let exp_enc = FheUint16::encrypt(exp, &client_key);
+            let expected = clear_modexp_64(base as u64, exp as u64, modval as u64) as u16;
+
+            let result: u16 = pool
+                .install(|| fhe2_modexp_16(&base_enc, &exp_enc))
+                .into_inner()
+                .decrypt(&client_key);
+            assert_eq!(result, expected);
+            let result: u16 = pool
+                .install(|| fhe2_modexp_16_ladder(&base_enc, &exp_enc))
+                .into_inner()
+                .decrypt(&client_key);
+            assert_eq!(result, expected);
+
+            let base_enc = ModInt::new(FheUint64::encrypt(base as u64, &client_key), modulus_64);
+            let exp_enc = FheUint64::encrypt(exp as u64, &client_key);
+            let result: u64 = pool
+                .install(|| fhe2_modexp_64(&base_enc, &exp_enc))
+                .into_inner()
+                .decrypt(&client_key);
+            assert_eq!(result, expected as u64);
+            let result: u64 = fhe2_modexp_64_ladder(&base_enc, &exp_enc)
+                .into_inner()
+                .decrypt(&client_key);
+            assert_eq!(result, expected as u64);
+        }
+    }
+
     #[test]
     fn test_fhe2_exp_toy() {
         let (client_key, server_key, public_key) = init_keys();
@@ -412,7 +581,6 @@ mod tests {
 
     #[test]
     fn test_fhe2_modexp_clear() {
-        // FIXME / TODO: Test failing!!!
         let g_sch: u64 = 3196401078;
         let k_sch: u64 = 28192;
         let p_sch: u64 = 3552575077;
@@ -421,7 +589,7 @@ mod tests {
         let p_biguint: BigUint = BigUint::from(p_sch);
         let r_biguint = g_biguint.modpow(&k_biguint, &p_biguint);
         let r_sch: u64 = r_biguint.to_u64().expect("r_sch is too large");
-        let r_true: u64 = 3196401078;
+        let r_true: u64 = 2070203715;
         let r_calc: u64 = fhe2_modexp_64_test(g_sch, k_sch, p_sch);
 
         assert_eq!(r_true, r_sch);